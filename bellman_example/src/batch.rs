@@ -0,0 +1,92 @@
+//! Batch verification of many proofs against one verifying key.
+//!
+//! `verify_proofs_batch` checks all proofs with a single randomized pairing
+//! product, which is much cheaper than one check per proof, but only says
+//! whether *all* of them are valid. When a batch fails it is split in halves
+//! and each half checked again until the invalid proofs are isolated, so a
+//! few bad proofs cost O(bad * log n) extra batch checks.
+
+use std::time::{Duration, Instant};
+
+use bellperson::{
+    groth16::{self, PreparedVerifyingKey, Proof},
+    SynthesisError,
+};
+use paired::Engine;
+use rand::RngCore;
+
+pub struct BatchReport {
+    /// Indices of the invalid proofs.
+    pub invalid: Vec<usize>,
+    /// How many batch checks were needed (1 if all proofs are valid).
+    pub checks: usize,
+    pub elapsed: Duration,
+}
+
+/// Verifies `proofs` (each with its public inputs) in batches, bisecting
+/// failing batches to find the invalid ones.
+pub fn verify<E: Engine, R: RngCore>(
+    pvk: &PreparedVerifyingKey<E>,
+    proofs: &[(&Proof<E>, Vec<E::Fr>)],
+    rng: &mut R,
+) -> Result<BatchReport, SynthesisError> {
+    let start = Instant::now();
+    let mut report = BatchReport {
+        invalid: Vec::new(),
+        checks: 0,
+        elapsed: Duration::default(),
+    };
+
+    if !proofs.is_empty() {
+        bisect(pvk, proofs, 0, rng, &mut report)?;
+    }
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+fn bisect<E: Engine, R: RngCore>(
+    pvk: &PreparedVerifyingKey<E>,
+    proofs: &[(&Proof<E>, Vec<E::Fr>)],
+    offset: usize,
+    rng: &mut R,
+    report: &mut BatchReport,
+) -> Result<(), SynthesisError> {
+    let batch: Vec<_> = proofs.iter().map(|&(proof, _)| proof).collect();
+    let inputs: Vec<_> = proofs.iter().map(|(_, inputs)| inputs.clone()).collect();
+
+    report.checks += 1;
+    if groth16::verify_proofs_batch(pvk, rng, &batch, &inputs)? {
+        return Ok(());
+    }
+
+    if proofs.len() == 1 {
+        report.invalid.push(offset);
+        return Ok(());
+    }
+
+    let mid = proofs.len() / 2;
+    bisect(pvk, &proofs[..mid], offset, rng, report)?;
+    bisect(pvk, &proofs[mid..], offset + mid, rng, report)
+}
+
+/// Verifies `proofs` one at a time, returning the indices of the invalid
+/// ones and the time taken (the baseline batching is compared against).
+pub fn verify_each<E: Engine>(
+    pvk: &PreparedVerifyingKey<E>,
+    proofs: &[(&Proof<E>, Vec<E::Fr>)],
+) -> Result<(Vec<usize>, Duration), SynthesisError> {
+    let start = Instant::now();
+    let mut invalid = Vec::new();
+
+    for (idx, (proof, inputs)) in proofs.iter().enumerate() {
+        if !groth16::verify_proof(pvk, proof, inputs)? {
+            invalid.push(idx);
+        }
+    }
+    Ok((invalid, start.elapsed()))
+}
+
+/// Proofs per second.
+pub fn throughput(count: usize, elapsed: Duration) -> f64 {
+    count as f64 / elapsed.as_secs_f64().max(std::f64::EPSILON)
+}
@@ -0,0 +1,268 @@
+//! Where proving time goes, on the CPU and on the GPU.
+//!
+//! For each circuit size this times, separately:
+//!
+//! - synthesis: building the constraint system with the witness;
+//! - FFT: one FFT over the evaluation domain the prover uses (it performs
+//!   seven per proof);
+//! - multiexp: one multiexp over as many random G1 bases as the circuit has
+//!   aux variables (the prover's largest);
+//! - prove: a whole `create_random_proof`.
+//!
+//! Synthesis is timed with `CircuitShape::with_witness`, which computes the
+//! witness values and linear combinations the prover does but keeps none of
+//! them. The FFT and multiexp are bellperson's own, run on random data of
+//! the sizes the prover would use.
+//!
+//! Each is timed in CPU mode and, in builds with the `gpu` feature on
+//! machines with a GPU, in GPU mode; builds without the feature (or machines
+//! without a GPU) only have the CPU run. The report is JSON so that runs of
+//! builds with and without the feature can be tracked over time.
+
+use std::env;
+use std::io;
+use std::sync::Arc;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bellperson::domain::{EvaluationDomain, Scalar};
+use bellperson::gpu::{LockedFFTKernel, LockedMultiexpKernel};
+use bellperson::multicore::Worker;
+use bellperson::multiexp::{multiexp, FullDensity};
+use bellperson::{groth16, SynthesisError};
+use ff::{Field, PrimeField};
+use futures::Future;
+use groupy::CurveProjective;
+use paired::bls12_381::{Bls12, Fr, G1};
+use serde::Serialize;
+
+use crate::circuit::{CircuitConfig, Witness};
+use crate::rng::ProofRng;
+use crate::shape::CircuitShape;
+
+/// Set to make bellperson prove on the CPU even in `gpu` builds.
+const NO_GPU_VAR: &str = "BELLMAN_NO_GPU";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Cpu,
+    Gpu,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Mode, String> {
+        match s {
+            "cpu" => Ok(Mode::Cpu),
+            "gpu" => Ok(Mode::Gpu),
+            _ => Err(format!("Unknown mode '{}' (cpu or gpu).", s)),
+        }
+    }
+}
+
+/// Median times in milliseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Timings {
+    pub synthesis_ms: f64,
+    pub fft_ms: f64,
+    pub multiexp_ms: f64,
+    pub prove_ms: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchResult {
+    pub circuit: String,
+    pub constraints: u64,
+    pub aux: u64,
+    /// log2 of the evaluation domain size.
+    pub log_domain: usize,
+    pub mode: Mode,
+    pub timings: Timings,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchReport {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub gpu_feature: bool,
+    pub gpu_devices: usize,
+    pub iterations: usize,
+    pub results: Vec<BenchResult>,
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Runs `f` `iterations` times, returning the median time.
+fn median<F>(iterations: usize, mut f: F) -> io::Result<f64>
+where
+    F: FnMut() -> io::Result<Duration>,
+{
+    let mut times = (0..iterations.max(1))
+        .map(|_| f().map(ms))
+        .collect::<Result<Vec<_>, _>>()?;
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Ok(times[times.len() / 2])
+}
+
+fn synthesis_error(err: SynthesisError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+/// The OpenCL platforms bellperson's kernels run on.
+#[cfg(feature = "gpu")]
+const GPU_PLATFORMS: &[&str] = &[
+    bellperson::gpu::GPU_NVIDIA_PLATFORM_NAME,
+    "AMD Accelerated Parallel Processing",
+];
+
+#[cfg(feature = "gpu")]
+fn gpu_devices() -> usize {
+    GPU_PLATFORMS
+        .iter()
+        .map(|name| bellperson::gpu::get_devices(name).map(|devices| devices.len()).unwrap_or(0))
+        .sum()
+}
+
+#[cfg(not(feature = "gpu"))]
+fn gpu_devices() -> usize {
+    0
+}
+
+fn time_fft(log_d: usize, mode: Mode, rng: &mut ProofRng) -> io::Result<Duration> {
+    let coeffs = (0..1 << log_d).map(|_| Scalar::<Bls12>(Fr::random(&mut *rng))).collect();
+    let mut domain = EvaluationDomain::from_coeffs(coeffs).map_err(synthesis_error)?;
+    let worker = Worker::new();
+    let mut kern = match mode {
+        Mode::Gpu => Some(LockedFFTKernel::<Bls12>::new(log_d, false)),
+        Mode::Cpu => None,
+    };
+
+    let start = Instant::now();
+    domain.fft(&worker, &mut kern).map_err(|err| {
+        io::Error::new(io::ErrorKind::Other, format!("FFT failed: {}", err))
+    })?;
+    Ok(start.elapsed())
+}
+
+fn time_multiexp(
+    len: usize,
+    log_d: usize,
+    mode: Mode,
+    rng: &mut ProofRng,
+) -> io::Result<Duration> {
+    let bases: Vec<_> = (0..len).map(|_| G1::random(&mut *rng).into_affine()).collect();
+    let exponents: Vec<_> = (0..len).map(|_| Fr::random(&mut *rng).into_repr()).collect();
+    let (bases, exponents) = (Arc::new(bases), Arc::new(exponents));
+    let worker = Worker::new();
+    let mut kern = match mode {
+        Mode::Gpu => Some(LockedMultiexpKernel::<Bls12>::new(log_d, false)),
+        Mode::Cpu => None,
+    };
+
+    let start = Instant::now();
+    multiexp(&worker, (bases, 0), FullDensity, exponents, &mut kern)
+        .wait()
+        .map_err(synthesis_error)?;
+    Ok(start.elapsed())
+}
+
+fn bench_circuit(
+    config: CircuitConfig,
+    modes: &[Mode],
+    iterations: usize,
+) -> io::Result<Vec<BenchResult>> {
+    // Fixed randomness: the numbers only need to be comparable between runs.
+    let mut rng = ProofRng::new(Some(0));
+    let witness = Witness {
+        prefix: vec![1; config.prefix_len],
+        preimage: vec![42; config.preimage_len],
+        suffix: vec![2; config.suffix_len],
+    };
+
+    let shape = CircuitShape::of::<Bls12, _>(config.blank()).map_err(synthesis_error)?;
+    let log_d = ((shape.constraints + shape.inputs) as f64).log2().ceil() as usize;
+
+    eprintln!("{}: generating parameters ({} constraints).", config, shape.constraints);
+    let params = groth16::generate_random_parameters::<Bls12, _, _>(config.blank(), &mut rng)
+        .map_err(synthesis_error)?;
+
+    let synthesis_ms = median(iterations, || {
+        let start = Instant::now();
+        CircuitShape::with_witness::<Bls12, _>(config.circuit(witness.clone()))
+            .map_err(synthesis_error)?;
+        Ok(start.elapsed())
+    })?;
+
+    let mut results = Vec::new();
+    for &mode in modes {
+        eprintln!("{}: benchmarking on the {:?}.", config, mode);
+        match mode {
+            Mode::Cpu => env::set_var(NO_GPU_VAR, "1"),
+            Mode::Gpu => env::remove_var(NO_GPU_VAR),
+        }
+
+        let timings = Timings {
+            synthesis_ms,
+            fft_ms: median(iterations, || time_fft(log_d, mode, &mut rng))?,
+            multiexp_ms: median(iterations, || {
+                time_multiexp(shape.aux as usize, log_d, mode, &mut rng)
+            })?,
+            prove_ms: median(iterations, || {
+                let start = Instant::now();
+                groth16::create_random_proof(config.circuit(witness.clone()), &params, &mut rng)
+                    .map_err(synthesis_error)?;
+                Ok(start.elapsed())
+            })?,
+        };
+
+        results.push(BenchResult {
+            circuit: config.to_string(),
+            constraints: shape.constraints,
+            aux: shape.aux,
+            log_domain: log_d,
+            mode,
+            timings,
+        });
+    }
+    env::remove_var(NO_GPU_VAR);
+    Ok(results)
+}
+
+/// Benchmarks every circuit in each of `modes` (default: both). GPU runs
+/// are dropped, with a warning, if the build or the machine has no GPU.
+pub fn run(
+    circuits: &[CircuitConfig],
+    iterations: usize,
+    modes: Option<&[Mode]>,
+) -> io::Result<BenchReport> {
+    let gpu_devices = gpu_devices();
+    let mut modes = modes.unwrap_or(&[Mode::Cpu, Mode::Gpu]).to_vec();
+    if modes.contains(&Mode::Gpu) && gpu_devices == 0 {
+        if cfg!(feature = "gpu") {
+            eprintln!("No GPU found, benchmarking the CPU only.");
+        } else {
+            eprintln!("Built without the 'gpu' feature, benchmarking the CPU only.");
+        }
+        modes = vec![Mode::Cpu];
+    }
+
+    let mut results = Vec::new();
+    for &config in circuits {
+        results.extend(bench_circuit(config, &modes, iterations)?);
+    }
+
+    Ok(BenchReport {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        gpu_feature: cfg!(feature = "gpu"),
+        gpu_devices,
+        iterations,
+        results,
+    })
+}
@@ -0,0 +1,306 @@
+//! Multi-party trusted setup (phase 2) for our circuits.
+//!
+//! `generate_random_parameters` knows the toxic waste of the parameters it
+//! makes, so whoever runs it can forge proofs. In a phase 2 ceremony the
+//! circuit-specific part of the parameters (`delta`) is instead built from
+//! the contributions of several participants, each multiplying in their own
+//! secret and destroying it: the result is sound as long as one participant
+//! was honest. The circuit-independent part comes from a phase 1 "powers of
+//! tau" ceremony, whose `phase1radix2m*` files must be in the working
+//! directory when the ceremony is started.
+//!
+//! Each contribution has a hash, which the participant publishes. The
+//! transcript file lists them in order, along with the SHA-256 of each
+//! resulting parameter file body, and `verify` checks the whole chain against
+//! it before the final parameters are extracted.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use paired::bls12_381::Bls12;
+use phase21::{verify_contribution, MPCParameters};
+
+use crate::circuit::CircuitConfig;
+use crate::params::{self, Header, Kind};
+use crate::proof::to_hex;
+use crate::rng::{self, ProofRng};
+use crate::shape::CircuitShape;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn shape(config: CircuitConfig) -> io::Result<CircuitShape> {
+    CircuitShape::of::<Bls12, _>(config.blank())
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+}
+
+/// SHA-256 of a ceremony file's body (from its header), as recorded in the
+/// transcript.
+fn body_hash(path: &Path) -> io::Result<String> {
+    Ok(to_hex(&params::read_header(path)?.hash))
+}
+
+/// Writes a ceremony file, flagged `insecure` if any contribution so far
+/// used a seeded RNG.
+fn write(
+    path: &Path,
+    config: CircuitConfig,
+    params: &MPCParameters,
+    insecure: bool,
+) -> io::Result<()> {
+    let header = Header {
+        kind: Kind::Ceremony,
+        circuit_id: config.to_string(),
+        shape: shape(config)?,
+        insecure,
+        hash: [0; 32],
+    };
+    params::write_file(path, header, |writer| params.write(writer))
+}
+
+/// Reads a ceremony file, returning the circuit it is for and whether it is
+/// flagged insecure (warning if so).
+pub fn read(path: &Path) -> io::Result<(CircuitConfig, MPCParameters, bool)> {
+    let header = params::read_header(path)?;
+    let config: CircuitConfig = header.circuit_id.parse().map_err(invalid)?;
+    if header.insecure {
+        rng::warn_insecure(&format!("'{}' has contributions", path.display()));
+    }
+    let shape = shape(config)?;
+    let params = params::read_file(path, Kind::Ceremony, &config.to_string(), &shape, |body| {
+        MPCParameters::read(body, true)
+    })?;
+    Ok((config, params, header.insecure))
+}
+
+/// One line of the transcript: a contribution's hash and the body hash of
+/// the file it produced.
+struct Entry {
+    contribution: String,
+    file: String,
+}
+
+fn read_transcript(path: &Path) -> io::Result<Vec<Entry>> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, contribution, file] => Ok(Entry {
+                    contribution: contribution.to_string(),
+                    file: file.to_string(),
+                }),
+                _ => Err(invalid(format!("Malformed transcript line: '{}'", line))),
+            }
+        })
+        .collect()
+}
+
+fn append_transcript(path: &Path, idx: usize, entry: &Entry) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{} {} {}", idx, entry.contribution, entry.file)
+}
+
+/// Starts a ceremony for `config` (from the phase 1 files in the working
+/// directory) and starts its transcript, which must not exist unless
+/// `force`.
+pub fn new(config: CircuitConfig, out: &Path, transcript: &Path, force: bool) -> io::Result<()> {
+    if transcript.exists() && !force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "The transcript '{}' already exists (use --force to overwrite it).",
+                transcript.display()
+            ),
+        ));
+    }
+    let params = MPCParameters::new(config.blank())
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+    write(out, config, &params, false)?;
+
+    fs::write(
+        transcript,
+        format!(
+            "# Phase 2 ceremony for '{}': <contribution> <contribution hash> <file body hash>\n\
+             0 - {}\n",
+            config,
+            body_hash(out)?
+        ),
+    )?;
+    println!(
+        "Started the ceremony for '{}' in '{}' (transcript: '{}').",
+        config,
+        out.display(),
+        transcript.display()
+    );
+    Ok(())
+}
+
+/// Adds a contribution to the parameters in `input`, writing them to `out`
+/// and appending the contribution to the transcript.
+pub fn contribute(
+    input: &Path,
+    out: &Path,
+    transcript: &Path,
+    seed: Option<u64>,
+) -> io::Result<()> {
+    let (config, mut params, insecure) = read(input)?;
+    let entries = read_transcript(transcript)?;
+    if entries.last().map(|entry| &entry.file) != Some(&body_hash(input)?) {
+        return Err(invalid(format!(
+            "'{}' is not the latest file of the transcript '{}'.",
+            input.display(),
+            transcript.display()
+        )));
+    }
+
+    let mut rng = ProofRng::new(seed);
+    if rng.is_seeded() {
+        rng::warn_insecure("Contributing");
+    }
+    let hash = params.contribute(&mut rng);
+
+    write(out, config, &params, insecure || rng.is_seeded())?;
+    let entry = Entry {
+        contribution: to_hex(&hash),
+        file: body_hash(out)?,
+    };
+    append_transcript(transcript, entries.len(), &entry)?;
+
+    println!("Contribution {} to '{}': {}", entries.len(), config, entry.contribution);
+    println!("Publish this hash so that others can check it is in the final parameters.");
+    Ok(())
+}
+
+/// Checks that `after` is `before` with one more valid contribution,
+/// returning its hash.
+pub fn verify_step(before: &Path, after: &Path) -> io::Result<String> {
+    let (before_config, before, _) = read(before)?;
+    let (after_config, after, _) = read(after)?;
+    if before_config != after_config {
+        return Err(invalid(format!(
+            "The files are for '{}' and '{}'.",
+            before_config, after_config
+        )));
+    }
+
+    verify_contribution(&before, &after)
+        .map(|hash| to_hex(&hash))
+        .map_err(|_| invalid("The contribution is invalid.".to_owned()))
+}
+
+/// Checks every contribution in `path` against its circuit and the
+/// transcript. Returns `false` if the chain is invalid.
+pub fn verify(path: &Path, transcript: &Path) -> io::Result<bool> {
+    let (config, params, _) = read(path)?;
+    let entries = read_transcript(transcript)?;
+
+    let hashes = match params.verify(config.blank()) {
+        Ok(hashes) => hashes,
+        Err(_) => {
+            println!("Invalid: the parameters are not the result of valid contributions.");
+            return Ok(false);
+        }
+    };
+
+    // The first transcript entry is the initial file, without contribution:
+    let recorded: Vec<_> = entries.iter().skip(1).map(|entry| &entry.contribution).collect();
+    let mut valid = hashes.len() == recorded.len();
+    for (idx, hash) in hashes.iter().enumerate() {
+        let hash = to_hex(hash);
+        let ok = recorded.get(idx) == Some(&&hash);
+        valid &= ok;
+        let status = if ok { "ok" } else { "NOT IN TRANSCRIPT" };
+        println!("Contribution {}: {} {}", idx + 1, hash, status);
+    }
+    if entries.last().map(|entry| &entry.file) != Some(&body_hash(path)?) {
+        println!("'{}' is not the latest file of the transcript.", path.display());
+        valid = false;
+    }
+
+    if valid {
+        println!("Valid: {} contributions to '{}'.", hashes.len(), config);
+    } else {
+        println!("Invalid: the contributions don't match the transcript.");
+    }
+    Ok(valid)
+}
+
+/// Verifies the ceremony and writes its final parameters and verifying key
+/// for use with `prove` and `verify`. Returns `false` if the chain is
+/// invalid.
+pub fn finalize(
+    path: &Path,
+    transcript: &Path,
+    params_out: &Path,
+    vk_out: &Path,
+) -> io::Result<bool> {
+    if !verify(path, transcript)? {
+        return Ok(false);
+    }
+
+    let (config, mpc, insecure) = read(path)?;
+    let shape = shape(config)?;
+    let groth_params = mpc.get_params();
+    let id = config.to_string();
+    params::write_params(params_out, &id, shape, insecure, groth_params)?;
+    params::write_vk(vk_out, &id, shape, insecure, &groth_params.vk)?;
+
+    println!(
+        "Wrote the final parameters to '{}' and the verifying key to '{}'.",
+        params_out.display(),
+        vk_out.display()
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellperson::groth16;
+
+    use crate::circuit::Witness;
+
+    /// new, contribute, verify and finalize, then prove and verify with the
+    /// final parameters. Needs the phase 1 files in the working directory.
+    #[test]
+    #[ignore]
+    fn round_trip() {
+        let config: CircuitConfig = "sha256-1".parse().unwrap();
+        let dir = std::env::temp_dir().join(format!("ceremony-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name);
+        let transcript = path("transcript");
+
+        new(config, &path("0"), &transcript, false).unwrap();
+        assert!(new(config, &path("0"), &transcript, false).is_err());
+        contribute(&path("0"), &path("1"), &transcript, Some(1)).unwrap();
+        contribute(&path("1"), &path("2"), &transcript, Some(2)).unwrap();
+        verify_step(&path("1"), &path("2")).unwrap();
+        assert!(verify(&path("2"), &transcript).unwrap());
+        assert!(finalize(&path("2"), &transcript, &path("params"), &path("vk")).unwrap());
+
+        // Seeded contributions flag everything after them:
+        assert!(!params::read_header(&path("0")).unwrap().insecure);
+        for name in &["1", "2", "params", "vk"] {
+            assert!(params::read_header(&path(name)).unwrap().insecure);
+        }
+
+        let (id, shape) = (config.to_string(), shape(config).unwrap());
+        let params = params::read_params::<Bls12>(&path("params"), &id, &shape, true).unwrap();
+        let vk = params::read_vk::<Bls12>(&path("vk"), &id, &shape).unwrap();
+        let witness = Witness::new(vec![42]);
+        let inputs = config.inputs::<Bls12>(&config.public(&witness)).unwrap();
+        let mut rng = ProofRng::new(Some(3));
+        let proof = groth16::create_random_proof(config.circuit(witness), &params, &mut rng)
+            .unwrap();
+        let pvk = groth16::prepare_verifying_key(&vk);
+        assert!(groth16::verify_proof(&pvk, &proof, &inputs).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Known test vectors for the circuits, checked with bellperson's
+//! `TestConstraintSystem`.
+//!
+//! Each vector is synthesized with its witness; every constraint must hold
+//! and the public inputs the circuit computes must equal the ones computed
+//! on the host. Finally a preimage bit of a satisfied circuit is flipped,
+//! which must be caught by a constraint.
+
+use bellperson::gadgets::test::TestConstraintSystem;
+use bellperson::{Circuit, SynthesisError};
+use ff::Field;
+use paired::bls12_381::{Bls12, Fr};
+
+use crate::circuit::{CircuitConfig, HashMode, Witness, HASH_LEN};
+use crate::merkle::{self, MerkleCircuit, MerkleTree, Node};
+use crate::proof::{from_hex, to_hex};
+
+/// SHA-256("abc"), from FIPS 180-2.
+const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+/// The preimage bit `check_tampered` flips (`AllocatedBit::alloc` names its
+/// variable "boolean").
+const TAMPERED_BIT: &str = "preimage/bit 0/boolean";
+
+/// One check.
+#[derive(Clone, Debug)]
+enum Vector {
+    /// The circuit for `config` must be satisfied by `witness` and, if
+    /// given, hash it to `hash` on the host.
+    Hash {
+        name: &'static str,
+        config: CircuitConfig,
+        witness: Witness,
+        hash: Option<String>,
+    },
+    /// Membership of leaf `leaf` in a sample tree of depth `depth`.
+    Merkle { depth: usize, leaf: usize },
+    /// A flipped preimage bit must be caught.
+    Tampered,
+}
+
+/// Every check `run` does.
+fn vectors() -> Vec<Vector> {
+    let abc = CircuitConfig {
+        mode: HashMode::Sha256,
+        preimage_len: 3,
+        prefix_len: 0,
+        suffix_len: 0,
+    };
+    let header = CircuitConfig {
+        preimage_len: 4,
+        prefix_len: 76,
+        ..CircuitConfig::default()
+    };
+    let sha256d_42 = to_hex(&CircuitConfig::default().public(&Witness::new(vec![42; 80])));
+
+    vec![
+        Vector::Hash {
+            name: "'abc'",
+            config: abc,
+            witness: Witness::new(b"abc".to_vec()),
+            hash: Some(SHA256_ABC.to_owned()),
+        },
+        Vector::Hash {
+            name: "[42; 80]",
+            config: CircuitConfig::default(),
+            witness: Witness::new(vec![42; 80]),
+            hash: None,
+        },
+        // The same message split into a public prefix and a secret preimage
+        // must hash the same:
+        Vector::Hash {
+            name: "[42; 76] || [42; 4]",
+            config: header,
+            witness: Witness {
+                prefix: vec![42; 76],
+                preimage: vec![42; 4],
+                suffix: Vec::new(),
+            },
+            hash: Some(sha256d_42),
+        },
+        Vector::Merkle { depth: 3, leaf: 5 },
+        Vector::Merkle { depth: 1, leaf: 0 },
+        Vector::Tampered,
+    ]
+}
+
+impl Vector {
+    /// Runs the check, printing the outcome.
+    fn check(&self) -> bool {
+        match *self {
+            Vector::Hash {
+                name,
+                config,
+                ref witness,
+                ref hash,
+            } => check_hash(name, config, witness.clone(), hash.as_ref().map(|h| h.as_str())),
+            Vector::Merkle { depth, leaf } => check_merkle(depth, leaf),
+            Vector::Tampered => check_tampered(),
+        }
+    }
+}
+
+fn synthesize<C: Circuit<Bls12>>(
+    circuit: C,
+) -> Result<TestConstraintSystem<Bls12>, SynthesisError> {
+    let mut cs = TestConstraintSystem::new();
+    circuit.synthesize(&mut cs)?;
+    Ok(cs)
+}
+
+/// Checks that `cs` is satisfied and has `expected` public inputs.
+fn check(name: &str, cs: &TestConstraintSystem<Bls12>, expected: &[Fr]) -> bool {
+    let res = match cs.which_is_unsatisfied() {
+        Some(path) => Err(format!("constraint '{}' fails", path)),
+        None if !cs.verify(expected) => Err("the public inputs differ from the host's".to_owned()),
+        None => Ok(()),
+    };
+
+    match res {
+        Ok(()) => println!("ok      {} ({} constraints)", name, cs.num_constraints()),
+        Err(ref err) => println!("FAILED  {}: {}", name, err),
+    }
+    res.is_ok()
+}
+
+fn check_hash(name: &str, config: CircuitConfig, witness: Witness, hash: Option<&str>) -> bool {
+    let public = config.public(&witness);
+    let host_hash = &public[public.len() - HASH_LEN..];
+    if let Some(hash) = hash {
+        if from_hex(hash).ok().as_deref() != Some(host_hash) {
+            println!("FAILED  {}: host hash {} is not {}", name, to_hex(host_hash), hash);
+            return false;
+        }
+    }
+
+    let name = format!("{} {}", config, name);
+    match synthesize(config.circuit(witness)) {
+        Ok(cs) => check(&name, &cs, &config.inputs::<Bls12>(&public).unwrap()),
+        Err(err) => {
+            println!("FAILED  {}: {}", name, err);
+            false
+        }
+    }
+}
+
+fn check_merkle(depth: usize, leaf_idx: usize) -> bool {
+    let leaves: Vec<Node> = (0..1 << depth).map(|idx| [idx as u8; 32]).collect();
+    let tree = MerkleTree::new(depth, &leaves).unwrap();
+    let name = format!("merkle-{} leaf {}", depth, leaf_idx);
+
+    let circuit = MerkleCircuit {
+        depth,
+        path: tree.path(leaf_idx),
+    };
+    match synthesize(circuit) {
+        Ok(cs) => check(&name, &cs, &merkle::inputs::<Bls12>(&tree.root())),
+        Err(err) => {
+            println!("FAILED  {}: {}", name, err);
+            false
+        }
+    }
+}
+
+/// Flips a preimage bit and checks that a constraint catches it.
+fn check_tampered() -> bool {
+    let config = CircuitConfig::default();
+    let mut cs = match synthesize(config.circuit(Witness::new(vec![42; 80]))) {
+        Ok(cs) => cs,
+        Err(err) => {
+            println!("FAILED  tampered witness: {}", err);
+            return false;
+        }
+    };
+
+    let mut flipped = Fr::one();
+    flipped.sub_assign(&cs.get(TAMPERED_BIT));
+    cs.set(TAMPERED_BIT, flipped);
+
+    match cs.which_is_unsatisfied() {
+        Some(path) => {
+            println!("ok      tampered witness ('{}' flipped) caught by '{}'", TAMPERED_BIT, path);
+            true
+        }
+        None => {
+            println!("FAILED  tampered witness ('{}' flipped) not caught", TAMPERED_BIT);
+            false
+        }
+    }
+}
+
+/// Runs every check, returning `false` if any fails.
+pub fn run() -> bool {
+    let results: Vec<bool> = vectors().iter().map(Vector::check).collect();
+
+    let failed = results.iter().filter(|&&ok| !ok).count();
+    println!("{} of {} checks passed.", results.len() - failed, results.len());
+    failed == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_pass() {
+        for vector in vectors() {
+            assert!(vector.check(), "{:?}", vector);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! The SHA-256(d) preimage circuit.
+//!
+//! A `CircuitConfig` fixes the shape of the circuit (and so of its
+//! parameters): the hash mode, the length of the secret preimage and the
+//! lengths of optional public data hashed before (`prefix`) and after it
+//! (`suffix`). The circuit proves knowledge of a preimage such that
+//!
+//! ```text
+//! hash = H(prefix || preimage || suffix)
+//! ```
+//!
+//! with `prefix`, `suffix` and `hash` public. SHA-256 padding is done
+//! in-circuit by the `sha256` gadget, so any byte length works. For example a
+//! Bitcoin block header is `sha256d-80`, or `sha256d-4-p76` to keep only the
+//! nonce secret.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bellperson::{
+    gadgets::{
+        boolean::{AllocatedBit, Boolean},
+        multipack,
+        sha256::sha256,
+    },
+    Circuit, ConstraintSystem, SynthesisError,
+};
+use paired::Engine;
+use sha2::{Digest, Sha256};
+
+pub const HASH_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashMode {
+    /// A single SHA-256.
+    Sha256,
+    /// SHA-256 of SHA-256, as in Bitcoin.
+    Sha256d,
+}
+
+impl HashMode {
+    fn name(self) -> &'static str {
+        match self {
+            HashMode::Sha256 => "sha256",
+            HashMode::Sha256d => "sha256d",
+        }
+    }
+}
+
+/// The shape of a `MyCircuit`. Its `Display` form is the circuit id in
+/// parameter, verifying key and proof files, e.g. `sha256d-80` or
+/// `sha256-32-p4-s8` (a 32 byte preimage with a 4 byte prefix and an 8 byte
+/// suffix).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitConfig {
+    pub mode: HashMode,
+    pub preimage_len: usize,
+    pub prefix_len: usize,
+    pub suffix_len: usize,
+}
+
+impl Default for CircuitConfig {
+    /// The original circuit: SHA-256d of an 80 byte preimage.
+    fn default() -> CircuitConfig {
+        CircuitConfig {
+            mode: HashMode::Sha256d,
+            preimage_len: 80,
+            prefix_len: 0,
+            suffix_len: 0,
+        }
+    }
+}
+
+impl fmt::Display for CircuitConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.mode.name(), self.preimage_len)?;
+        if self.prefix_len > 0 {
+            write!(f, "-p{}", self.prefix_len)?;
+        }
+        if self.suffix_len > 0 {
+            write!(f, "-s{}", self.suffix_len)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CircuitConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CircuitConfig, String> {
+        let invalid = || {
+            format!(
+                "Invalid circuit '{}' (expected '<sha256|sha256d>-<LEN>[-p<LEN>][-s<LEN>]').",
+                s
+            )
+        };
+        let mut parts = s.split('-');
+
+        let mode = match parts.next() {
+            Some("sha256") => HashMode::Sha256,
+            Some("sha256d") => HashMode::Sha256d,
+            _ => return Err(invalid()),
+        };
+        let preimage_len = parts
+            .next()
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut config = CircuitConfig {
+            mode,
+            preimage_len,
+            prefix_len: 0,
+            suffix_len: 0,
+        };
+
+        for part in parts {
+            let len = part.get(1..).and_then(|len| len.parse().ok()).ok_or_else(invalid)?;
+            match part.chars().next() {
+                Some('p') => config.prefix_len = len,
+                Some('s') => config.suffix_len = len,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl CircuitConfig {
+    /// The circuit without a witness, for setup and verification.
+    pub fn blank(self) -> MyCircuit {
+        MyCircuit {
+            config: self,
+            witness: None,
+        }
+    }
+
+    pub fn circuit(self, witness: Witness) -> MyCircuit {
+        MyCircuit {
+            config: self,
+            witness: Some(witness),
+        }
+    }
+
+    /// The length of a proof's public data: prefix, suffix and hash.
+    pub fn public_len(&self) -> usize {
+        self.prefix_len + self.suffix_len + HASH_LEN
+    }
+
+    /// Computes the public data (`prefix || suffix || hash`) for `witness`
+    /// on the host.
+    pub fn public(&self, witness: &Witness) -> Vec<u8> {
+        let mut message = witness.prefix.clone();
+        message.extend_from_slice(&witness.preimage);
+        message.extend_from_slice(&witness.suffix);
+
+        let hash = match self.mode {
+            HashMode::Sha256 => Sha256::digest(&message),
+            HashMode::Sha256d => Sha256::digest(&Sha256::digest(&message)),
+        };
+
+        let mut public = witness.prefix.clone();
+        public.extend_from_slice(&witness.suffix);
+        public.extend_from_slice(&hash);
+        public
+    }
+
+    /// Splits public data into prefix, suffix and hash.
+    pub fn split_public<'a>(&self, public: &'a [u8]) -> Result<[&'a [u8]; 3], String> {
+        if public.len() != self.public_len() {
+            return Err(format!(
+                "Circuit '{}' has {} bytes of public data, not {}.",
+                self,
+                self.public_len(),
+                public.len()
+            ));
+        }
+        let (prefix, rest) = public.split_at(self.prefix_len);
+        let (suffix, hash) = rest.split_at(self.suffix_len);
+        Ok([prefix, suffix, hash])
+    }
+
+    /// The public inputs for `public` data, packed the way the circuit packs
+    /// them.
+    pub fn inputs<E: Engine>(&self, public: &[u8]) -> Result<Vec<E::Fr>, String> {
+        Ok(self
+            .split_public(public)?
+            .iter()
+            .flat_map(|bytes| {
+                multipack::compute_multipacking::<E>(&multipack::bytes_to_bits_le(bytes))
+            })
+            .collect())
+    }
+}
+
+/// The data a proof is made from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Witness {
+    /// Public data hashed before the preimage.
+    pub prefix: Vec<u8>,
+    /// The secret.
+    pub preimage: Vec<u8>,
+    /// Public data hashed after the preimage.
+    pub suffix: Vec<u8>,
+}
+
+impl Witness {
+    pub fn new(preimage: Vec<u8>) -> Witness {
+        Witness {
+            preimage,
+            ..Witness::default()
+        }
+    }
+}
+
+/// Flips the bit order of each byte (the `sha256` gadget is big-endian, we
+/// use little-endian bit order).
+fn flip_endianness(bits: &[Boolean]) -> Vec<Boolean> {
+    bits.chunks(8)
+        .map(|c| c.iter().rev())
+        .flatten()
+        .cloned()
+        .collect()
+}
+
+/// Our own SHA-256d gadget. Input and output are in little-endian bit order.
+pub fn sha256d<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    data: &[Boolean],
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let input = flip_endianness(data);
+
+    let mid = sha256(cs.namespace(|| "SHA-256(input)"), &input)?;
+    let res = sha256(cs.namespace(|| "SHA-256(mid)"), &mid)?;
+
+    Ok(flip_endianness(&res))
+}
+
+/// SHA-256 with little-endian bit order input and output.
+pub fn sha256_le<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    data: &[Boolean],
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let res = sha256(cs.namespace(|| "SHA-256(input)"), &flip_endianness(data))?;
+    Ok(flip_endianness(&res))
+}
+
+/// Witnesses `len` bytes as bits (little-endian within each byte), or `len`
+/// unknown bytes if `bytes` is `None`.
+fn alloc_bytes<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    bytes: Option<&[u8]>,
+    len: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    // If we are verifying a proof, we still need to create the same
+    // constraints, so we use an equivalent-size Vec of None (indicating that
+    // the value of each bit is unknown).
+    let bit_values: Vec<Option<bool>> = match bytes {
+        Some(bytes) => {
+            if bytes.len() != len {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+            bytes
+                .iter()
+                .map(|byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+                .flatten()
+                .map(|b| Some(b))
+                .collect()
+        }
+        None => vec![None; len * 8],
+    };
+
+    bit_values
+        .into_iter()
+        .enumerate()
+        // Allocate each bit.
+        .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), b))
+        // Convert the AllocatedBits into Booleans (required for the sha256 gadget).
+        .map(|b| b.map(Boolean::from))
+        .collect()
+}
+
+pub struct MyCircuit {
+    pub config: CircuitConfig,
+    /// The data we are proving that we know the preimage in. Set to `None`
+    /// when we are verifying a proof (and do not have the witness data).
+    pub witness: Option<Witness>,
+}
+
+impl<E: Engine> Circuit<E> for MyCircuit {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let config = self.config;
+        let witness = self.witness.as_ref();
+
+        // Witness the bits of the message.
+        let prefix_bits = alloc_bytes(
+            cs.namespace(|| "prefix"),
+            witness.map(|w| &w.prefix[..]),
+            config.prefix_len,
+        )?;
+        let preimage_bits = alloc_bytes(
+            cs.namespace(|| "preimage"),
+            witness.map(|w| &w.preimage[..]),
+            config.preimage_len,
+        )?;
+        let suffix_bits = alloc_bytes(
+            cs.namespace(|| "suffix"),
+            witness.map(|w| &w.suffix[..]),
+            config.suffix_len,
+        )?;
+
+        let message: Vec<_> = prefix_bits
+            .iter()
+            .chain(preimage_bits.iter())
+            .chain(suffix_bits.iter())
+            .cloned()
+            .collect();
+
+        // Compute hash = H(prefix || preimage || suffix).
+        let hash = match config.mode {
+            HashMode::Sha256 => sha256_le(cs.namespace(|| "SHA-256(preimage)"), &message)?,
+            HashMode::Sha256d => sha256d(cs.namespace(|| "SHA-256d(preimage)"), &message)?,
+        };
+
+        // Expose the prefix, suffix and the vector of 32 boolean variables
+        // of the hash as compact public inputs.
+        if !prefix_bits.is_empty() {
+            multipack::pack_into_inputs(cs.namespace(|| "pack prefix"), &prefix_bits)?;
+        }
+        if !suffix_bits.is_empty() {
+            multipack::pack_into_inputs(cs.namespace(|| "pack suffix"), &suffix_bits)?;
+        }
+        multipack::pack_into_inputs(cs.namespace(|| "pack hash"), &hash)
+    }
+}
@@ -1,109 +1,767 @@
+mod batch;
+mod bench;
+mod ceremony;
+mod check;
+mod circuit;
+mod merkle;
+mod params;
+mod proof;
+mod prover;
+mod rng;
+mod shape;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
 use bellperson::{
-    gadgets::{
-        boolean::{AllocatedBit, Boolean},
-        multipack,
-        sha256::sha256,
-    },
-    groth16, Circuit, ConstraintSystem, SynthesisError,
+    groth16::{self, Parameters, VerifyingKey},
+    SynthesisError,
 };
-use paired::{bls12_381::Bls12, Engine};
+use paired::bls12_381::{Bls12, Fr};
 use rand::rngs::OsRng;
 use sha2::{Digest, Sha256};
 
-/// Our own SHA-256d gadget. Input and output are in little-endian bit order.
-fn sha256d<E: Engine, CS: ConstraintSystem<E>>(
-    mut cs: CS,
-    data: &[Boolean],
-) -> Result<Vec<Boolean>, SynthesisError> {
-    // Flip endianness of each input byte
-    let input: Vec<_> = data
-        .chunks(8)
-        .map(|c| c.iter().rev())
-        .flatten()
-        .cloned()
+use crate::circuit::{CircuitConfig, Witness, HASH_LEN};
+use crate::merkle::{MerkleCircuit, MerkleTree, Node};
+use crate::proof::ProofFile;
+use crate::prover::Proved;
+use crate::rng::ProofRng;
+use crate::shape::CircuitShape;
+
+static USAGE: &'static str = "\
+Usage: bellman_example [COMMAND]
+
+Commands:
+    setup <PARAMS> <VK> [--circuit ID]
+                            Generate random parameters for the circuit ID
+                            (default: sha256d-80, see below) and write them to
+                            PARAMS and its verifying key to VK
+    prove <PARAMS> <PREIMAGE> <PROOF> [--prefix DATA] [--suffix DATA]
+                            Prove knowledge of PREIMAGE (hex, or a file
+                            holding it raw or as hex) and write the proof and
+                            the public data (prefix, suffix and hash) to PROOF.
+                            The prefix and suffix are required if the circuit
+                            of PARAMS has them
+    prove-batch <PARAMS> <WITNESSES> <OUT_DIR> [--threads N]
+                            Prove every line of WITNESSES (hex prefix,
+                            preimage and suffix, separated by whitespace; the
+                            prefix and suffix only if the circuit has them) in
+                            parallel on N threads (default: one per core) and
+                            write the proofs to OUT_DIR/<LINE>.proof as they
+                            finish
+    verify <VK> <PROOF> [HASH]
+                            Check PROOF against VK (and that it is for the hex
+                            HASH, if given)
+    batch-verify <VK> <PROOF>... [--compare]
+                            Check many proofs against VK with randomized batch
+                            pairing checks, bisecting failed batches to find
+                            the invalid proofs. With --compare, also check
+                            them one by one, fail if the results disagree and
+                            compare the throughput
+    demo [PARAMS]           Prove and verify knowledge of the SHA-256d
+                            preimage [42; 80] (default), with the parameters
+                            in PARAMS instead of fresh ones
+    merkle-demo [--depth N] [--leaf IDX]
+                            Prove and verify membership of leaf IDX (default:
+                            3) in a SHA-256 Merkle tree of depth N (default: 4)
+                            of sample leaves
+    analyze [--circuit ID | --merkle DEPTH] [--depth N]
+                            Synthesize the circuit ID (default: sha256d-80) or
+                            the Merkle circuit of depth DEPTH and count its
+                            constraints, inputs and aux variables per
+                            namespace, N levels deep (default: 2)
+    check                   Check the circuits against known test vectors,
+                            and that a tampered witness is caught
+    bench [--circuits ID,...] [--iterations N] [--mode cpu|gpu] [--out FILE]
+                            Time synthesis, an FFT, a multiexp and whole
+                            proofs for each circuit (default: sha256d-32,
+                            sha256d-80 and sha256d-256), taking the median of
+                            N runs (default: 3), on the CPU and the GPU (if
+                            built with the `gpu` feature and one is found),
+                            and write the results as JSON to FILE (default:
+                            standard output)
+    ceremony <SUBCOMMAND>   Run a phase 2 trusted setup ceremony, recording
+                            each contribution in a transcript (TRANSCRIPT,
+                            default: ceremony.transcript):
+        new <ID> <FILE> [--force]
+                            Start one for the circuit ID (needs the phase 1
+                            `phase1radix2m*` files in the working directory),
+                            overwriting an existing transcript only with
+                            --force
+        contribute <IN> <OUT>
+                            Add a contribution to IN and write it to OUT
+        verify-step <BEFORE> <AFTER>
+                            Check the one contribution between two files
+        verify <FILE>       Check every contribution in FILE against the
+                            circuit and the transcript
+        finalize <FILE> <PARAMS> <VK>
+                            Verify FILE and write its parameters and
+                            verifying key for `prove` and `verify`
+        --transcript <TRANSCRIPT>
+
+`setup`, `prove`, `prove-batch`, `demo`, `merkle-demo` and `ceremony
+contribute` take `--insecure-seed N` to use a ChaCha RNG seeded with N
+instead of the OS RNG, making parameters and proofs reproducible byte for
+byte. This is for test fixtures only: anyone who knows N can forge proofs.
+Seeded parameter files are flagged as such.
+
+Circuits: <sha256|sha256d>-<LEN>[-p<LEN>][-s<LEN>] proves knowledge of a LEN
+byte preimage with H(prefix || preimage || suffix) = hash, for an optional
+public prefix and suffix of the given lengths (e.g. sha256d-4-p76 for the
+nonce of a Bitcoin block header).
+
+Exit status: 0 on success, 1 if a proof is invalid (or failed to be created),
+2 on malformed input or other errors.";
+
+fn synthesis_error(err: SynthesisError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+fn invalid_input<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err.to_string())
+}
+
+fn shape(config: CircuitConfig) -> io::Result<CircuitShape> {
+    CircuitShape::of::<Bls12, _>(config.blank()).map_err(synthesis_error)
+}
+
+/// The circuit a parameter or verifying key file was generated for.
+fn config_of(path: &Path) -> io::Result<CircuitConfig> {
+    let header = params::read_header(path)?;
+    if header.insecure {
+        rng::warn_insecure(&format!("'{}' was generated", path.display()));
+    }
+    header
+        .circuit_id
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn load_params(path: &Path) -> io::Result<(CircuitConfig, Parameters<Bls12>)> {
+    let config = config_of(path)?;
+    let params = params::read_params(path, &config.to_string(), &shape(config)?, false)?;
+    Ok((config, params))
+}
+
+fn load_vk(path: &Path) -> io::Result<(CircuitConfig, VerifyingKey<Bls12>)> {
+    let config = config_of(path)?;
+    let vk = params::read_vk(path, &config.to_string(), &shape(config)?)?;
+    Ok((config, vk))
+}
+
+/// Reads the prefix, preimage and suffix for `config` from hex (or files).
+fn read_witness(
+    config: CircuitConfig,
+    prefix: Option<&str>,
+    preimage: &str,
+    suffix: Option<&str>,
+) -> io::Result<Witness> {
+    let read_public = |arg: Option<&str>, len: usize, name: &str| match (arg, len) {
+        (None, 0) => Ok(Vec::new()),
+        (Some(arg), len) if len > 0 => proof::read_input(arg, len),
+        _ => Err(invalid_input(format!(
+            "Circuit '{}' has a {} byte {}.",
+            config, len, name
+        ))),
+    };
+
+    Ok(Witness {
+        prefix: read_public(prefix, config.prefix_len, "prefix")?,
+        preimage: proof::read_input(preimage, config.preimage_len)?,
+        suffix: read_public(suffix, config.suffix_len, "suffix")?,
+    })
+}
+
+// Create parameters for our circuit. In a production deployment these would
+// be generated securely using a multiparty computation (see `ceremony`).
+fn setup(
+    params_path: &Path,
+    vk_path: &Path,
+    config: CircuitConfig,
+    seed: Option<u64>,
+) -> io::Result<()> {
+    let shape = shape(config)?;
+    let mut rng = ProofRng::new(seed);
+    if rng.is_seeded() {
+        rng::warn_insecure("Generating parameters");
+    }
+    let params = groth16::generate_random_parameters::<Bls12, _, _>(config.blank(), &mut rng)
+        .map_err(synthesis_error)?;
+
+    let id = config.to_string();
+    params::write_params(params_path, &id, shape, rng.is_seeded(), &params)?;
+    params::write_vk(vk_path, &id, shape, rng.is_seeded(), &params.vk)?;
+    println!(
+        "Wrote parameters for '{}' ({} constraints) to '{}' and its verifying key to '{}'.",
+        config,
+        shape.constraints,
+        params_path.display(),
+        vk_path.display()
+    );
+    Ok(())
+}
+
+fn prove(
+    params_path: &Path,
+    preimage: &str,
+    proof_path: &Path,
+    prefix: Option<&str>,
+    suffix: Option<&str>,
+    seed: Option<u64>,
+) -> io::Result<()> {
+    let (config, params) = load_params(params_path)?;
+    let witness = read_witness(config, prefix, preimage, suffix)?;
+    let public = config.public(&witness);
+
+    let mut rng = ProofRng::new(seed);
+    if rng.is_seeded() {
+        rng::warn_insecure("Proving");
+    }
+    let proof = groth16::create_random_proof(config.circuit(witness), &params, &mut rng)
+        .map_err(synthesis_error)?;
+
+    let hash = proof::to_hex(&public[public.len() - HASH_LEN..]);
+    ProofFile {
+        circuit_id: config.to_string(),
+        public,
+        proof,
+    }
+    .write(proof_path)?;
+    println!("Wrote the proof for hash {} to '{}'.", hash, proof_path.display());
+    Ok(())
+}
+
+/// Returns `false` if any proof failed.
+fn prove_batch(
+    params_path: &Path,
+    witnesses_path: &Path,
+    out_dir: &Path,
+    threads: usize,
+    seed: Option<u64>,
+) -> io::Result<bool> {
+    let (config, params) = load_params(params_path)?;
+    if seed.is_some() {
+        rng::warn_insecure("Proving");
+    }
+
+    let witnesses = fs::read_to_string(witnesses_path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let prefix = if config.prefix_len > 0 { fields.next() } else { None };
+            let preimage = fields.next().unwrap_or("");
+            let suffix = fields.next();
+            read_witness(config, prefix, preimage, suffix)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    fs::create_dir_all(out_dir)?;
+
+    println!("Proving {} witnesses for '{}' ({}).", witnesses.len(), config, prover::mode());
+    let start = Instant::now();
+    let failed = AtomicUsize::new(0);
+
+    prover::prove_all(&params, config, &witnesses, threads, seed, |proved| {
+        let Proved {
+            idx,
+            public,
+            proof,
+            elapsed,
+        } = proved;
+        let path = out_dir.join(format!("{}.proof", idx));
+        let hash = proof::to_hex(&public[public.len() - HASH_LEN..]);
+        let res = proof.map_err(synthesis_error).and_then(|proof| {
+            ProofFile {
+                circuit_id: config.to_string(),
+                public,
+                proof,
+            }
+            .write(&path)
+        });
+
+        match res {
+            Ok(()) => {
+                println!("[{}] {} proved in {:?} -> '{}'", idx, hash, elapsed, path.display())
+            }
+            Err(err) => {
+                failed.fetch_add(1, Ordering::SeqCst);
+                println!("[{}] failed after {:?}: {}", idx, elapsed, err);
+            }
+        }
+    })
+    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    let elapsed = start.elapsed();
+    let failed = failed.load(Ordering::SeqCst);
+    println!(
+        "{} of {} proofs in {:?} ({:.2} proofs/s).",
+        witnesses.len() - failed,
+        witnesses.len(),
+        elapsed,
+        batch::throughput(witnesses.len() - failed, elapsed)
+    );
+    Ok(failed == 0)
+}
+
+/// Reads a proof file, checking that it is for the circuit `config` and
+/// returning it with its public inputs.
+fn read_proof(
+    path: &Path,
+    config: CircuitConfig,
+) -> io::Result<(ProofFile<Bls12>, Vec<Fr>)> {
+    let file = ProofFile::<Bls12>::read(path)?;
+    if file.circuit_id != config.to_string() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "'{}' is a proof for circuit '{}', not '{}'",
+                path.display(),
+                file.circuit_id,
+                config
+            ),
+        ));
+    }
+    let inputs = config
+        .inputs::<Bls12>(&file.public)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((file, inputs))
+}
+
+/// Returns `false` if the proof is invalid.
+fn verify(vk_path: &Path, proof_path: &Path, hash: Option<&str>) -> io::Result<bool> {
+    let (config, vk) = load_vk(vk_path)?;
+    let (file, inputs) = read_proof(proof_path, config)?;
+    let file_hash = &file.public[file.public.len() - HASH_LEN..];
+
+    if let Some(hash) = hash {
+        if proof::read_input(hash, HASH_LEN)? != file_hash {
+            println!("Invalid: the proof is for hash {}.", proof::to_hex(file_hash));
+            return Ok(false);
+        }
+    }
+
+    let pvk = groth16::prepare_verifying_key(&vk);
+    let valid = groth16::verify_proof(&pvk, &file.proof, &inputs).map_err(synthesis_error)?;
+
+    if valid {
+        println!("Valid proof for hash {}.", proof::to_hex(file_hash));
+    } else {
+        println!("Invalid proof.");
+    }
+    Ok(valid)
+}
+
+/// Returns `false` if any proof is invalid. With `compare`, also checks
+/// the proofs one by one, failing if that disagrees with the batch checks.
+fn batch_verify(vk_path: &Path, proof_paths: &[&str], compare: bool) -> io::Result<bool> {
+    let (config, vk) = load_vk(vk_path)?;
+    let pvk = groth16::prepare_verifying_key(&vk);
+
+    let files = proof_paths
+        .iter()
+        .map(|path| read_proof(Path::new(path), config))
+        .collect::<io::Result<Vec<_>>>()?;
+    let proofs: Vec<_> = files
+        .iter()
+        .map(|(file, inputs)| (&file.proof, inputs.clone()))
         .collect();
 
-    let mid = sha256(cs.namespace(|| "SHA-256(input)"), &input)?;
-    let res = sha256(cs.namespace(|| "SHA-256(mid)"), &mid)?;
-
-    // Flip endianness of each output byte
-    Ok(res
-        .chunks(8)
-        .map(|c| c.iter().rev())
-        .flatten()
-        .cloned()
-        .collect())
-}
-
-struct MyCircuit {
-    /// The input to SHA-256d we are proving that we know. Set to `None` when we
-    /// are verifying a proof (and do not have the witness data).
-    preimage: Option<[u8; 80]>,
-}
-
-impl<E: Engine> Circuit<E> for MyCircuit {
-    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
-        // Compute the values for the bits of the preimage. If we are verifying a proof,
-        // we still need to create the same constraints, so we return an equivalent-size
-        // Vec of None (indicating that the value of each bit is unknown).
-        let bit_values = if let Some(preimage) = self.preimage {
-            preimage
-                .iter()
-                .map(|byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
-                .flatten()
-                .map(|b| Some(b))
-                .collect()
+    let report = batch::verify(&pvk, &proofs, &mut OsRng).map_err(synthesis_error)?;
+    for &idx in report.invalid.iter() {
+        println!("Invalid proof: '{}'.", proof_paths[idx]);
+    }
+    println!(
+        "{} of {} proofs valid ({} batch checks).",
+        proofs.len() - report.invalid.len(),
+        proofs.len(),
+        report.checks
+    );
+
+    if compare {
+        let (invalid_each, elapsed_each) =
+            batch::verify_each(&pvk, &proofs).map_err(synthesis_error)?;
+        // Both must agree, or batching is broken:
+        if report.invalid != invalid_each {
+            let names = |invalid: &[usize]| {
+                invalid.iter().map(|&idx| proof_paths[idx]).collect::<Vec<_>>().join(", ")
+            };
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Batched and one by one checks disagree: invalid [{}] vs [{}].",
+                    names(&report.invalid),
+                    names(&invalid_each)
+                ),
+            ));
+        }
+        println!(
+            "Batched: {:?} ({:.1} proofs/s), one by one: {:?} ({:.1} proofs/s).",
+            report.elapsed,
+            batch::throughput(proofs.len(), report.elapsed),
+            elapsed_each,
+            batch::throughput(proofs.len(), elapsed_each)
+        );
+    } else {
+        println!(
+            "Batched: {:?} ({:.1} proofs/s).",
+            report.elapsed,
+            batch::throughput(proofs.len(), report.elapsed)
+        );
+    }
+    Ok(report.invalid.is_empty())
+}
+
+fn demo(params_path: Option<&Path>, seed: Option<u64>) -> io::Result<()> {
+    let config = CircuitConfig::default();
+    let mut rng = ProofRng::new(seed);
+    if rng.is_seeded() {
+        rng::warn_insecure("Generating parameters and proving");
+    }
+    let params = match params_path {
+        Some(path) => params::read_params::<Bls12>(
+            path,
+            &config.to_string(),
+            &shape(config)?,
+            false,
+        )?,
+        None => groth16::generate_random_parameters::<Bls12, _, _>(config.blank(), &mut rng)
+            .map_err(synthesis_error)?,
+    };
+
+    // Prepare the verification key (for proof verification).
+    let pvk = groth16::prepare_verifying_key(&params.vk);
+
+    // Pick a preimage and compute its hash.
+    let witness = Witness::new(vec![42; 80]);
+    let public = config.public(&witness);
+
+    // Create a Groth16 proof with our parameters, with an instance of our
+    // circuit (with the preimage as a witness).
+    let proof = groth16::create_random_proof(config.circuit(witness), &params, &mut rng)
+        .map_err(synthesis_error)?;
+
+    // Pack the hash as inputs for proof verification.
+    let inputs = config.inputs::<Bls12>(&public).map_err(invalid_input)?;
+    // Check the proof!
+    assert!(groth16::verify_proof(&pvk, &proof, &inputs).unwrap());
+    println!("Proved and verified knowledge of the preimage of {}.", proof::to_hex(&public));
+    Ok(())
+}
+
+fn merkle_demo(depth: usize, leaf_idx: usize, seed: Option<u64>) -> io::Result<()> {
+    if depth > 20 {
+        return Err(invalid_input("The demo tree's depth is at most 20."));
+    }
+
+    // Sample leaves: hashes of their index.
+    let leaves: Vec<Node> = (0..1usize << depth)
+        .map(|idx| {
+            let mut leaf = [0u8; HASH_LEN];
+            leaf.copy_from_slice(&Sha256::digest(&idx.to_le_bytes()));
+            leaf
+        })
+        .collect();
+    let tree = MerkleTree::new(depth, &leaves).map_err(invalid_input)?;
+    let path = tree.path(leaf_idx).ok_or_else(|| {
+        invalid_input(format!("No leaf {} in a tree of depth {}.", leaf_idx, depth))
+    })?;
+    assert_eq!(path.root(), tree.root());
+
+    let mut rng = ProofRng::new(seed);
+    if rng.is_seeded() {
+        rng::warn_insecure("Generating parameters and proving");
+    }
+    let params = groth16::generate_random_parameters::<Bls12, _, _>(
+        MerkleCircuit { depth, path: None },
+        &mut rng,
+    )
+    .map_err(synthesis_error)?;
+    let pvk = groth16::prepare_verifying_key(&params.vk);
+
+    let circuit = MerkleCircuit {
+        depth,
+        path: Some(path),
+    };
+    let proof =
+        groth16::create_random_proof(circuit, &params, &mut rng).map_err(synthesis_error)?;
+
+    assert!(groth16::verify_proof(&pvk, &proof, &merkle::inputs::<Bls12>(&tree.root())).unwrap());
+    println!(
+        "Proved and verified membership of leaf {} in the tree of depth {} with root {}.",
+        leaf_idx,
+        depth,
+        proof::to_hex(&tree.root())
+    );
+    Ok(())
+}
+
+fn analyze(circuit: Option<&str>, merkle_depth: Option<usize>, depth: usize) -> io::Result<()> {
+    let (total, namespaces) = match (circuit, merkle_depth) {
+        (Some(_), Some(_)) => {
+            return Err(invalid_input("'--circuit' and '--merkle' cannot be used together."))
+        }
+        (_, Some(merkle_depth)) => CircuitShape::by_namespace::<Bls12, _>(
+            MerkleCircuit {
+                depth: merkle_depth,
+                path: None,
+            },
+            depth,
+        ),
+        (id, None) => {
+            let config = match id {
+                Some(id) => id.parse().map_err(invalid_input)?,
+                None => CircuitConfig::default(),
+            };
+            CircuitShape::by_namespace::<Bls12, _>(config.blank(), depth)
+        }
+    }
+    .map_err(synthesis_error)?;
+
+    println!("{:>12} {:>8} {:>8}  namespace", "constraints", "inputs", "aux");
+    for (namespace, shape) in namespaces {
+        let name = if namespace.is_empty() { "(root)" } else { namespace.as_str() };
+        println!("{:>12} {:>8} {:>8}  {}", shape.constraints, shape.inputs, shape.aux, name);
+    }
+    println!(
+        "{:>12} {:>8} {:>8}  total (inputs including ONE)",
+        total.constraints, total.inputs, total.aux
+    );
+    Ok(())
+}
+
+fn bench(
+    circuits: &[CircuitConfig],
+    iterations: usize,
+    modes: Option<&[bench::Mode]>,
+    out: Option<&str>,
+) -> io::Result<()> {
+    let report = bench::run(circuits, iterations, modes)?;
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    let out = match out {
+        Some(out) => Path::new(out),
+        None => {
+            println!("{}", json);
+            return Ok(());
+        }
+    };
+    fs::write(out, json + "\n")?;
+
+    println!(
+        "{:<20} {:>4} {:>14} {:>14} {:>14} {:>14}",
+        "circuit", "mode", "synth (ms)", "fft (ms)", "multiexp (ms)", "prove (ms)"
+    );
+    for result in report.results.iter() {
+        let t = &result.timings;
+        println!(
+            "{:<20} {:>4} {:>14.1} {:>14.1} {:>14.1} {:>14.1}",
+            result.circuit,
+            format!("{:?}", result.mode).to_lowercase(),
+            t.synthesis_ms,
+            t.fft_ms,
+            t.multiexp_ms,
+            t.prove_ms
+        );
+    }
+    println!("Wrote the results to '{}'.", out.display());
+    Ok(())
+}
+
+/// Options which take no value.
+const FLAGS: &[&str] = &["--compare", "--force"];
+
+/// Splits arguments into positional ones and `--name value` options (or
+/// `--name` for `FLAGS`, with an empty value).
+fn split_args<'a>(args: &[&'a str]) -> io::Result<(Vec<&'a str>, Vec<(&'a str, &'a str)>)> {
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(&arg) = args.next() {
+        if FLAGS.contains(&arg) {
+            options.push((arg, ""));
+        } else if arg.starts_with("--") && arg != "--help" {
+            let value = args
+                .next()
+                .ok_or_else(|| invalid_input(format!("Missing value for '{}'.", arg)))?;
+            options.push((arg, *value));
         } else {
-            vec![None; 80 * 8]
-        };
-        assert_eq!(bit_values.len(), 80 * 8);
+            positional.push(arg);
+        }
+    }
+    Ok((positional, options))
+}
 
-        // Witness the bits of the preimage.
-        let preimage_bits = bit_values
-            .into_iter()
-            .enumerate()
-            // Allocate each bit.
-            .map(|(i, b)| {
-                AllocatedBit::alloc(cs.namespace(|| format!("preimage bit {}", i)), b)
-            })
-            // Convert the AllocatedBits into Booleans (required for the sha256 gadget).
-            .map(|b| b.map(Boolean::from))
-            .collect::<Result<Vec<_>, _>>()?;
+fn run(args: &[&str]) -> io::Result<bool> {
+    let (positional, options) = split_args(args)?;
+    let option = |name: &str| {
+        options
+            .iter()
+            .find(|&&(opt, _)| opt == name)
+            .map(|&(_, value)| value)
+    };
+    let allowed: &[&str] = match positional.first() {
+        Some(&"setup") => &["--circuit", "--insecure-seed"],
+        Some(&"prove") => &["--prefix", "--suffix", "--insecure-seed"],
+        Some(&"prove-batch") => &["--threads", "--insecure-seed"],
+        Some(&"demo") | None => &["--insecure-seed"],
+        Some(&"merkle-demo") => &["--depth", "--leaf", "--insecure-seed"],
+        Some(&"analyze") => &["--circuit", "--merkle", "--depth"],
+        Some(&"batch-verify") => &["--compare"],
+        Some(&"bench") => &["--circuits", "--iterations", "--mode", "--out"],
+        Some(&"ceremony") => &["--transcript", "--insecure-seed", "--force"],
+        _ => &[],
+    };
+    if let Some(&(opt, _)) = options.iter().find(|(opt, _)| !allowed.contains(opt)) {
+        return Err(invalid_input(format!("Unknown option: '{}'\n\n{}", opt, USAGE)));
+    }
 
-        // Compute hash = SHA-256d(preimage).
-        let hash = sha256d(cs.namespace(|| "SHA-256d(preimage)"), &preimage_bits)?;
+    let number = |name: &str, default: usize| match option(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| invalid_input(format!("Invalid value for '{}'.", name))),
+        None => Ok(default),
+    };
+    let seed = match option("--insecure-seed") {
+        Some(seed) => Some(seed.parse().map_err(|_| invalid_input("Invalid seed."))?),
+        None => None,
+    };
 
-        // Expose the vector of 32 boolean variables as compact public inputs.
-        multipack::pack_into_inputs(cs.namespace(|| "pack hash"), &hash)
+    match positional.as_slice() {
+        ["setup", params, vk] => {
+            let config = match option("--circuit") {
+                Some(id) => id.parse().map_err(invalid_input)?,
+                None => CircuitConfig::default(),
+            };
+            setup(Path::new(params), Path::new(vk), config, seed).map(|_| true)
+        }
+        ["prove", params, preimage, proof] => prove(
+            Path::new(params),
+            preimage,
+            Path::new(proof),
+            option("--prefix"),
+            option("--suffix"),
+            seed,
+        )
+        .map(|_| true),
+        ["prove-batch", params, witnesses, out_dir] => {
+            let threads = match option("--threads") {
+                Some(threads) => threads
+                    .parse()
+                    .map_err(|_| invalid_input("Invalid thread count."))?,
+                None => 0,
+            };
+            let (params, witnesses) = (Path::new(params), Path::new(witnesses));
+            prove_batch(params, witnesses, Path::new(out_dir), threads, seed)
+        }
+        ["verify", vk, proof] => verify(Path::new(vk), Path::new(proof), None),
+        ["verify", vk, proof, hash] => verify(Path::new(vk), Path::new(proof), Some(hash)),
+        ["batch-verify", vk, proofs @ ..] if !proofs.is_empty() => {
+            batch_verify(Path::new(vk), proofs, option("--compare").is_some())
+        }
+        [] | ["demo"] => demo(None, seed).map(|_| true),
+        ["demo", params] => demo(Some(Path::new(params)), seed).map(|_| true),
+        ["merkle-demo"] => {
+            merkle_demo(number("--depth", 4)?, number("--leaf", 3)?, seed).map(|_| true)
+        }
+        ["analyze"] => {
+            let merkle = match option("--merkle") {
+                Some(_) => Some(number("--merkle", 0)?),
+                None => None,
+            };
+            analyze(option("--circuit"), merkle, number("--depth", 2)?).map(|_| true)
+        }
+        ["check"] => Ok(check::run()),
+        ["bench"] => {
+            let circuits = option("--circuits")
+                .unwrap_or("sha256d-32,sha256d-80,sha256d-256")
+                .split(',')
+                .map(|id| id.parse().map_err(invalid_input))
+                .collect::<io::Result<Vec<_>>>()?;
+            let mode = match option("--mode") {
+                Some(mode) => Some([mode.parse::<bench::Mode>().map_err(invalid_input)?]),
+                None => None,
+            };
+            let modes = mode.as_ref().map(|mode| &mode[..]);
+            bench(&circuits, number("--iterations", 3)?, modes, option("--out"))
+                .map(|_| true)
+        }
+        ["ceremony", command, files @ ..] => {
+            let transcript = Path::new(option("--transcript").unwrap_or("ceremony.transcript"));
+            let path = |idx: usize| Path::new(files[idx]);
+            match (*command, files.len()) {
+                ("new", 2) => {
+                    let config = files[0].parse().map_err(invalid_input)?;
+                    let force = option("--force").is_some();
+                    ceremony::new(config, path(1), transcript, force).map(|_| true)
+                }
+                ("contribute", 2) => {
+                    ceremony::contribute(path(0), path(1), transcript, seed).map(|_| true)
+                }
+                ("verify-step", 2) => ceremony::verify_step(path(0), path(1)).map(|hash| {
+                    println!("Valid contribution: {}", hash);
+                    true
+                }),
+                ("verify", 1) => ceremony::verify(path(0), transcript),
+                ("finalize", 3) => ceremony::finalize(path(0), transcript, path(1), path(2)),
+                _ => Err(invalid_input(USAGE)),
+            }
+        }
+        ["--help"] | ["-h"] => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        _ => Err(invalid_input(USAGE)),
     }
 }
 
-// Create parameters for our circuit. In a production deployment these would
-// be generated securely using a multiparty computation.
-pub fn main(){
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let params = {
-    let c = MyCircuit { preimage: None };
-    groth16::generate_random_parameters::<Bls12, _, _>(c, &mut OsRng).unwrap()
-};
+    match run(&args.iter().map(|s| s.as_str()).collect::<Vec<_>>()) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            println!("{}", err);
+            process::exit(2);
+        }
+    }
+}
 
-// Prepare the verification key (for proof verification).
-let pvk = groth16::prepare_verifying_key(&params.vk);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-// Pick a preimage and compute its hash.
-let preimage = [42; 80];
-let hash = Sha256::digest(&Sha256::digest(&preimage));
+    /// The same `--insecure-seed` must give byte-identical parameters,
+    /// verifying keys and proofs, so they can be used as golden files.
+    /// Ignored as generating parameters is slow in debug builds.
+    #[test]
+    #[ignore]
+    fn seeded_runs_are_reproducible() {
+        let dir = env::temp_dir().join(format!("seeded-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name);
+        let config: CircuitConfig = "sha256-1".parse().unwrap();
 
-// Create an instance of our circuit (with the preimage as a witness).
-let c = MyCircuit {
-    preimage: Some(preimage),
-};
-// Create a Groth16 proof with our parameters.
-let proof = groth16::create_random_proof(c, &params, &mut OsRng).unwrap();
-
-// Pack the hash as inputs for proof verification.
-let hash_bits = multipack::bytes_to_bits_le(&hash);
-let inputs = multipack::compute_multipacking::<Bls12>(&hash_bits);
-// Check the proof!
-assert!(groth16::verify_proof(&pvk, &proof, &inputs).unwrap());
-}
\ No newline at end of file
+        for run in &["a", "b"] {
+            let (params, vk) = (path(&format!("{}.params", run)), path(&format!("{}.vk", run)));
+            setup(&params, &vk, config, Some(7)).unwrap();
+            let proof = path(&format!("{}.proof", run));
+            prove(&params, "2a", &proof, None, None, Some(11)).unwrap();
+        }
+        for ext in &["params", "vk", "proof"] {
+            let a = fs::read(path(&format!("a.{}", ext))).unwrap();
+            let b = fs::read(path(&format!("b.{}", ext))).unwrap();
+            assert!(a == b, "the {} files differ", ext);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
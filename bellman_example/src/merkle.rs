@@ -0,0 +1,184 @@
+//! Membership in a SHA-256 Merkle tree.
+//!
+//! `MerkleCircuit` proves knowledge of a leaf and a path from it to a public
+//! root, without revealing either. Nodes are `SHA-256(left || right)` of
+//! their 32 byte children, the leaves are 32 byte values (e.g. hashes of the
+//! actual data) and trees are padded to `2^depth` leaves with zeros.
+//! `MerkleTree` builds trees and the paths used as witnesses on the host.
+
+use bellperson::{
+    gadgets::{
+        boolean::{AllocatedBit, Boolean},
+        multipack,
+    },
+    Circuit, ConstraintSystem, SynthesisError,
+};
+use paired::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::circuit::{sha256_le, HASH_LEN};
+
+pub type Node = [u8; HASH_LEN];
+
+fn hash_nodes(left: &Node, right: &Node) -> Node {
+    let mut hasher = Sha256::new();
+    hasher.input(left);
+    hasher.input(right);
+
+    let mut node = [0u8; HASH_LEN];
+    node.copy_from_slice(&hasher.result());
+    node
+}
+
+/// A complete tree, level by level from the leaves up.
+pub struct MerkleTree {
+    levels: Vec<Vec<Node>>,
+}
+
+impl MerkleTree {
+    /// Builds a tree of `depth` levels above `leaves` (padded with zero
+    /// leaves). Fails if there are more than `2^depth` leaves.
+    pub fn new(depth: usize, leaves: &[Node]) -> Result<MerkleTree, String> {
+        if depth >= 32 || leaves.len() > 1 << depth {
+            return Err(format!("{} leaves don't fit a tree of depth {}.", leaves.len(), depth));
+        }
+
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, [0; HASH_LEN]);
+
+        let mut levels = vec![level];
+        for _ in 0..depth {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_nodes(&pair[0], &pair[1]))
+                .collect();
+            levels.push(next);
+        }
+        Ok(MerkleTree { levels })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    pub fn root(&self) -> Node {
+        self.levels[self.depth()][0]
+    }
+
+    /// The path from leaf `idx` to the root.
+    pub fn path(&self, idx: usize) -> Option<MerklePath> {
+        let leaf = *self.levels[0].get(idx)?;
+        let siblings = (0..self.depth())
+            .map(|level| self.levels[level][(idx >> level) ^ 1])
+            .collect();
+        Some(MerklePath { leaf, idx, siblings })
+    }
+}
+
+/// A leaf and the siblings of each node on its way to the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerklePath {
+    pub leaf: Node,
+    /// The leaf's position; bit `i` says whether the node at level `i` is a
+    /// right child.
+    pub idx: usize,
+    pub siblings: Vec<Node>,
+}
+
+impl MerklePath {
+    /// The root this path leads to.
+    pub fn root(&self) -> Node {
+        self.siblings
+            .iter()
+            .enumerate()
+            .fold(self.leaf, |node, (level, sibling)| {
+                if (self.idx >> level) & 1 == 1 {
+                    hash_nodes(sibling, &node)
+                } else {
+                    hash_nodes(&node, sibling)
+                }
+            })
+    }
+}
+
+/// Witnesses a node as bits (little-endian within each byte).
+fn alloc_node<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    node: Option<&Node>,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    (0..HASH_LEN * 8)
+        .map(|i| {
+            let bit = node.map(|node| (node[i / 8] >> (i % 8)) & 1 == 1);
+            AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), bit).map(Boolean::from)
+        })
+        .collect()
+}
+
+/// Returns `(a, b)` if `swap` is false and `(b, a)` otherwise.
+fn conditionally_swap<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    a: &[Boolean],
+    b: &[Boolean],
+    swap: &Boolean,
+) -> Result<(Vec<Boolean>, Vec<Boolean>), SynthesisError> {
+    let mut left = Vec::with_capacity(a.len());
+    let mut right = Vec::with_capacity(b.len());
+
+    for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+        let mut cs = cs.namespace(|| format!("bit {}", i));
+        // delta = swap & (a ^ b), then a ^ delta and b ^ delta are swapped
+        // exactly when `swap` is set.
+        let diff = Boolean::xor(cs.namespace(|| "a ^ b"), a, b)?;
+        let delta = Boolean::and(cs.namespace(|| "swap & (a ^ b)"), swap, &diff)?;
+        left.push(Boolean::xor(cs.namespace(|| "left"), a, &delta)?);
+        right.push(Boolean::xor(cs.namespace(|| "right"), b, &delta)?);
+    }
+    Ok((left, right))
+}
+
+pub struct MerkleCircuit {
+    pub depth: usize,
+    /// The path we are proving membership with. Set to `None` when we are
+    /// verifying a proof (and do not have the witness data).
+    pub path: Option<MerklePath>,
+}
+
+impl<E: Engine> Circuit<E> for MerkleCircuit {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        if let Some(ref path) = self.path {
+            if path.siblings.len() != self.depth {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+        }
+
+        let mut node = alloc_node(cs.namespace(|| "leaf"), self.path.as_ref().map(|p| &p.leaf))?;
+
+        for level in 0..self.depth {
+            let mut cs = cs.namespace(|| format!("level {}", level));
+
+            let sibling = alloc_node(
+                cs.namespace(|| "sibling"),
+                self.path.as_ref().map(|p| &p.siblings[level]),
+            )?;
+            let is_right = Boolean::from(AllocatedBit::alloc(
+                cs.namespace(|| "is right child"),
+                self.path.as_ref().map(|p| (p.idx >> level) & 1 == 1),
+            )?);
+
+            let (left, right) =
+                conditionally_swap(cs.namespace(|| "order"), &node, &sibling, &is_right)?;
+            let children: Vec<_> = left.into_iter().chain(right.into_iter()).collect();
+            node = sha256_le(cs.namespace(|| "SHA-256(left || right)"), &children)?;
+        }
+
+        // Expose the root as compact public inputs.
+        multipack::pack_into_inputs(cs.namespace(|| "pack root"), &node)
+    }
+}
+
+/// The public inputs for a proof of membership in the tree with `root`.
+pub fn inputs<E: Engine>(root: &Node) -> Vec<E::Fr> {
+    multipack::compute_multipacking::<E>(&multipack::bytes_to_bits_le(root))
+}
@@ -0,0 +1,290 @@
+//! Groth16 parameter and verifying key files.
+//!
+//! Each file starts with a header naming the circuit it was generated for,
+//! the circuit's shape and a SHA-256 hash of the serialized body:
+//!
+//! ```text
+//! magic "BEX2" | kind: u8 | flags: u8 | id length: u32 | id (UTF-8)
+//! constraints: u64 | inputs: u64 | aux: u64 | body hash: [u8; 32] | body
+//! ```
+//!
+//! (integers big-endian, like the body). Loading checks all of it, so a file
+//! for another circuit, or a truncated or corrupted one, is rejected rather
+//! than producing proofs which never verify. The only flag marks files
+//! generated from a seed (`--insecure-seed`), which must never be used
+//! outside of tests.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use bellperson::groth16::{Parameters, VerifyingKey};
+use memmap::Mmap;
+use paired::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::shape::CircuitShape;
+
+const MAGIC: &[u8; 4] = b"BEX2";
+
+const FLAG_INSECURE: u8 = 1;
+
+/// What a file holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Parameters = 1,
+    VerifyingKey = 2,
+    /// Phase 2 ceremony parameters (see `ceremony`).
+    Ceremony = 3,
+}
+
+impl Kind {
+    fn from_u8(byte: u8) -> Option<Kind> {
+        match byte {
+            1 => Some(Kind::Parameters),
+            2 => Some(Kind::VerifyingKey),
+            3 => Some(Kind::Ceremony),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub kind: Kind,
+    pub circuit_id: String,
+    pub shape: CircuitShape,
+    /// Generated from a seeded RNG, so anyone knowing the seed can forge
+    /// proofs.
+    pub insecure: bool,
+    pub hash: [u8; 32],
+}
+
+impl Header {
+    /// The byte offset of `hash` (filled in after the body is written).
+    fn hash_offset(&self) -> u64 {
+        (MAGIC.len() + 2 + 4 + self.circuit_id.len() + 3 * 8) as u64
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        let flags = if self.insecure { FLAG_INSECURE } else { 0 };
+        writer.write_all(&[self.kind as u8, flags])?;
+        writer.write_all(&(self.circuit_id.len() as u32).to_be_bytes())?;
+        writer.write_all(self.circuit_id.as_bytes())?;
+        writer.write_all(&self.shape.constraints.to_be_bytes())?;
+        writer.write_all(&self.shape.inputs.to_be_bytes())?;
+        writer.write_all(&self.shape.aux.to_be_bytes())?;
+        writer.write_all(&self.hash)
+    }
+
+    fn read<R: Read>(mut reader: R) -> io::Result<Header> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a parameter or verifying key file".to_owned()));
+        }
+
+        let mut bytes = [0u8; 2];
+        reader.read_exact(&mut bytes)?;
+        let kind = Kind::from_u8(bytes[0])
+            .ok_or_else(|| invalid(format!("unknown file kind {}", bytes[0])))?;
+        let insecure = bytes[1] & FLAG_INSECURE != 0;
+
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let mut id = vec![0u8; u32::from_be_bytes(len) as usize];
+        reader.read_exact(&mut id)?;
+        let circuit_id = String::from_utf8(id)
+            .map_err(|_| invalid("circuit id is not UTF-8".to_owned()))?;
+
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_be_bytes(bytes))
+        };
+        let shape = CircuitShape {
+            constraints: read_u64()?,
+            inputs: read_u64()?,
+            aux: read_u64()?,
+        };
+
+        let mut hash = [0u8; 32];
+        reader.read_exact(&mut hash)?;
+
+        Ok(Header {
+            kind,
+            circuit_id,
+            shape,
+            insecure,
+            hash,
+        })
+    }
+
+    /// Checks that the file holds `kind` for the circuit `circuit_id` of
+    /// `shape`.
+    fn check(&self, kind: Kind, circuit_id: &str, shape: &CircuitShape) -> io::Result<()> {
+        if self.kind != kind {
+            return Err(invalid(format!("expected {:?}, found {:?}", kind, self.kind)));
+        }
+        if self.circuit_id != circuit_id || self.shape != *shape {
+            return Err(invalid(format!(
+                "generated for circuit '{}' {:?}, not '{}' {:?}",
+                self.circuit_id, self.shape, circuit_id, shape
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Hashes everything written through it.
+struct HashWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.input(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a header with a placeholder hash, streams the body after it and
+/// then fills in the hash.
+pub fn write_file<F>(path: &Path, mut header: Header, body: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    header.write(&mut writer)?;
+
+    let mut hashing = HashWriter { inner: writer, hasher: Sha256::new() };
+    body(&mut hashing)?;
+    header.hash.copy_from_slice(&hashing.hasher.result());
+
+    let mut file = hashing
+        .inner
+        .into_inner()
+        .map_err(|err| io::Error::new(err.error().kind(), err.to_string()))?;
+    file.seek(SeekFrom::Start(header.hash_offset()))?;
+    file.write_all(&header.hash)?;
+    file.sync_all()
+}
+
+/// A whole file, mapped into memory if possible (parameter files can be
+/// hundreds of megabytes) or read otherwise.
+enum Contents {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Contents {
+    fn open(path: &Path) -> io::Result<Contents> {
+        let file = File::open(path)?;
+        match unsafe { Mmap::map(&file) } {
+            Ok(mmap) => Ok(Contents::Mapped(mmap)),
+            Err(_) => {
+                let mut bytes = Vec::new();
+                BufReader::new(file).read_to_end(&mut bytes)?;
+                Ok(Contents::Read(bytes))
+            }
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match *self {
+            Contents::Mapped(ref mmap) => &mmap[..],
+            Contents::Read(ref bytes) => &bytes[..],
+        }
+    }
+}
+
+/// Opens `path`, checks its header and hash and returns the body.
+pub fn read_file<T, F>(path: &Path, kind: Kind, circuit_id: &str, shape: &CircuitShape, body: F)
+    -> io::Result<T>
+where
+    F: FnOnce(&[u8]) -> io::Result<T>,
+{
+    let contents = Contents::open(path)?;
+    let mut bytes = contents.bytes();
+    let header = Header::read(&mut bytes)?;
+    header.check(kind, circuit_id, shape)?;
+
+    if Sha256::digest(bytes).as_slice() != &header.hash[..] {
+        return Err(invalid("body hash mismatch (truncated or corrupted file)".to_owned()));
+    }
+    body(bytes)
+}
+
+/// Reads just the header of a parameter or verifying key file.
+pub fn read_header(path: &Path) -> io::Result<Header> {
+    Header::read(BufReader::new(File::open(path)?))
+}
+
+pub fn write_params<E: Engine>(
+    path: &Path,
+    circuit_id: &str,
+    shape: CircuitShape,
+    insecure: bool,
+    params: &Parameters<E>,
+) -> io::Result<()> {
+    let header = Header {
+        kind: Kind::Parameters,
+        circuit_id: circuit_id.to_owned(),
+        shape,
+        insecure,
+        hash: [0; 32],
+    };
+    write_file(path, header, |writer| params.write(writer))
+}
+
+/// Loads parameters for the circuit `circuit_id` of `shape`. With `checked`,
+/// every point is also checked to be on the curve and in the subgroup
+/// (slow, and redundant if the hash matches a trusted file).
+pub fn read_params<E: Engine>(
+    path: &Path,
+    circuit_id: &str,
+    shape: &CircuitShape,
+    checked: bool,
+) -> io::Result<Parameters<E>> {
+    read_file(path, Kind::Parameters, circuit_id, shape, |body| {
+        Parameters::read(body, checked)
+    })
+}
+
+pub fn write_vk<E: Engine>(
+    path: &Path,
+    circuit_id: &str,
+    shape: CircuitShape,
+    insecure: bool,
+    vk: &VerifyingKey<E>,
+) -> io::Result<()> {
+    let header = Header {
+        kind: Kind::VerifyingKey,
+        circuit_id: circuit_id.to_owned(),
+        shape,
+        insecure,
+        hash: [0; 32],
+    };
+    write_file(path, header, |writer| vk.write(writer))
+}
+
+pub fn read_vk<E: Engine>(
+    path: &Path,
+    circuit_id: &str,
+    shape: &CircuitShape,
+) -> io::Result<VerifyingKey<E>> {
+    read_file(path, Kind::VerifyingKey, circuit_id, shape, |body| {
+        VerifyingKey::read(body)
+    })
+}
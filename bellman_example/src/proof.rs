@@ -0,0 +1,114 @@
+//! Proof files: a compressed Groth16 proof with the public data it proves
+//! something about.
+//!
+//! ```text
+//! magic "BEP1" | id length: u32 | id (UTF-8) | public length: u32 | public
+//! proof (compressed, 192 bytes for BLS12-381)
+//! ```
+//!
+//! The public data is what the circuit packs into its public inputs (see
+//! `CircuitConfig::inputs`): for the default `MyCircuit` just the SHA-256d
+//! hash.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use bellperson::groth16::Proof;
+use paired::Engine;
+
+const MAGIC: &[u8; 4] = b"BEP1";
+
+pub struct ProofFile<E: Engine> {
+    pub circuit_id: String,
+    pub public: Vec<u8>,
+    pub proof: Proof<E>,
+}
+
+impl<E: Engine> ProofFile<E> {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.circuit_id.len() as u32).to_be_bytes())?;
+        writer.write_all(self.circuit_id.as_bytes())?;
+        writer.write_all(&(self.public.len() as u32).to_be_bytes())?;
+        writer.write_all(&self.public)?;
+        self.proof.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Reads a proof file, failing with `InvalidData` if it is malformed
+    /// (including proof points which are not on the curve).
+    pub fn read(path: &Path) -> io::Result<ProofFile<E>> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a proof file".to_owned()));
+        }
+
+        let circuit_id = String::from_utf8(read_bytes(&mut reader)?)
+            .map_err(|_| invalid("circuit id is not UTF-8".to_owned()))?;
+        let public = read_bytes(&mut reader)?;
+        let proof = Proof::read(&mut reader)?;
+
+        if reader.read(&mut [0u8; 1])? != 0 {
+            return Err(invalid("trailing bytes after the proof".to_owned()));
+        }
+        Ok(ProofFile { circuit_id, public, proof })
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; u32::from_be_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> io::Result<Vec<u8>> {
+    let hex = hex.trim();
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.is_ascii() || hex.len() % 2 != 0 {
+        return Err(invalid(format!("'{}' is not an even number of hex digits", hex)));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| {
+            u8::from_str_radix(&hex[idx..idx + 2], 16)
+                .map_err(|_| invalid(format!("invalid hex '{}'", hex)))
+        })
+        .collect()
+}
+
+/// Reads `len` bytes given either as hex or as a file holding them (raw or
+/// as hex).
+pub fn read_input(arg: &str, len: usize) -> io::Result<Vec<u8>> {
+    let bytes = if Path::new(arg).is_file() {
+        let raw = fs::read(arg)?;
+        if raw.len() == len {
+            raw
+        } else {
+            let text = String::from_utf8(raw)
+                .map_err(|_| invalid(format!("'{}' is neither {} bytes nor hex", arg, len)))?;
+            from_hex(&text)?
+        }
+    } else {
+        from_hex(arg)?
+    };
+
+    if bytes.len() != len {
+        return Err(invalid(format!("expected {} bytes, got {}", len, bytes.len())));
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,77 @@
+//! Proving many `MyCircuit` instances in parallel.
+//!
+//! Each witness is synthesized and proved on a thread of a rayon pool, all
+//! sharing one set of parameters. With the `gpu` feature the multiexps and
+//! FFTs run on the GPU (bellperson serializes access to it and falls back to
+//! the CPU when no GPU is found); without it everything runs on the CPU.
+//! Proofs are handed to a callback as soon as each one is done, rather than
+//! after the whole batch.
+
+use std::time::{Duration, Instant};
+
+use bellperson::{
+    groth16::{self, Parameters, Proof},
+    SynthesisError,
+};
+use paired::Engine;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::circuit::{CircuitConfig, Witness};
+use crate::rng::ProofRng;
+
+/// One finished proof.
+pub struct Proved<E: Engine> {
+    /// Index of the witness.
+    pub idx: usize,
+    /// The public data proved about.
+    pub public: Vec<u8>,
+    pub proof: Result<Proof<E>, SynthesisError>,
+    pub elapsed: Duration,
+}
+
+/// Whether proofs can use the GPU in this build.
+pub fn mode() -> &'static str {
+    if cfg!(feature = "gpu") {
+        "GPU (CPU fallback)"
+    } else {
+        "CPU"
+    }
+}
+
+/// Proves every witness on `threads` threads (or one per core if `0`),
+/// calling `on_proof` from the proving thread as each finishes. With a
+/// `seed`, each proof is reproducible whatever the thread it runs on.
+pub fn prove_all<E, F>(
+    params: &Parameters<E>,
+    config: CircuitConfig,
+    witnesses: &[Witness],
+    threads: usize,
+    seed: Option<u64>,
+    on_proof: F,
+) -> Result<(), String>
+where
+    E: Engine,
+    F: Fn(Proved<E>) + Sync,
+{
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|err| format!("Unable to create the proving thread pool: {}", err))?;
+
+    pool.install(|| {
+        witnesses.par_iter().enumerate().for_each(|(idx, witness)| {
+            let start = Instant::now();
+            let c = config.circuit(witness.clone());
+            let proof = groth16::create_random_proof(c, params, &mut ProofRng::nth(seed, idx));
+
+            on_proof(Proved {
+                idx,
+                public: config.public(witness),
+                proof,
+                elapsed: start.elapsed(),
+            });
+        })
+    });
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! The randomness for setup and proving.
+//!
+//! Normally `OsRng`. With a seed (`--insecure-seed`) a ChaCha20 RNG is used
+//! instead, so that parameters and proofs are reproducible byte for byte
+//! (e.g. golden files in test fixtures). Anyone who knows the seed knows the
+//! toxic waste of the parameters and can forge proofs, so seeded parameters
+//! are flagged in their header and a warning is printed whenever they are
+//! made or used.
+
+use rand::rngs::OsRng;
+use rand::{CryptoRng, Error, RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+pub enum ProofRng {
+    Os(OsRng),
+    Seeded(ChaChaRng),
+}
+
+impl ProofRng {
+    /// `OsRng`, or a ChaCha RNG seeded with `seed`.
+    pub fn new(seed: Option<u64>) -> ProofRng {
+        match seed {
+            Some(seed) => ProofRng::Seeded(ChaChaRng::seed_from_u64(seed)),
+            None => ProofRng::Os(OsRng),
+        }
+    }
+
+    /// The RNG for the `idx`th of several proofs: seeded ones get their own
+    /// stream, so proofs don't depend on the order they are made in.
+    pub fn nth(seed: Option<u64>, idx: usize) -> ProofRng {
+        match seed {
+            Some(seed) => {
+                let mut rng = ChaChaRng::seed_from_u64(seed);
+                rng.set_stream(idx as u64);
+                ProofRng::Seeded(rng)
+            }
+            None => ProofRng::Os(OsRng),
+        }
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        match *self {
+            ProofRng::Seeded(_) => true,
+            ProofRng::Os(_) => false,
+        }
+    }
+}
+
+impl RngCore for ProofRng {
+    fn next_u32(&mut self) -> u32 {
+        match *self {
+            ProofRng::Os(ref mut rng) => rng.next_u32(),
+            ProofRng::Seeded(ref mut rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match *self {
+            ProofRng::Os(ref mut rng) => rng.next_u64(),
+            ProofRng::Seeded(ref mut rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match *self {
+            ProofRng::Os(ref mut rng) => rng.fill_bytes(dest),
+            ProofRng::Seeded(ref mut rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match *self {
+            ProofRng::Os(ref mut rng) => rng.try_fill_bytes(dest),
+            ProofRng::Seeded(ref mut rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+impl CryptoRng for ProofRng {}
+
+/// Warns that `what` is made or used with insecure, seeded randomness.
+pub fn warn_insecure(what: &str) {
+    eprintln!(
+        "WARNING: {} from a fixed seed. These are for tests only: anyone who knows the \
+         seed can forge proofs.",
+        what
+    );
+}
@@ -0,0 +1,162 @@
+//! Counting the constraints and variables of a circuit without proving,
+//! in total or per namespace (e.g. `SHA-256d(preimage)/SHA-256(input)`).
+
+use std::collections::BTreeMap;
+
+use bellperson::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use paired::Engine;
+
+/// The size of a synthesized circuit. Parameters only fit circuits of the
+/// same shape.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CircuitShape {
+    pub constraints: u64,
+    /// Public inputs, including the constant `ONE` input.
+    pub inputs: u64,
+    pub aux: u64,
+}
+
+impl CircuitShape {
+    /// Synthesizes `circuit` (witness values are never computed, so it can be
+    /// the verifier's instance) and counts what it allocates.
+    pub fn of<E: Engine, C: Circuit<E>>(circuit: C) -> Result<CircuitShape, SynthesisError> {
+        ShapeCs::synthesize(circuit, false, None).map(|cs| cs.shape)
+    }
+
+    /// Like `of`, but also counts per namespace, truncated to the first
+    /// `depth` levels (`0` lumps everything together). The `ONE` input is
+    /// only in the total.
+    pub fn by_namespace<E: Engine, C: Circuit<E>>(
+        circuit: C,
+        depth: usize,
+    ) -> Result<(CircuitShape, BTreeMap<String, CircuitShape>), SynthesisError> {
+        let namespaces = Namespaces {
+            depth,
+            path: Vec::new(),
+            counts: BTreeMap::new(),
+        };
+        let cs = ShapeCs::synthesize(circuit, false, Some(namespaces))?;
+        Ok((cs.shape, cs.namespaces.map(|ns| ns.counts).unwrap_or_default()))
+    }
+
+    /// Synthesizes `circuit`, computing every witness value and linear
+    /// combination as the prover would but keeping none of them, and counts
+    /// what it allocates. Fails if a witness value can't be computed.
+    pub fn with_witness<E: Engine, C: Circuit<E>>(
+        circuit: C,
+    ) -> Result<CircuitShape, SynthesisError> {
+        ShapeCs::synthesize(circuit, true, None).map(|cs| cs.shape)
+    }
+}
+
+/// Counts per namespace.
+struct Namespaces {
+    depth: usize,
+    path: Vec<String>,
+    counts: BTreeMap<String, CircuitShape>,
+}
+
+impl Namespaces {
+    fn current(&mut self) -> &mut CircuitShape {
+        let key = self.path[..self.path.len().min(self.depth)].join("/");
+        self.counts.entry(key).or_default()
+    }
+}
+
+/// A constraint system which only counts (and, with `witness`, evaluates).
+struct ShapeCs {
+    shape: CircuitShape,
+    witness: bool,
+    namespaces: Option<Namespaces>,
+}
+
+impl ShapeCs {
+    fn synthesize<E: Engine, C: Circuit<E>>(
+        circuit: C,
+        witness: bool,
+        namespaces: Option<Namespaces>,
+    ) -> Result<ShapeCs, SynthesisError> {
+        let mut cs = ShapeCs {
+            shape: CircuitShape { inputs: 1, ..CircuitShape::default() },
+            witness,
+            namespaces,
+        };
+        circuit.synthesize(&mut cs)?;
+        Ok(cs)
+    }
+}
+
+impl<E: Engine> ConstraintSystem<E> for ShapeCs {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if self.witness {
+            f()?;
+        }
+        if let Some(ref mut namespaces) = self.namespaces {
+            namespaces.current().aux += 1;
+        }
+        self.shape.aux += 1;
+        Ok(Variable::new_unchecked(Index::Aux(self.shape.aux as usize - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if self.witness {
+            f()?;
+        }
+        if let Some(ref mut namespaces) = self.namespaces {
+            namespaces.current().inputs += 1;
+        }
+        self.shape.inputs += 1;
+        Ok(Variable::new_unchecked(Index::Input(self.shape.inputs as usize - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        if self.witness {
+            a(LinearCombination::zero());
+            b(LinearCombination::zero());
+            c(LinearCombination::zero());
+        }
+        if let Some(ref mut namespaces) = self.namespaces {
+            namespaces.current().constraints += 1;
+        }
+        self.shape.constraints += 1;
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        if let Some(ref mut namespaces) = self.namespaces {
+            namespaces.path.push(name_fn().into());
+        }
+    }
+
+    fn pop_namespace(&mut self) {
+        if let Some(ref mut namespaces) = self.namespaces {
+            namespaces.path.pop();
+        }
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
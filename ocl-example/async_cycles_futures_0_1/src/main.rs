@@ -19,6 +19,9 @@
 //! design of the aforementioned `CommandGraph` but here we're managing the
 //! events manually for demonstration.
 //!
+//! Pass `--capture <FILE>` to record every enqueued command for later replay
+//! with the `replay` tool from the `command_capture` crate.
+//!
 
 // #![feature(conservative_impl_trait, unboxed_closures)]
 
@@ -26,8 +29,11 @@ extern crate chrono;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate ocl;
+extern crate command_capture;
+extern crate verify;
 #[macro_use] extern crate colorify;
 
+use std::env;
 use std::fmt::Debug;
 use std::thread::{self, JoinHandle};
 use std::sync::mpsc::{self, Receiver};
@@ -40,6 +46,8 @@ use ocl::error::{Error as OclError, Result as OclResult};
 use ocl::flags::{MemFlags, CommandQueueProperties};
 use ocl::prm::Int4;
 use ocl::ffi::{cl_event, c_void};
+use command_capture::{Capture, Arg as CaptureArg};
+use verify::{Verifier, Report};
 
 // Size of buffers and kernel work size:
 const WORK_SIZE: usize = 1 << 22;
@@ -129,7 +137,7 @@ pub fn completion_thread<T, E>(rx: Receiver<Option<CpuFuture<T, E>>>)
 /// ============
 ///
 /// Fill buffer with -999's just to ensure the upcoming write misses nothing:
-pub fn fill_junk(src_buf: &Buffer<Int4>, common_queue: &Queue,
+pub fn fill_junk(src_buf: &Buffer<Int4>, common_queue: &Queue, capture: &Capture,
         verify_init_event: Option<&Event>,
         kernel_event: Option<&Event>,
         fill_event: &mut Option<Event>,
@@ -159,12 +167,17 @@ pub fn fill_junk(src_buf: &Buffer<Int4>, common_queue: &Queue,
 
     *fill_event = Some(Event::empty());
 
+    let capture_cmd = capture.fill(src_buf, Int4::new(-999, -999, -999, -999),
+        [kernel_event, verify_init_event].iter().filter_map(|ev| *ev));
+
     src_buf.cmd().fill(Int4::new(-999, -999, -999, -999), None)
         .queue(common_queue)
         .ewait(&wait_list)
         .enew(fill_event.as_mut())
         .enq()?;
 
+    capture.bind_event(capture_cmd, fill_event.as_ref().unwrap());
+
     unsafe { fill_event.as_ref().unwrap()
         .set_callback(_print_complete, task_iter as *mut c_void)?; }
     Ok(())
@@ -179,7 +192,7 @@ pub fn fill_junk(src_buf: &Buffer<Int4>, common_queue: &Queue,
 /// the common queue and the `unmap` will automatically use the
 /// dedicated queue passed to the buffer during creation (unless we
 /// specify otherwise).
-pub fn write_init(src_buf: &Buffer<Int4>, common_queue: &Queue,
+pub fn write_init(src_buf: &Buffer<Int4>, common_queue: &Queue, capture: &Capture,
         write_init_unmap_queue: Queue,
         fill_event: Option<&Event>,
         verify_init_event: Option<&Event>,
@@ -195,6 +208,10 @@ pub fn write_init(src_buf: &Buffer<Int4>, common_queue: &Queue,
 
     *write_init_event = Some(Event::empty());
 
+    let capture_cmd = capture.map(src_buf, true,
+        [verify_init_event, fill_event].iter().filter_map(|ev| *ev));
+    let write_capture = capture.clone();
+
     let future_write_data = unsafe {
         src_buf.cmd().map()
             .queue(common_queue)
@@ -220,6 +237,7 @@ pub fn write_init(src_buf: &Buffer<Int4>, common_queue: &Queue,
 
     unsafe { write_init_event.as_ref().unwrap().set_callback(_write_complete,
         task_iter as *mut c_void)?; }
+    capture.bind_event(capture_cmd, write_init_event.as_ref().unwrap());
 
     Ok(Box::new(future_write_data.and_then(move |mut data| {
         printlnc!(teal_bold: "* Write init starting \t\t(iter: {}, t: {}s) ...",
@@ -228,6 +246,7 @@ pub fn write_init(src_buf: &Buffer<Int4>, common_queue: &Queue,
         for val in data.iter_mut() {
             *val = Int4::new(write_val, write_val, write_val, write_val);
         }
+        write_capture.set_payload(capture_cmd, &data[..]);
 
         Ok(task_iter)
     })))
@@ -242,13 +261,14 @@ pub fn write_init(src_buf: &Buffer<Int4>, common_queue: &Queue,
 /// queue for the verification completion event (used to signal the next
 /// command in the chain).
 pub fn verify_init(src_buf: &Buffer<Int4>, dst_vec: &RwVec<Int4>, common_queue: &Queue,
+        capture: &Capture,
         verify_init_queue: &Queue,
         write_init_event: Option<&Event>,
         verify_init_event: &mut Option<Event>,
         correct_val: i32, task_iter: i32)
-        // -> AndThen<PendingRwGuard<Int4>, OclResult<i32>,
-        //     impl FnOnce(RwGuard<Int4>) -> OclResult<i32>>
-        -> OclResult<Box<dyn Future<Item=i32, Error=OclError> + Send>> {
+        // -> AndThen<PendingRwGuard<Int4>, OclResult<Report>,
+        //     impl FnOnce(RwGuard<Int4>) -> OclResult<Report>>
+        -> OclResult<Box<dyn Future<Item=Report, Error=OclError> + Send>> {
     extern "C" fn _verify_starting(_: cl_event, _: i32, task_iter : *mut c_void) {
         printlnc!(blue_bold: "* Verify init starting \t\t(iter: {}, t: {}s) ...",
             task_iter as usize, timestamp());
@@ -259,6 +279,10 @@ pub fn verify_init(src_buf: &Buffer<Int4>, dst_vec: &RwVec<Int4>, common_queue:
     // event.
     let wait_list = [&write_init_event, &verify_init_event.as_ref()].into_raw_array();
 
+    let capture_cmd = capture.read(src_buf,
+        [write_init_event, verify_init_event.as_ref()].iter().filter_map(|ev| *ev));
+    let verify_capture = capture.clone();
+
     let mut future_read_data = src_buf.cmd().read(dst_vec)
         .queue(common_queue)
         .ewait(&wait_list)
@@ -272,23 +296,20 @@ pub fn verify_init(src_buf: &Buffer<Int4>, dst_vec: &RwVec<Int4>, common_queue:
     // Create a release event which is triggered when the read guard is dropped.
     *verify_init_event = Some(future_read_data.create_release_event(verify_init_queue)
         ?.clone());
+    capture.bind_event(capture_cmd, verify_init_event.as_ref().unwrap());
 
     // The future which will actually verify the initial value:
     Ok(Box::new(future_read_data.and_then(move |data| {
-        let mut val_count = 0;
+        verify_capture.set_payload(capture_cmd, &data[..]);
 
-        for (idx, val) in data.iter().enumerate() {
-            let cval = Int4::new(correct_val, correct_val, correct_val, correct_val);
-            if *val != cval {
-                return Err(format!("Verify init: Result value mismatch: {:?} != {:?} @ [{}]", val, cval, idx).into());
-            }
-            val_count += 1;
-        }
+        let report = Verifier::<Int4>::new()
+            .label(format!("Verify init (iter: {})", task_iter))
+            .compare_splat(&data, Int4::new(correct_val, correct_val, correct_val, correct_val));
 
         printlnc!(blue_bold: "* Verify init complete \t\t(iter: {}, t: {}s)",
             task_iter, timestamp());
 
-        Ok(val_count)
+        Ok(report)
     })))
 }
 
@@ -300,7 +321,7 @@ pub fn verify_init(src_buf: &Buffer<Int4>, dst_vec: &RwVec<Int4>, common_queue:
 ///
 /// The `Kernel complete ...` message is sometimes delayed slightly (a few
 /// microseconds) due to the time it takes the callback to trigger.
-pub fn kernel_add(kern: &Kernel, common_queue: &Queue,
+pub fn kernel_add(kern: &Kernel, common_queue: &Queue, capture: &Capture,
         verify_add_event: Option<&Event>,
         write_init_event: Option<&Event>,
         kernel_event: &mut Option<Event>,
@@ -331,6 +352,9 @@ pub fn kernel_add(kern: &Kernel, common_queue: &Queue,
     // Create an empty event ready to hold the new kernel event, overwriting any old one.
     *kernel_event = Some(Event::empty());
 
+    let capture_cmd = capture.enqueue_kernel(kern,
+        [verify_add_event, write_init_event].iter().filter_map(|ev| *ev))?;
+
     // Enqueues the kernel. Since we did not specify a default queue upon
     // creation (for no particular reason) we must specify it here. Also note
     // that the events that this kernel depends on are linked to the *unmap*,
@@ -346,6 +370,7 @@ pub fn kernel_add(kern: &Kernel, common_queue: &Queue,
     // Attach a status message printing callback to the kernel completion event:
     unsafe { kernel_event.as_ref().unwrap().set_callback(_print_complete,
         task_iter as *mut c_void)?; }
+    capture.bind_event(capture_cmd, kernel_event.as_ref().unwrap());
     Ok(())
 }
 
@@ -360,14 +385,14 @@ pub fn kernel_add(kern: &Kernel, common_queue: &Queue,
 /// This occasionally shows as having begun a few microseconds before the
 /// kernel has completed but that's just due to the slight callback delay on
 /// the kernel completion event.
-pub fn verify_add(dst_buf: &Buffer<Int4>, common_queue: &Queue,
+pub fn verify_add(dst_buf: &Buffer<Int4>, common_queue: &Queue, capture: &Capture,
         verify_add_unmap_queue: Queue,
         wait_event: Option<&Event>,
         verify_add_event: &mut Option<Event>,
         correct_val: i32, task_iter: i32)
-        // -> AndThen<FutureMemMap<Int4>, OclResult<i32>,
-        //     impl FnOnce(MemMap<Int4>) -> OclResult<i32>>
-        -> OclResult<Box<dyn Future<Item=i32, Error=OclError> + Send>> {
+        // -> AndThen<FutureMemMap<Int4>, OclResult<Report>,
+        //     impl FnOnce(MemMap<Int4>) -> OclResult<Report>>
+        -> OclResult<Box<dyn Future<Item=Report, Error=OclError> + Send>> {
     extern "C" fn _verify_starting(_: cl_event, _: i32, task_iter : *mut c_void) {
         printlnc!(lime_bold: "* Verify add starting \t\t(iter: {}, t: {}s) ...",
             task_iter as usize, timestamp());
@@ -378,6 +403,9 @@ pub fn verify_add(dst_buf: &Buffer<Int4>, common_queue: &Queue,
 
     *verify_add_event = Some(Event::empty());
 
+    let capture_cmd = capture.map(dst_buf, false, wait_event.into_iter());
+    let verify_capture = capture.clone();
+
     let future_read_data = unsafe {
         dst_buf.cmd().map()
             .queue(common_queue)
@@ -392,23 +420,19 @@ pub fn verify_add(dst_buf: &Buffer<Int4>, common_queue: &Queue,
             // queue would be used and could cause a deadlock:
             .with_unmap_queue(verify_add_unmap_queue)
     };
+    capture.bind_event(capture_cmd, verify_add_event.as_ref().unwrap());
 
     Ok(Box::new(future_read_data.and_then(move |data| {
-        let mut val_count = 0;
-        let cval = Int4::splat(correct_val);
+        verify_capture.set_payload(capture_cmd, &data[..]);
 
-        for (idx, val) in data.iter().enumerate() {
-            if *val != cval {
-                return Err(format!("Verify add: Result value mismatch: {:?} != {:?} @ [{}]",
-                    val, cval, idx).into());
-            }
-            val_count += 1;
-        }
+        let report = Verifier::<Int4>::new()
+            .label(format!("Verify add (iter: {})", task_iter))
+            .compare_splat(&data, Int4::splat(correct_val));
 
         printlnc!(lime_bold: "* Verify add complete \t\t(iter: {}, t: {}s)",
             task_iter, timestamp());
 
-        Ok(val_count)
+        Ok(report)
     })))
 }
 
@@ -483,6 +507,16 @@ pub fn async_cycles() -> OclResult<()> {
         .arg(&dst_buf)
         .build()?;
 
+    // Optionally capture every enqueued command (`--capture <FILE>`):
+    let capture = match env::args().skip_while(|arg| arg != "--capture").nth(1) {
+        Some(path) => Capture::to_file(path),
+        None => Capture::disabled(),
+    };
+
+    capture.program(&program, KERN_SRC);
+    capture.kernel(&kern, &program, "add_slowly", vec![capture.buffer_arg(&src_buf),
+        CaptureArg::scalar(&SCALAR_ADDEND), capture.buffer_arg(&dst_buf)])?;
+
     // A lockable vector for non-map reads.
     let rw_vec: RwVec<Int4> = RwVec::from(vec![Default::default(); WORK_SIZE]);
 
@@ -514,7 +548,7 @@ pub fn async_cycles() -> OclResult<()> {
 
         // 0. Fill-Junk
         // ============
-        fill_junk(&src_buf, &common_queue,
+        fill_junk(&src_buf, &common_queue, &capture,
             verify_init_event.as_ref(),
             kernel_event.as_ref(),
             &mut fill_event,
@@ -522,7 +556,7 @@ pub fn async_cycles() -> OclResult<()> {
 
         // 1. Map-Write-Init
         // ============
-        let write_init = write_init(&src_buf, &common_queue,
+        let write_init = write_init(&src_buf, &common_queue, &capture,
             write_init_unmap_queue.clone(),
             fill_event.as_ref(),
             verify_init_event.as_ref(),
@@ -531,7 +565,7 @@ pub fn async_cycles() -> OclResult<()> {
 
         // 2. Read-Verify-Init
         // ============
-        let verify_init = verify_init(&src_buf, &rw_vec, &common_queue,
+        let verify_init = verify_init(&src_buf, &rw_vec, &common_queue, &capture,
             &verify_init_queue,
             write_init_event.as_ref(),
             &mut verify_init_event,
@@ -539,7 +573,7 @@ pub fn async_cycles() -> OclResult<()> {
 
         // 3. Kernel-Add
         // =============
-        kernel_add(&kern, &common_queue,
+        kernel_add(&kern, &common_queue, &capture,
             verify_add_event.as_ref(),
             write_init_event.as_ref(),
             &mut kernel_event,
@@ -547,7 +581,7 @@ pub fn async_cycles() -> OclResult<()> {
 
         // 4. Map-Verify-Add
         // =================
-        let verify_add = verify_add(&dst_buf, &common_queue,
+        let verify_add = verify_add(&dst_buf, &common_queue, &capture,
             verify_add_unmap_queue.clone(),
             kernel_event.as_ref(),
             &mut verify_add_event,
@@ -556,7 +590,21 @@ pub fn async_cycles() -> OclResult<()> {
         printlnc!(orange: "All commands for iteration {} enqueued    (t: {}s)",
             task_iter, timestamp());
 
-        let join = write_init.join3(verify_init, verify_add);
+        // Fail the task with the full report(s) if either verification
+        // found mismatches:
+        let join = write_init.join3(verify_init, verify_add)
+            .and_then(|(task_iter, init_report, add_report)| {
+                let failures: Vec<String> = [init_report, add_report].iter()
+                    .filter(|report| !report.is_ok())
+                    .map(|report| report.to_string())
+                    .collect();
+
+                if failures.is_empty() {
+                    Ok(task_iter)
+                } else {
+                    Err(OclError::from(failures.join("\n")))
+                }
+            });
         let join_spawned = thread_pool.spawn(join);
 
         // This places our already spawned and running task into the queue for
@@ -569,6 +617,11 @@ pub fn async_cycles() -> OclResult<()> {
     tx.send(None).unwrap();
     completion_thread.join().unwrap();
 
+    if capture.is_enabled() {
+        capture.save()?;
+        printlnc!(white_bold: "Captured {} commands.", capture.cmd_count());
+    }
+
     printlnc!(yellow_bold: "All result values are correct! \n\
         Duration => | Total: {} seconds |", timestamp());
 
@@ -13,29 +13,60 @@ extern crate rand;
 extern crate chrono;
 extern crate ocl;
 extern crate ocl_extras as extras;
+extern crate command_capture;
+extern crate verify;
+extern crate tuning;
+extern crate requirements;
+extern crate subdevice;
 #[macro_use] extern crate colorify;
 
-use rand::{Rng, XorShiftRng};
-use rand::distributions::{IndependentSample, Range as RandRange};
+mod workload;
+
+use std::env;
+use std::path::PathBuf;
 use futures::{stream, Future, Sink, Stream, Join};
 use futures::sync::mpsc::{self, Sender};
 use futures_cpupool::{CpuPool, CpuFuture};
 use ocl::{Result as OclResult, Platform, Device, Context, Queue, Program, Kernel, OclPrm,
-    Event, EventList, FutureMemMap};
+    Event, EventList, FutureMemMap, Buffer};
 use ocl::flags::{MemFlags, MapFlags, CommandQueueProperties};
 use ocl::prm::Float4;
 use ocl::error::{Error as OclError};
 use crate::extras::{SubBufferPool, CommandGraph, Command, CommandDetails, KernelArgBuffer};
+use crate::workload::{Workload, TaskKind, TaskSpec};
+use command_capture::{Capture, CmdId, Arg as CaptureArg};
+use verify::{Verifier, Report};
+use tuning::{Autotuner, TuningDb};
+use requirements::Requirements;
+use subdevice::{Partition, SubDevices};
 
 const INITIAL_BUFFER_LEN: u32 = 1 << 24; // 512MiB of Float4
 const SUB_BUF_MIN_LEN: u32 = 1 << 15; // 1MiB of Float4
 const SUB_BUF_MAX_LEN: u32 = 1 << 19; // 16MiB of Float4
 
-
-enum TaskKind {
-    Simple,
-    Complex,
-}
+// Work sizes the generated kernels are tuned for with `--tune` (tasks in
+// between use the nearest):
+const TUNE_WORK_SIZES: [u32; 3] = [SUB_BUF_MIN_LEN, 1 << 17, SUB_BUF_MAX_LEN];
+
+static USAGE: &'static str = "\
+Usage: async_menagerie [OPTIONS]
+
+Options:
+    --seed <N>         Seed for workload generation (default: time based)
+    --device <IDX>     Index of the device within the default platform (default: 0)
+    --partition <SPEC> Split the device into sub-devices (`equally:N`,
+                       `counts:A,B,...` or `affinity:<numa|l4|l3|l2|l1|next>`)
+                       and run every task on one of them
+    --sub-device <IDX> Index of the sub-device to use with `--partition`
+                       (default: 0)
+    --record <FILE>    Write the generated task list to FILE
+    --replay <FILE>    Re-run the task list recorded in FILE
+    --capture <FILE>   Capture every enqueued command to FILE (see the
+                       `replay` tool in `command_capture`)
+    --tune             Autotune the generated kernels' local work sizes on
+                       the chosen device and save them to the tuning
+                       database before running
+    --help             Print this message";
 
 /// The specific details and pieces needed to execute the commands in the
 /// command graph.
@@ -48,11 +79,12 @@ struct Task {
     kind: TaskKind,
     work_size: u32,
     finish_events: EventList,
+    capture: Capture,
 }
 
 impl Task {
     /// Returns a new, empty task.
-    pub fn new(task_id: usize, kind: TaskKind, work_size: u32) -> Task {
+    pub fn new(task_id: usize, kind: TaskKind, work_size: u32, capture: Capture) -> Task {
         Task {
             task_id: task_id,
             cmd_graph: CommandGraph::new(),
@@ -61,6 +93,7 @@ impl Task {
             kind: kind,
             work_size: work_size,
             finish_events: EventList::new(),
+            capture: capture,
         }
     }
 
@@ -124,17 +157,24 @@ impl Task {
 
         let mut ev = Event::empty();
         let buf = buf_pool.get(buffer_id).unwrap();
+        let wait_events = self.cmd_graph.get_req_events(cmd_idx).unwrap();
+        let capture_cmd = self.capture.fill(buf, pattern, wait_events.iter());
 
         buf.cmd().fill(pattern, None)
-            .ewait(self.cmd_graph.get_req_events(cmd_idx).unwrap())
+            .ewait(wait_events)
             .enew(&mut ev)
             .enq().unwrap();
 
+        self.capture.bind_event(capture_cmd, &ev);
         self.cmd_graph.set_cmd_event(cmd_idx, ev).unwrap();
     }
 
     /// Map some memory for reading or writing.
-    pub fn map<T: OclPrm>(&self, cmd_idx: usize, buf_pool: &SubBufferPool<T>) -> FutureMemMap<T>
+    ///
+    /// Also returns the capture command id with which to record the data
+    /// written or read once the map completes.
+    pub fn map<T: OclPrm>(&self, cmd_idx: usize, buf_pool: &SubBufferPool<T>)
+            -> (FutureMemMap<T>, CmdId)
     {
         let (buffer_id, flags, is_write) = match *self.cmd_graph.commands()[cmd_idx].details(){
             CommandDetails::Write { target } => (target, MapFlags::new().write_invalidate_region(), true),
@@ -143,6 +183,8 @@ impl Task {
         };
 
         let buf = buf_pool.get(buffer_id).unwrap();
+        let capture_cmd = self.capture.map(buf, is_write,
+            self.cmd_graph.get_req_events(cmd_idx).unwrap().iter());
 
         // Set the wait list for the map command if this is a read and the
         // unmap command if this is an invalidating write.
@@ -161,9 +203,11 @@ impl Task {
 
         if is_write { future_data.set_unmap_wait_events(unmap_wait_list.unwrap()); }
         let unmap_event_target = future_data.create_unmap_event().unwrap().clone();
-        self.cmd_graph.set_cmd_event(cmd_idx, unmap_event_target.into()).unwrap();
+        let unmap_event: Event = unmap_event_target.into();
+        self.capture.bind_event(capture_cmd, &unmap_event);
+        self.cmd_graph.set_cmd_event(cmd_idx, unmap_event).unwrap();
 
-        future_data
+        (future_data, capture_cmd)
     }
 
     /// Copy contents of one buffer to another.
@@ -176,12 +220,15 @@ impl Task {
         let mut ev = Event::empty();
         let src_buf = buf_pool.get(src_buf_id).unwrap();
         let tar_buf = buf_pool.get(tar_buf_id).unwrap();
+        let wait_events = self.cmd_graph.get_req_events(cmd_idx).unwrap();
+        let capture_cmd = self.capture.copy(src_buf, tar_buf, wait_events.iter());
 
         src_buf.cmd().copy(tar_buf, None, None)
-            .ewait(self.cmd_graph.get_req_events(cmd_idx).unwrap())
+            .ewait(wait_events)
             .enew(&mut ev)
             .enq().unwrap();
 
+        self.capture.bind_event(capture_cmd, &ev);
         self.cmd_graph.set_cmd_event(cmd_idx, ev).unwrap();
     }
 
@@ -193,18 +240,32 @@ impl Task {
         };
 
         let mut ev = Event::empty();
+        let wait_events = self.cmd_graph.get_req_events(cmd_idx).unwrap();
+        let capture_cmd = self.capture.enqueue_kernel(&self.kernels[kernel_id], wait_events.iter())
+            .unwrap();
 
         unsafe {
             self.kernels[kernel_id].cmd().enew(&mut ev)
-                .ewait(self.cmd_graph.get_req_events(cmd_idx).unwrap())
+                .ewait(wait_events)
                 .enq().unwrap();
         }
 
+        self.capture.bind_event(capture_cmd, &ev);
         self.cmd_graph.set_cmd_event(cmd_idx, ev).unwrap();
     }
 }
 
 
+/// What the generated kernels need from a device. They all run on sub-buffers
+/// of one pool buffer, which must fit in a single allocation.
+fn kernel_requirements() -> Vec<Requirements> {
+    ["kern", "kernel_a", "kernel_b", "kernel_c"].iter()
+        .map(|&name| Requirements::new(name)
+            .allocation::<Float4>("buffer pool", INITIAL_BUFFER_LEN as usize))
+        .collect()
+}
+
+
 fn coeff(add: bool) -> f32 {
     if add { 1. } else { -1. }
 }
@@ -270,13 +331,14 @@ fn gen_kern_src(kernel_name: &str, type_str: &str, simple: bool, add: bool) -> S
 /// (2) Read data
 ///
 fn create_simple_task(task_id: usize, device: Device, context: &Context,
-        buf_pool: &mut SubBufferPool<Float4>, work_size: u32, queues: &[Queue]) -> Result<Task, ()>
+        buf_pool: &mut SubBufferPool<Float4>, work_size: u32, queues: &[Queue],
+        capture: &Capture, tuning_db: &TuningDb) -> Result<Task, ()>
 {
     let write_buf_flags = Some(MemFlags::new().read_only() | MemFlags::new().host_write_only());
     let read_buf_flags = Some(MemFlags::new().write_only() | MemFlags::new().host_read_only());
 
     // The container for this task:
-    let mut task = Task::new(task_id, TaskKind::Simple, work_size);
+    let mut task = Task::new(task_id, TaskKind::Simple, work_size, capture.clone());
 
     // Allocate our input buffer:
     let write_buf_id = match buf_pool.alloc(work_size, write_buf_flags) {
@@ -296,9 +358,11 @@ fn create_simple_task(task_id: usize, device: Device, context: &Context,
     buf_pool.get_mut(write_buf_id).unwrap().set_default_queue(queues[0].clone());
     buf_pool.get_mut(read_buf_id).unwrap().set_default_queue(queues[1].clone());
 
+    let src = gen_kern_src("kern", "float4", true, true);
+
     let program = Program::builder()
         .devices(device)
-        .src(gen_kern_src("kern", "float4", true, true))
+        .src(src.clone())
         .build(context).unwrap();
 
     let kern = Kernel::builder()
@@ -306,11 +370,19 @@ fn create_simple_task(task_id: usize, device: Device, context: &Context,
         .name("kern")
         .queue(queues[2].clone())
         .global_work_size(work_size)
+        .local_work_size(tuning_db.lws(device, "kern", work_size as usize))
         .arg(buf_pool.get(write_buf_id).unwrap())
         .arg(Float4::new(100., 100., 100., 100.))
         .arg(buf_pool.get(read_buf_id).unwrap())
         .build().unwrap();
 
+    capture.program(&program, &src);
+    capture.kernel(&kern, &program, "kern", vec![
+        capture.buffer_arg(buf_pool.get(write_buf_id).unwrap()),
+        CaptureArg::scalar(&Float4::new(100., 100., 100., 100.)),
+        capture.buffer_arg(buf_pool.get(read_buf_id).unwrap()),
+    ]).unwrap();
+
     // (0) Initial write to device:
     assert!(task.add_write_command(write_buf_id).unwrap() == 0);
 
@@ -329,16 +401,19 @@ fn create_simple_task(task_id: usize, device: Device, context: &Context,
 
 /// Enqueues a unique simple task as defined above.
 fn enqueue_simple_task(task: &Task, buf_pool: &SubBufferPool<Float4>, thread_pool: &CpuPool,
-        tx: Sender<usize>) -> Join<CpuFuture<usize, OclError>, CpuFuture<Sender<usize>, OclError>>
+        tx: Sender<Report>) -> Join<CpuFuture<usize, OclError>, CpuFuture<Sender<Report>, OclError>>
 {
     // Do some extra work:
     let task_id = task.task_id;
+    let (write_capture, verify_capture) = (task.capture.clone(), task.capture.clone());
 
     // (0) Write a bunch of 50's:
-    let write = task.map(0, &buf_pool).and_then(move |mut data| {
+    let (write_data, write_cmd) = task.map(0, &buf_pool);
+    let write = write_data.and_then(move |mut data| {
         for val in data.iter_mut() {
             *val = Float4::new(50., 50., 50., 50.);
         }
+        write_capture.set_payload(write_cmd, &data[..]);
 
         printlnc!(green: "Task [{}] (simple): Buffer initialized.", task_id);
 
@@ -352,22 +427,23 @@ fn enqueue_simple_task(task: &Task, buf_pool: &SubBufferPool<Float4>, thread_poo
     task.kernel(1);
 
     // (2) Read results and verify them:
-    let verify = task.map(2, &buf_pool)
+    let (verify_data, verify_cmd) = task.map(2, &buf_pool);
+    let verify = verify_data
         .and_then(move |data| {
-            let mut val_count = 0usize;
+            verify_capture.set_payload(verify_cmd, &data[..]);
 
-            for val in data.iter() {
-                let correct_val = Float4::new(150., 150., 150., 150.);
-                if *val != correct_val {
-                    return Err(format!("Result value mismatch: {:?} != {:?}", val, correct_val).into())
-                }
-                val_count += 1;
-            }
+            let report = Verifier::<Float4>::new()
+                .label(format!("Task [{}] (simple)", task_id))
+                .compare_splat(&data, Float4::new(150., 150., 150., 150.));
 
-            printlnc!(yellow: "Task [{}] (simple): Verify successful: \
-                {} values correct.", task_id, val_count);
+            if report.is_ok() {
+                printlnc!(yellow: "Task [{}] (simple): Verify successful: \
+                    {} values correct.", task_id, report.elements);
+            } else {
+                printlnc!(red_bold: "{}", report);
+            }
 
-            Ok(tx.send(val_count))
+            Ok(tx.send(report))
         })
         .and_then(|send| send.map_err(|e| OclError::from(e)));
 
@@ -400,10 +476,10 @@ fn enqueue_simple_task(task: &Task, buf_pool: &SubBufferPool<Float4>, thread_poo
 ///
 fn create_complex_task(task_id: usize, device: Device, context: &Context,
         buf_pool: &mut SubBufferPool<Float4>, work_size: u32, queues: &[Queue],
-        rng: &mut XorShiftRng) -> Result<Task, ()>
+        spec: &TaskSpec, capture: &Capture, tuning_db: &TuningDb) -> Result<Task, ()>
 {
     // The container for this task:
-    let mut task = Task::new(task_id, TaskKind::Complex, work_size);
+    let mut task = Task::new(task_id, TaskKind::Complex, work_size, capture.clone());
 
     let buffer_count = 7;
 
@@ -439,18 +515,22 @@ fn create_complex_task(task_id: usize, device: Device, context: &Context,
         }
     }
 
-    let kern_a_sign = rng.gen();
-    let kern_b_sign = rng.gen();
-    let kern_c_sign = rng.gen();
-    let kern_a_val = RandRange::new(-1000., 1000.).ind_sample(rng);
-    let kern_b_val = RandRange::new(-500., 500.).ind_sample(rng);
-    let kern_c_val = RandRange::new(-2000., 2000.).ind_sample(rng);
+    // Kernel signs and values come from the (seeded or replayed) workload:
+    let (kern_a_sign, kern_a_val) = (spec.kernel_ops[0].add, spec.kernel_ops[0].val);
+    let (kern_b_sign, kern_b_val) = (spec.kernel_ops[1].add, spec.kernel_ops[1].val);
+    let (kern_c_sign, kern_c_val) = (spec.kernel_ops[2].add, spec.kernel_ops[2].val);
+
+    let srcs = [
+        gen_kern_src("kernel_a", "float4", true, kern_a_sign),
+        gen_kern_src("kernel_b", "float4", false, kern_b_sign),
+        gen_kern_src("kernel_c", "float4", true, kern_c_sign),
+    ];
 
     let program = Program::builder()
         .devices(device)
-        .src(gen_kern_src("kernel_a", "float4", true, kern_a_sign))
-        .src(gen_kern_src("kernel_b", "float4", false, kern_b_sign))
-        .src(gen_kern_src("kernel_c", "float4", true, kern_c_sign))
+        .src(srcs[0].clone())
+        .src(srcs[1].clone())
+        .src(srcs[2].clone())
         .build(context).unwrap();
 
     let kernel_a = Kernel::builder()
@@ -458,6 +538,7 @@ fn create_complex_task(task_id: usize, device: Device, context: &Context,
         .name("kernel_a")
         .queue(queues[7].clone())
         .global_work_size(work_size)
+        .local_work_size(tuning_db.lws(device, "kernel_a", work_size as usize))
         .arg(buf_pool.get(buffer_ids[0]).unwrap())
         .arg(&Float4::new(kern_a_val, kern_a_val, kern_a_val, kern_a_val))
         .arg(buf_pool.get(buffer_ids[1]).unwrap())
@@ -468,6 +549,7 @@ fn create_complex_task(task_id: usize, device: Device, context: &Context,
         .name("kernel_b")
         .queue(queues[7].clone())
         .global_work_size(work_size)
+        .local_work_size(tuning_db.lws(device, "kernel_b", work_size as usize))
         .arg(buf_pool.get(buffer_ids[2]).unwrap())
         .arg(buf_pool.get(buffer_ids[3]).unwrap())
         .arg(buf_pool.get(buffer_ids[4]).unwrap())
@@ -480,11 +562,21 @@ fn create_complex_task(task_id: usize, device: Device, context: &Context,
         .name("kernel_c")
         .queue(queues[7].clone())
         .global_work_size(work_size)
+        .local_work_size(tuning_db.lws(device, "kernel_c", work_size as usize))
         .arg(buf_pool.get(buffer_ids[5]).unwrap())
         .arg(&Float4::new(kern_c_val, kern_c_val, kern_c_val, kern_c_val))
         .arg(buf_pool.get(buffer_ids[6]).unwrap())
         .build().unwrap();
 
+    capture.program(&program, &srcs.join("\n"));
+    let buf_arg = |idx: usize| capture.buffer_arg(buf_pool.get(buffer_ids[idx]).unwrap());
+    capture.kernel(&kernel_a, &program, "kernel_a", vec![buf_arg(0),
+        CaptureArg::scalar(&Float4::splat(kern_a_val)), buf_arg(1)]).unwrap();
+    capture.kernel(&kernel_b, &program, "kernel_b", vec![buf_arg(2), buf_arg(3), buf_arg(4),
+        CaptureArg::scalar(&Float4::splat(kern_b_val)), buf_arg(5)]).unwrap();
+    capture.kernel(&kernel_c, &program, "kernel_c", vec![buf_arg(5),
+        CaptureArg::scalar(&Float4::splat(kern_c_val)), buf_arg(6)]).unwrap();
+
     // (0) Initially write 500s:
     assert!(task.add_write_command(buffer_ids[0]).unwrap() == 0);
 
@@ -533,15 +625,18 @@ fn create_complex_task(task_id: usize, device: Device, context: &Context,
 
 /// Enqueues a unique complex task as defined above.
 fn enqueue_complex_task(task: &Task, buf_pool: &SubBufferPool<Float4>, thread_pool: &CpuPool,
-        tx: Sender<usize>) -> Join<CpuFuture<usize, OclError>, CpuFuture<Sender<usize>, OclError>>
+        tx: Sender<Report>) -> Join<CpuFuture<usize, OclError>, CpuFuture<Sender<Report>, OclError>>
 {
     let task_id = task.task_id;
+    let (write_capture, verify_capture) = (task.capture.clone(), task.capture.clone());
 
     // (0) Initially write 500s:
-    let write = task.map(0, &buf_pool).and_then(move |mut data| {
+    let (write_data, write_cmd) = task.map(0, &buf_pool);
+    let write = write_data.and_then(move |mut data| {
         for val in data.iter_mut() {
             *val = Float4::new(500., 500., 500., 500.);
         }
+        write_capture.set_payload(write_cmd, &data[..]);
 
         printlnc!(green_bold: "Task [{}] (complex): Buffer initialized.", task_id);
 
@@ -569,22 +664,23 @@ fn enqueue_complex_task(task: &Task, buf_pool: &SubBufferPool<Float4>, thread_po
     // (7) Finally read and verify:
     let expected_result = task.expected_result.unwrap();
 
-    let verify = task.map(7, &buf_pool)
+    let (verify_data, verify_cmd) = task.map(7, &buf_pool);
+    let verify = verify_data
         .and_then(move |data| {
-            let mut val_count = 0usize;
+            verify_capture.set_payload(verify_cmd, &data[..]);
 
-            for val in data.iter() {
-                let correct_val = expected_result;
-                if *val != correct_val {
-                    return Err(format!("Result value mismatch: {:?} != {:?}", val, correct_val).into())
-                }
-                val_count += 1;
-            }
+            let report = Verifier::<Float4>::new()
+                .label(format!("Task [{}] (complex)", task_id))
+                .compare_splat(&data, expected_result);
 
-            printlnc!(yellow_bold: "Task [{}] (complex): Verify successful: \
-                {} values correct.", task_id, val_count);
+            if report.is_ok() {
+                printlnc!(yellow_bold: "Task [{}] (complex): Verify successful: \
+                    {} values correct.", task_id, report.elements);
+            } else {
+                printlnc!(red_bold: "{}", report);
+            }
 
-            Ok(tx.send(val_count))
+            Ok(tx.send(report))
         })
         .and_then(|send| send.map_err(|e| OclError::from(e)));
 
@@ -595,6 +691,62 @@ fn enqueue_complex_task(task: &Task, buf_pool: &SubBufferPool<Float4>, thread_po
 }
 
 
+/// Autotunes the local work size of each generated kernel at each of
+/// `TUNE_WORK_SIZES` and saves the results to `tuning_db`.
+fn tune_generated_kernels(device: Device, context: &Context, tuning_db: &mut TuningDb)
+        -> OclResult<()>
+{
+    let queue = Queue::new(context, device, None)?;
+    let buffers = (0..4).map(|_| Buffer::<Float4>::builder()
+            .queue(queue.clone())
+            .len(SUB_BUF_MAX_LEN)
+            .fill_val(Float4::new(1., 1., 1., 1.))
+            .build())
+        .collect::<OclResult<Vec<_>>>()?;
+    let values = Float4::new(1., 1., 1., 1.);
+    let kernels = [("kern", true), ("kernel_a", true), ("kernel_b", false), ("kernel_c", true)];
+
+    printlnc!(white_bold: "Tuning generated kernels...");
+
+    for &(name, simple) in kernels.iter() {
+        let program = Program::builder()
+            .devices(device)
+            .src(gen_kern_src(name, "float4", simple, true))
+            .build(context)?;
+
+        for &work_size in TUNE_WORK_SIZES.iter() {
+            let mut builder = Kernel::builder();
+            builder.program(&program)
+                .name(name)
+                .queue(queue.clone())
+                .global_work_size(work_size);
+
+            if simple {
+                builder.arg(&buffers[0]).arg(&values).arg(&buffers[3]);
+            } else {
+                builder.arg(&buffers[0]).arg(&buffers[1]).arg(&buffers[2]).arg(&values)
+                    .arg(&buffers[3]);
+            }
+
+            let kernel = builder.build()?;
+            let tuning = Autotuner::new(&kernel)?.run(work_size as usize)?;
+
+            match tuning.result.local_work_size {
+                Some(lws) => printlnc!(teal: "Tuned '{}' @ {}: local work size {} \
+                    ({:.2}x driver choice).", name, work_size, lws, tuning.result.speedup()),
+                None => printlnc!(teal: "Tuned '{}' @ {}: the driver's choice is fastest.",
+                    name, work_size),
+            }
+            tuning_db.insert(device, name, tuning.result)?;
+        }
+    }
+
+    tuning_db.save()?;
+    printlnc!(white_bold: "Saved tuning results to '{}'.", tuning_db.path().display());
+    Ok(())
+}
+
+
 /// Returns a nicely formatted duration in seconds.
 fn fmt_duration(duration: chrono::Duration) -> String {
     let el_sec = duration.num_seconds();
@@ -603,31 +755,137 @@ fn fmt_duration(duration: chrono::Duration) -> String {
 }
 
 
+/// Command line options.
+pub struct Options {
+    seed: Option<u64>,
+    device_idx: Option<usize>,
+    partition: Option<Partition>,
+    sub_device_idx: usize,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    capture: Option<PathBuf>,
+    tune: bool,
+}
+
+impl Options {
+    /// Parses options from the process arguments, returning `None` if usage
+    /// should be printed instead.
+    fn from_args() -> OclResult<Option<Options>> {
+        let mut opts = Options { seed: None, device_idx: None, partition: None,
+            sub_device_idx: 0, record: None, replay: None, capture: None, tune: false };
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| -> OclResult<String> {
+                args.next().ok_or_else(|| format!("Missing value for '{}'.", name).into())
+            };
+
+            match arg.as_str() {
+                "--seed" => opts.seed = Some(value("--seed")?.parse()
+                    .map_err(|err| format!("Invalid seed: {}", err))?),
+                "--device" => opts.device_idx = Some(value("--device")?.parse()
+                    .map_err(|err| format!("Invalid device index: {}", err))?),
+                "--partition" => opts.partition = Some(value("--partition")?.parse::<Partition>()?),
+                "--sub-device" => opts.sub_device_idx = value("--sub-device")?.parse()
+                    .map_err(|err| format!("Invalid sub-device index: {}", err))?,
+                "--record" => opts.record = Some(value("--record")?.into()),
+                "--replay" => opts.replay = Some(value("--replay")?.into()),
+                "--capture" => opts.capture = Some(value("--capture")?.into()),
+                "--tune" => opts.tune = true,
+                "--help" | "-h" => return Ok(None),
+                other => return Err(format!("Unknown argument: '{}'\n\n{}", other, USAGE).into()),
+            }
+        }
+
+        if opts.seed.is_some() && opts.replay.is_some() {
+            return Err("'--seed' and '--replay' cannot be used together.".into());
+        }
+
+        Ok(Some(opts))
+    }
+}
+
+
 /// Creates a large number of both simple and complex asynchronous tasks and
 /// verifies that they all execute correctly.
-pub fn async_menagerie() -> OclResult<()> {
-    // Buffer/work size range:
-    let buffer_size_range = RandRange::new(SUB_BUF_MIN_LEN, SUB_BUF_MAX_LEN);
-    let mut rng = rand::weak_rng();
+pub fn async_menagerie(opts: Options) -> OclResult<()> {
+    // Workload, either replayed from a file or generated from a seed:
+    let mut workload = match opts.replay {
+        Some(ref path) => {
+            let workload = Workload::replay(path)?;
+            printlnc!(white_bold: "Replaying {} tasks from '{}' (seed: {}).",
+                workload.remaining().unwrap_or(0), path.display(), workload.seed());
+            workload
+        },
+        None => {
+            let seed = opts.seed.unwrap_or_else(workload::time_seed);
+            printlnc!(white_bold: "Seed: {} (rerun with '--seed {}').", seed, seed);
+            Workload::seeded(seed, 0, SUB_BUF_MIN_LEN, SUB_BUF_MAX_LEN)
+        },
+    };
+
+    if let Some(device_idx) = opts.device_idx {
+        workload.set_device_idx(device_idx);
+    }
 
-    // Set up context using defaults:
+    // Set up context using the default platform and an explicitly chosen device:
     let platform = Platform::default();
     printlnc!(blue: "Platform: {}", platform.name()?);
 
-    // let device = Device::first(platform);
-    let device_idx = RandRange::new(0, 15).ind_sample(&mut rng);
+    let devices = Device::list_all(platform)?;
+    let device = match devices.get(workload.device_idx()) {
+        Some(&device) => device,
+        None => return Err(format!("Device index {} is out of range (platform has {} devices).",
+            workload.device_idx(), devices.len()).into()),
+    };
 
-    let device = Device::specifier()
-        .wrapping_indices(vec![device_idx])
-        .to_device_list(Some(platform))?[0];
+    printlnc!(teal: "Device [{}]: {} {}", workload.device_idx(), device.vendor()?, device.name()?);
+
+    // Optionally pin the tasks to one sub-device (e.g. a group of CPU cores).
+    // The sub-device is released when `sub_device` is dropped, after
+    // everything using it:
+    let sub_device = match opts.partition {
+        Some(ref partition) => {
+            let subs = SubDevices::create(device, partition.clone())?;
+            match subs.devices().get(opts.sub_device_idx) {
+                Some(sub_device) => {
+                    printlnc!(teal: "Sub-device [{}] of {} ({}): {} compute units",
+                        opts.sub_device_idx, subs.len(), subs.partition(),
+                        sub_device.info(ocl::enums::DeviceInfo::MaxComputeUnits)?);
+                    Some(sub_device.clone())
+                },
+                None => return Err(format!("Sub-device index {} is out of range ({} \
+                    sub-devices).", opts.sub_device_idx, subs.len()).into()),
+            }
+        },
+        None => None,
+    };
+    let device = sub_device.as_ref().map_or(device, |sub_device| **sub_device);
 
-    printlnc!(teal: "Device: {} {}", device.vendor()?, device.name()?);
+    requirements::check_all(&kernel_requirements(), device)?;
 
     let context = Context::builder()
         .platform(platform)
         .devices(device)
         .build()?;
 
+    // Tuned local work sizes for the generated kernels (driver chosen for
+    // any not yet tuned on this device). A database which can't be read is
+    // only an error when tuning, which would overwrite it:
+    let tuning_db = if opts.tune {
+        let mut tuning_db = TuningDb::load_default()?;
+        tune_generated_kernels(device, &context, &mut tuning_db)?;
+        tuning_db
+    } else {
+        TuningDb::load_default_or_empty()
+    };
+
+    // Command stream capture (does nothing unless enabled):
+    let capture = match opts.capture {
+        Some(ref path) => Capture::to_file(path),
+        None => Capture::disabled(),
+    };
+
     // Queues (events coordinated by command graph):
     let queue_flags = Some(CommandQueueProperties::new().out_of_order());
     let queues_simple: Vec<_> = (0..3).map(|_| Queue::new(&context, device, queue_flags)
@@ -646,35 +904,34 @@ pub fn async_menagerie() -> OclResult<()> {
     // Our thread pool for offloading reading, writing, and other host-side processing.
     let thread_pool = CpuPool::new_num_cpus();
     let mut correct_val_count = 0usize;
+    let mut failed_reports = Vec::new();
 
-    // Channels are used to communicate result counts (this isn't really
-    // necessary here but shown for demonstration):
+    // Channels are used to communicate verification reports:
     let (tx, mut rx) = mpsc::channel(1);
 
     let start_time = chrono::Local::now();
     printlnc!(white_bold: "Creating and enqueuing tasks...");
 
-    // Create some arbitrary tasks until our buffer pool is full:
-    loop {
-        // Random work size:
-        let work_size = buffer_size_range.ind_sample(&mut rng);
-
+    // Create tasks until our buffer pool is full (or a replayed workload
+    // runs out):
+    while let Some(spec) = workload.next_spec() {
         // Create task if there is room in the buffer pool:
-        let task_id = tasks.len();
-
-        let task_res = if rng.gen() {
-        // let task_res = if false {
-            create_simple_task(task_id, device, &context, &mut buf_pool, work_size,
-                &queues_simple)
-        } else {
-            create_complex_task(task_id, device, &context, &mut buf_pool, work_size,
-                &queues_complex, &mut rng)
+        let task_res = match spec.kind {
+            TaskKind::Simple => create_simple_task(spec.task_id, device, &context,
+                &mut buf_pool, spec.work_size, &queues_simple, &capture, &tuning_db),
+            TaskKind::Complex => create_complex_task(spec.task_id, device, &context,
+                &mut buf_pool, spec.work_size, &queues_complex, &spec, &capture, &tuning_db),
         };
 
         let task = match task_res {
             Ok(task) => task,
             Err(_) => {
+                workload.discard_last();
                 println!("Buffer pool is now full.");
+                if workload.is_replay() {
+                    printlnc!(red_bold: "Warning: Replay stopped after {} tasks; \
+                        the buffer pool on this device is too small.", tasks.len());
+                }
                 break;
             },
         };
@@ -685,6 +942,14 @@ pub fn async_menagerie() -> OclResult<()> {
         }
     }
 
+    // Record the task list before waiting so that it is available even if
+    // the run fails:
+    if let Some(ref path) = opts.record {
+        workload.save(path)?;
+        printlnc!(white_bold: "Recorded {} tasks to '{}'.", workload.recorded().len(),
+            path.display());
+    }
+
     let create_enqueue_duration = chrono::Local::now() - start_time;
     let task_count = tasks.len();
     printlnc!(white_bold: "Waiting on {} tasks to complete...", task_count);
@@ -696,8 +961,22 @@ pub fn async_menagerie() -> OclResult<()> {
 
     rx.close();
 
-    for count in rx.wait() {
-        correct_val_count += count.unwrap();
+    for report in rx.wait() {
+        let report = report.unwrap();
+        correct_val_count += report.matched_elements();
+        if !report.is_ok() { failed_reports.push(report); }
+    }
+
+    if let Some(ref path) = opts.capture {
+        capture.save()?;
+        printlnc!(white_bold: "Captured {} commands to '{}'.", capture.cmd_count(), path.display());
+    }
+
+    if !failed_reports.is_empty() {
+        return Err(format!("{} of {} tasks produced incorrect results ({} values correct). \
+            Rerun with '--seed {}' or replay a recording to reproduce. First failure:\n{}",
+            failed_reports.len(), task_count, correct_val_count, workload.seed(),
+            failed_reports[0]).into());
     }
 
     let run_duration = chrono::Local::now() - start_time - create_enqueue_duration;
@@ -713,7 +992,13 @@ pub fn async_menagerie() -> OclResult<()> {
 
 
 pub fn main() {
-    match async_menagerie() {
+    let res = match Options::from_args() {
+        Ok(Some(opts)) => async_menagerie(opts),
+        Ok(None) => { println!("{}", USAGE); Ok(()) },
+        Err(err) => Err(err),
+    };
+
+    match res {
         Ok(_) => (),
         Err(err) => println!("{}", err),
     }
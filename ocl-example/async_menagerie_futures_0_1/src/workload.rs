@@ -0,0 +1,293 @@
+//! Seedable, recordable workload generation.
+//!
+//! Every random decision made by a menagerie run (task kind, work size,
+//! kernel signs and addends) is drawn from a single `XorShiftRng` seeded from
+//! a `u64`. The resulting task list can be written to a plain text file and
+//! later replayed exactly, regardless of seed.
+//!
+//! Workload file format (one entry per line, `#` starts a comment):
+//!
+//! ```text
+//! seed 1234
+//! device 0
+//! task simple 65536
+//! task complex 131072 +447a0000 -c3fa0000 +44fa0000
+//! ```
+//!
+//! Kernel addends are stored as the hex bits of their `f32` value so that a
+//! replayed run computes bit-identical expected results.
+//!
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write, BufWriter};
+use std::path::Path;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use rand::distributions::{IndependentSample, Range as RandRange};
+use ocl::error::{Error as OclError, Result as OclResult};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskKind {
+    Simple,
+    Complex,
+}
+
+/// The sign and value a generated kernel applies to its input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KernelOp {
+    pub add: bool,
+    pub val: f32,
+}
+
+impl fmt::Display for KernelOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{:08x}", if self.add { '+' } else { '-' }, self.val.to_bits())
+    }
+}
+
+/// Everything needed to recreate a single task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskSpec {
+    pub task_id: usize,
+    pub kind: TaskKind,
+    pub work_size: u32,
+    /// Kernel sign/value pairs, empty for simple tasks and one each for
+    /// kernels a, b and c of complex tasks.
+    pub kernel_ops: Vec<KernelOp>,
+}
+
+impl fmt::Display for TaskSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            TaskKind::Simple => write!(f, "task simple {}", self.work_size)?,
+            TaskKind::Complex => write!(f, "task complex {}", self.work_size)?,
+        }
+        for op in self.kernel_ops.iter() {
+            write!(f, " {}", op)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Expands a `u64` seed into the four non-zero words `XorShiftRng` requires
+/// (splitmix64).
+fn expand_seed(seed: u64) -> [u32; 4] {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    let (a, b) = (next(), next());
+    let words = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+    if words.iter().all(|&w| w == 0) { [1, 0, 0, 0] } else { words }
+}
+
+/// Returns a seed derived from the current time.
+pub fn time_seed() -> u64 {
+    let now = chrono::Local::now();
+    (now.timestamp() as u64).wrapping_mul(1_000_000_007) ^ now.timestamp_subsec_nanos() as u64
+}
+
+
+enum Source {
+    Seeded(XorShiftRng),
+    Replay(VecDeque<TaskSpec>),
+}
+
+/// Produces task specifications, either freshly generated from a seed or
+/// read back from a recorded workload file, and records every specification
+/// handed out.
+pub struct Workload {
+    seed: u64,
+    device_idx: usize,
+    source: Source,
+    work_size_range: RandRange<u32>,
+    recorded: Vec<TaskSpec>,
+}
+
+impl Workload {
+    /// Returns a new generator seeded with `seed`. Work sizes are drawn from
+    /// `[work_size_min, work_size_max)`.
+    pub fn seeded(seed: u64, device_idx: usize, work_size_min: u32, work_size_max: u32)
+            -> Workload
+    {
+        Workload {
+            seed: seed,
+            device_idx: device_idx,
+            source: Source::Seeded(XorShiftRng::from_seed(expand_seed(seed))),
+            work_size_range: RandRange::new(work_size_min, work_size_max),
+            recorded: Vec::with_capacity(256),
+        }
+    }
+
+    /// Loads a previously recorded workload file.
+    pub fn replay<P: AsRef<Path>>(path: P) -> OclResult<Workload> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|err| format!("Unable to open workload file '{}': {}", path.display(), err))?;
+
+        let mut seed = None;
+        let mut device_idx = None;
+        let mut specs = VecDeque::new();
+
+        for (line_idx, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|err| OclError::from(err.to_string()))?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue; }
+
+            let err = |msg: &str| -> OclError {
+                format!("{}:{}: {}: '{}'", path.display(), line_idx + 1, msg, line).into()
+            };
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("seed") => seed = Some(words.next().and_then(|w| w.parse().ok())
+                    .ok_or_else(|| err("Invalid seed"))?),
+                Some("device") => device_idx = Some(words.next().and_then(|w| w.parse().ok())
+                    .ok_or_else(|| err("Invalid device index"))?),
+                Some("task") => {
+                    let kind = match words.next() {
+                        Some("simple") => TaskKind::Simple,
+                        Some("complex") => TaskKind::Complex,
+                        _ => return Err(err("Invalid task kind")),
+                    };
+                    let work_size = words.next().and_then(|w| w.parse().ok())
+                        .ok_or_else(|| err("Invalid work size"))?;
+                    let kernel_ops = words.map(|w| parse_kernel_op(w).ok_or_else(|| err("Invalid kernel op")))
+                        .collect::<OclResult<Vec<_>>>()?;
+
+                    let expected_op_count = match kind { TaskKind::Simple => 0, TaskKind::Complex => 3 };
+                    if kernel_ops.len() != expected_op_count {
+                        return Err(err("Wrong number of kernel ops"));
+                    }
+
+                    specs.push_back(TaskSpec { task_id: specs.len(), kind, work_size, kernel_ops });
+                },
+                _ => return Err(err("Unknown entry")),
+            }
+        }
+
+        Ok(Workload {
+            seed: seed.unwrap_or(0),
+            device_idx: device_idx.unwrap_or(0),
+            source: Source::Replay(specs),
+            work_size_range: RandRange::new(0, 1),
+            recorded: Vec::with_capacity(256),
+        })
+    }
+
+    /// The seed used to generate this workload.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The index of the device (within the platform) this workload runs on.
+    pub fn device_idx(&self) -> usize {
+        self.device_idx
+    }
+
+    /// Overrides the device index (for replaying a workload elsewhere).
+    pub fn set_device_idx(&mut self, device_idx: usize) {
+        self.device_idx = device_idx;
+    }
+
+    /// Returns `true` if this workload was loaded from a file.
+    pub fn is_replay(&self) -> bool {
+        match self.source {
+            Source::Replay(_) => true,
+            Source::Seeded(_) => false,
+        }
+    }
+
+    /// The number of tasks left in a replayed workload (`None` if seeded).
+    pub fn remaining(&self) -> Option<usize> {
+        match self.source {
+            Source::Replay(ref specs) => Some(specs.len()),
+            Source::Seeded(_) => None,
+        }
+    }
+
+    /// Returns the specification for the next task, or `None` if a replayed
+    /// workload has been exhausted.
+    pub fn next_spec(&mut self) -> Option<TaskSpec> {
+        let task_id = self.recorded.len();
+
+        let spec = match self.source {
+            Source::Seeded(ref mut rng) => {
+                let work_size = self.work_size_range.ind_sample(rng);
+
+                if rng.gen() {
+                    TaskSpec { task_id, kind: TaskKind::Simple, work_size, kernel_ops: Vec::new() }
+                } else {
+                    let signs: [bool; 3] = [rng.gen(), rng.gen(), rng.gen()];
+                    let vals = [
+                        RandRange::new(-1000., 1000.).ind_sample(rng),
+                        RandRange::new(-500., 500.).ind_sample(rng),
+                        RandRange::new(-2000., 2000.).ind_sample(rng),
+                    ];
+                    let kernel_ops = signs.iter().zip(vals.iter())
+                        .map(|(&add, &val)| KernelOp { add, val })
+                        .collect();
+
+                    TaskSpec { task_id, kind: TaskKind::Complex, work_size, kernel_ops }
+                }
+            },
+            Source::Replay(ref mut specs) => {
+                let mut spec = specs.pop_front()?;
+                spec.task_id = task_id;
+                spec
+            },
+        };
+
+        self.recorded.push(spec.clone());
+        Some(spec)
+    }
+
+    /// Marks the most recently returned specification as not having been
+    /// run (e.g. because the buffer pool was full) so that it is left out of
+    /// the recording.
+    pub fn discard_last(&mut self) {
+        self.recorded.pop();
+    }
+
+    /// The specifications handed out so far.
+    pub fn recorded(&self) -> &[TaskSpec] {
+        &self.recorded
+    }
+
+    /// Writes the recorded task list to `path` in a form `Workload::replay`
+    /// can read back.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> OclResult<()> {
+        let path = path.as_ref();
+        let write = || -> std::io::Result<()> {
+            let mut out = BufWriter::new(File::create(path)?);
+            writeln!(out, "# async_menagerie workload ({} tasks)", self.recorded.len())?;
+            writeln!(out, "seed {}", self.seed)?;
+            writeln!(out, "device {}", self.device_idx)?;
+            for spec in self.recorded.iter() {
+                writeln!(out, "{}", spec)?;
+            }
+            out.flush()
+        };
+
+        write().map_err(|err| format!("Unable to write workload file '{}': {}",
+            path.display(), err).into())
+    }
+}
+
+
+fn parse_kernel_op(word: &str) -> Option<KernelOp> {
+    let add = match word.chars().next()? {
+        '+' => true,
+        '-' => false,
+        _ => return None,
+    };
+    let bits = u32::from_str_radix(&word[1..], 16).ok()?;
+    Some(KernelOp { add, val: f32::from_bits(bits) })
+}
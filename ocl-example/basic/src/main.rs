@@ -1,7 +1,9 @@
 extern crate ocl;
 extern crate ocl_extras;
+extern crate verify;
 
 use ocl::{ProQue, Buffer, MemFlags};
+use verify::Verifier;
 
 // Number of results to print out:
 const RESULTS_TO_PRINT: usize = 20;
@@ -71,15 +73,22 @@ fn basics() -> ocl::Result<()> {
     // Read results from the device into result_buffer's local vector:
     result_buffer.read(&mut vec_result).enq()?;
 
-    // Check results and print the first 20:
-    for idx in 0..WORK_SIZE {
-        if idx < RESULTS_TO_PRINT {
-            println!("source[{idx}]: {:.03}, \t coeff: {}, \tresult[{idx}]: {}",
-            vec_source[idx], COEFF, vec_result[idx], idx = idx);
-        }
-        assert_eq!(vec_source[idx] * COEFF, vec_result[idx]);
+    // Print the first 20 results:
+    for idx in 0..RESULTS_TO_PRINT {
+        println!("source[{idx}]: {:.03}, \t coeff: {}, \tresult[{idx}]: {}",
+        vec_source[idx], COEFF, vec_result[idx], idx = idx);
     }
 
+    // Check every result against the host computation:
+    let report = Verifier::<f32>::new()
+        .label("multiply_by_scalar")
+        .compare_fn(&vec_result, |idx| vec_source[idx] * COEFF);
+
+    if !report.is_ok() {
+        return Err(report.to_string().into());
+    }
+
+    println!("{}", report);
     Ok(())
 }
 
@@ -1,28 +1,67 @@
 extern crate ocl;
 extern crate log;
+extern crate tuning;
+extern crate requirements;
+
+mod vector;
 
 use std::result::Result;
-use ocl::{Buffer, MemFlags, ProQue,Error};
+use ocl::{Buffer, Kernel, MemFlags, ProQue, Platform, Device, Error};
 use log::info;
+use tuning::{Autotuner, Tuning, TuningDb};
+use requirements::Requirements;
+
+pub use crate::vector::{VectorWidth, WidthSelection, gen_elementwise_src};
 
-static MULTIPLY_SRC: &str = include_str!("kernel/multiply.cl");
+static KERNEL_NAME: &str = "multiply_by_scalar";
+
+/// What the kernel needs from a device for `work_size` elements.
+pub fn requirements(work_size: usize) -> Requirements {
+    Requirements::new(KERNEL_NAME)
+        .allocation::<f32>("source_buffer", work_size)
+        .allocation::<f32>("result_buffer", work_size)
+}
 
 pub struct MultiplyKernel
 {
     proque: ProQue,
     source_buffer: Buffer<f32>,
-    pub result_buffer: Buffer<f32>
+    pub result_buffer: Buffer<f32>,
+    work_size: usize,
+    width: VectorWidth,
+    tuning_db: TuningDb,
 }
 
 impl MultiplyKernel
 {
     pub fn create(work_size: usize,vec_source:&Vec<f32>) -> Result<MultiplyKernel,Error> {
+        MultiplyKernel::create_with_width(work_size, vec_source, WidthSelection::Preferred)
+    }
+
+    /// Creates the kernel, choosing the scalar or a vector variant according
+    /// to `selection`.
+    pub fn create_with_width(work_size: usize, vec_source: &Vec<f32>, selection: WidthSelection)
+            -> Result<MultiplyKernel,Error>
+    {
+        let platform = Platform::default();
+        let device = Device::first(platform)?;
+        MultiplyKernel::create_on(platform, device, work_size, vec_source, selection)
+    }
+
+    /// Creates the kernel on `device` (e.g. a sub-device of a partitioned
+    /// CPU, to pin the work to a group of cores).
+    pub fn create_on(platform: Platform, device: Device, work_size: usize, vec_source: &Vec<f32>,
+            selection: WidthSelection) -> Result<MultiplyKernel,Error>
+    {
         // let devices = &GPU_NVIDIA_DEVICES;
         // if devices.is_empty() {
         //     return Err(GPUError::Simple("No working GPUs found!"));
         // }
         // let device = devices[0]; // Select the first device for FFT
-        let pq = ProQue::builder().src(MULTIPLY_SRC).dims(work_size).build()?;
+        let src = gen_elementwise_src(KERNEL_NAME, &["float const coeff"], "x * coeff");
+        requirements(work_size).check(device)?;
+        let pq = ProQue::builder().platform(platform).device(device).src(src).dims(work_size)
+            .build()?;
 
         
         let source_buffer = Buffer::builder()
@@ -37,11 +76,64 @@ impl MultiplyKernel
         info!("FFT: 1 working device(s) selected.");
         info!("FFT: Device 0: {}", pq.device().name()?);
 
-        Ok(MultiplyKernel {
+        // Tuned local work sizes, if any have been saved for this device:
+        let tuning_db = TuningDb::load_default_or_empty();
+
+        let width = selection.reported(pq.device())?;
+
+        let mut kernel = MultiplyKernel {
             proque: pq,
             source_buffer: source_buffer,
             result_buffer: result_buffer,
-        })
+            work_size: work_size,
+            width: width,
+            tuning_db: tuning_db,
+        };
+
+        if selection == WidthSelection::Measure {
+            let kernels = VectorWidth::ALL.iter()
+                .map(|&width| Ok((width, kernel.kernel_for(width, 1.0)?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            kernel.width = vector::measure(&kernels, work_size)?;
+        }
+
+        info!("Multiply: Using the {} variant.", kernel.width);
+        Ok(kernel)
+    }
+
+    /// The kernel variant in use.
+    pub fn width(&self) -> VectorWidth {
+        self.width
+    }
+
+    fn kernel_for(&self, width: VectorWidth, coeff: f32) -> Result<Kernel, Error> {
+        let name = width.kernel_name(KERNEL_NAME);
+        let gws = width.global_work_size(self.work_size);
+
+        self.proque.kernel_builder(name.as_str())
+        .global_work_size(gws)
+        .arg(coeff)
+        .arg(&self.source_buffer)
+        .arg(&self.result_buffer)
+        .arg(self.work_size as u32)
+        .local_work_size(self.tuning_db.lws(self.proque.device(), &name, gws))
+        .build()
+    }
+
+    fn kernel(&self, coeff: f32) -> Result<Kernel, Error> {
+        self.kernel_for(self.width, coeff)
+    }
+
+    /// Benchmarks local work sizes for the kernel variant in use and saves
+    /// the fastest to the tuning database, where later launches find it.
+    pub fn tune(&mut self, coeff: f32) -> Result<Tuning, Error> {
+        let kern = self.kernel(coeff)?;
+        let tuning = Autotuner::new(&kern)?.run(self.width.global_work_size(self.work_size))?;
+
+        self.tuning_db.insert(self.proque.device(), &self.width.kernel_name(KERNEL_NAME),
+            tuning.result.clone())?;
+        self.tuning_db.save()?;
+        Ok(tuning)
     }
 
     pub fn multiply(&mut self,coeff: f32){
@@ -51,11 +143,7 @@ impl MultiplyKernel
         // .arg_named("result", None::<&Buffer<f32>>)
         // .build().unwrap();
 
-        let kern = self.proque.kernel_builder("multiply_by_scalar")
-        .arg(coeff)
-        .arg(&self.source_buffer)
-        .arg(&self.result_buffer)
-        .build().unwrap();
+        let kern = self.kernel(coeff).unwrap();
         
         // Set our named argument. The Option<_> wrapper is, well... optional:
         // kern.set_arg("result", &self.result_buffer);
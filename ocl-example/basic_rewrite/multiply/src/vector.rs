@@ -0,0 +1,194 @@
+//! Scalar and vectorized variants of element-wise kernels.
+//!
+//! `gen_elementwise_src` writes one kernel per `VectorWidth`. Vector
+//! variants load and store `floatN` values with `vloadN`/`vstoreN`, one
+//! vector per work item, and the last `len % N` work items each handle one
+//! leftover scalar element so any `len` is covered.
+//!
+
+use std::fmt;
+use ocl::{Device, Kernel};
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::error::{Error, Result as OclResult};
+use tuning::Autotuner;
+
+/// Launches timed per variant when measuring.
+const MEASURE_ITERS: usize = 10;
+
+
+/// The number of `float`s each work item processes at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorWidth {
+    Scalar,
+    Two,
+    Four,
+    Eight,
+    Sixteen,
+}
+
+impl VectorWidth {
+    /// Every width, narrowest first.
+    pub const ALL: [VectorWidth; 5] = [VectorWidth::Scalar, VectorWidth::Two,
+        VectorWidth::Four, VectorWidth::Eight, VectorWidth::Sixteen];
+
+    /// The number of lanes.
+    pub fn lanes(self) -> usize {
+        match self {
+            VectorWidth::Scalar => 1,
+            VectorWidth::Two => 2,
+            VectorWidth::Four => 4,
+            VectorWidth::Eight => 8,
+            VectorWidth::Sixteen => 16,
+        }
+    }
+
+    /// The width with exactly `lanes` lanes (1, 2, 4, 8 or 16).
+    pub fn from_lanes(lanes: u32) -> OclResult<VectorWidth> {
+        VectorWidth::ALL.iter().cloned()
+            .find(|width| width.lanes() == lanes as usize)
+            .ok_or_else(|| format!("Unsupported vector width {} (expected 1, 2, 4, 8 or 16).",
+                lanes).into())
+    }
+
+    /// The OpenCL C type name (`float`, `float2`, ...).
+    pub fn type_name(self) -> String {
+        match self {
+            VectorWidth::Scalar => "float".to_owned(),
+            _ => format!("float{}", self.lanes()),
+        }
+    }
+
+    /// The name of the `base` kernel's variant for this width. The scalar
+    /// variant keeps the base name.
+    pub fn kernel_name(self, base: &str) -> String {
+        match self {
+            VectorWidth::Scalar => base.to_owned(),
+            _ => format!("{}_v{}", base, self.lanes()),
+        }
+    }
+
+    /// The global work size covering `len` elements: one work item per
+    /// vector plus one per leftover element.
+    pub fn global_work_size(self, len: usize) -> usize {
+        len / self.lanes() + len % self.lanes()
+    }
+}
+
+impl fmt::Display for VectorWidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.type_name())
+    }
+}
+
+
+/// How to pick a kernel variant for a device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WidthSelection {
+    /// `PreferredVectorWidthFloat`.
+    Preferred,
+    /// `NativeVectorWidthFloat`, falling back to the preferred width when
+    /// the device reports 0.
+    Native,
+    /// Time every variant and take the fastest.
+    Measure,
+    /// Always use the given width.
+    Fixed(VectorWidth),
+}
+
+impl WidthSelection {
+    /// Returns the width `device` reports for this selection (`Measure`
+    /// reports the preferred width, to be refined with `measure`).
+    pub fn reported(self, device: Device) -> OclResult<VectorWidth> {
+        let preferred = || -> OclResult<VectorWidth> {
+            match device.info(DeviceInfo::PreferredVectorWidthFloat)? {
+                DeviceInfoResult::PreferredVectorWidthFloat(lanes) => {
+                    VectorWidth::from_lanes(lanes.max(1))
+                },
+                _ => Ok(VectorWidth::Scalar),
+            }
+        };
+
+        match self {
+            WidthSelection::Preferred | WidthSelection::Measure => preferred(),
+            WidthSelection::Native => match device.info(DeviceInfo::NativeVectorWidthFloat)? {
+                DeviceInfoResult::NativeVectorWidthFloat(lanes) if lanes > 0 => {
+                    VectorWidth::from_lanes(lanes)
+                },
+                _ => preferred(),
+            },
+            WidthSelection::Fixed(width) => Ok(width),
+        }
+    }
+}
+
+
+/// Returns the source of every variant of an element-wise kernel named
+/// `base`.
+///
+/// `params` are the kernel's scalar parameters (e.g. `"float const coeff"`)
+/// and come first, followed by the `src` and `res` buffers and the element
+/// count `len`. `expr` computes a result from `x`, the input element (or
+/// vector), and must be valid for both `float` and `floatN`.
+pub fn gen_elementwise_src(base: &str, params: &[&str], expr: &str) -> String {
+    let params: String = params.iter().map(|param| format!("__private {},\n        ", param))
+        .collect();
+
+    VectorWidth::ALL.iter().map(|&width| {
+        let name = width.kernel_name(base);
+
+        if width == VectorWidth::Scalar {
+            return format!(r#"__kernel void {name}(
+        {params}__global float const* const src,
+        __global float* const res,
+        __private uint const len)
+{{
+    uint const idx = get_global_id(0);
+    if (idx < len) {{
+        float const x = src[idx];
+        res[idx] = {expr};
+    }}
+}}
+"#,
+                name=name, params=params, expr=expr);
+        }
+
+        format!(r#"__kernel void {name}(
+        {params}__global float const* const src,
+        __global float* const res,
+        __private uint const len)
+{{
+    uint const idx = get_global_id(0);
+    uint const vec_count = len / {lanes};
+    if (idx < vec_count) {{
+        {ty} const x = vload{lanes}(idx, src);
+        vstore{lanes}({expr}, idx, res);
+    }} else {{
+        uint const tail_idx = vec_count * {lanes} + (idx - vec_count);
+        if (tail_idx < len) {{
+            float const x = src[tail_idx];
+            res[tail_idx] = {expr};
+        }}
+    }}
+}}
+"#,
+            name=name, params=params, ty=width.type_name(), lanes=width.lanes(), expr=expr)
+    }).collect::<Vec<_>>().join("\n")
+}
+
+
+/// Times each of `kernels` (one per width, each ready to launch over `len`
+/// elements) and returns the fastest width.
+pub fn measure(kernels: &[(VectorWidth, Kernel)], len: usize) -> OclResult<VectorWidth> {
+    let mut best: Option<(VectorWidth, u64)> = None;
+
+    for &(width, ref kernel) in kernels.iter() {
+        let avg_ns = Autotuner::new(kernel)?.iters(MEASURE_ITERS)
+            .time(width.global_work_size(len), None)?;
+
+        if best.map(|(_, best_ns)| avg_ns < best_ns).unwrap_or(true) {
+            best = Some((width, avg_ns));
+        }
+    }
+
+    best.map(|(width, _)| width).ok_or_else(|| Error::from("No kernel variants to measure."))
+}
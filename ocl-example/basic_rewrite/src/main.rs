@@ -1,5 +1,11 @@
+extern crate ocl;
 extern crate ocl_extras;
-use multiply::{ MultiplyKernel};
+extern crate verify;
+extern crate subdevice;
+use ocl::{Platform, Device};
+use multiply::{ MultiplyKernel, VectorWidth, WidthSelection};
+use subdevice::{Partition, SubDevices};
+use verify::Verifier;
 
 
 const RESULTS_TO_PRINT: usize = 20;
@@ -10,17 +16,88 @@ const COEFF: f32 = 5432.1;
 
 fn main() {
     let vec_source = ocl_extras::scrambled_vec((0.0, 20.0), WORK_SIZE);
-    let mut kernel = MultiplyKernel::create(WORK_SIZE,&vec_source).unwrap();
+    let args: Vec<String> = std::env::args().collect();
+
+    // Pass `--width <preferred|native|measure|LANES>` to choose the kernel
+    // variant (default: the device's preferred float vector width):
+    let selection = match args.iter().position(|arg| arg == "--width").map(|idx| args.get(idx + 1)) {
+        None => WidthSelection::Preferred,
+        Some(None) => panic!("Missing value for '--width'."),
+        Some(Some(width)) => match width.as_str() {
+            "preferred" => WidthSelection::Preferred,
+            "native" => WidthSelection::Native,
+            "measure" => WidthSelection::Measure,
+            lanes => WidthSelection::Fixed(VectorWidth::from_lanes(lanes.parse()
+                .expect("Invalid value for '--width'.")).unwrap_or_else(|err| panic!("{}", err))),
+        },
+    };
+
+    // Pass `--partition <equally:N|counts:A,B,...|affinity:DOMAIN>` to split
+    // the first device into sub-devices and run the multiply on each of them:
+    let partition = match args.iter().position(|arg| arg == "--partition").map(|idx| args.get(idx + 1)) {
+        None => None,
+        Some(None) => panic!("Missing value for '--partition'."),
+        Some(Some(spec)) => Some(spec.parse::<Partition>().unwrap_or_else(|err| panic!("{}", err))),
+    };
+
+    // Keeps the sub-devices alive while their kernels run:
+    let mut sub_devices = None;
+    let mut kernels = match partition {
+        None => vec![MultiplyKernel::create_with_width(WORK_SIZE,&vec_source, selection).unwrap()],
+        Some(partition) => {
+            let platform = Platform::default();
+            let subs = SubDevices::create(Device::first(platform).unwrap(), partition).unwrap();
+            println!("Partitioned '{}' ({}) into {} sub-devices.", subs.parent().name().unwrap(),
+                subs.partition(), subs.len());
+            let kernels = subs.devices().iter()
+                .map(|device| MultiplyKernel::create_on(platform, **device, WORK_SIZE, &vec_source,
+                    selection).unwrap())
+                .collect();
+            sub_devices = Some(subs);
+            kernels
+        },
+    };
+
+    for (idx, kernel) in kernels.iter_mut().enumerate() {
+        if sub_devices.is_some() {
+            println!("Sub-device [{}]:", idx);
+        }
+        run(kernel, &vec_source, &args);
+    }
+}
+
+fn run(kernel: &mut MultiplyKernel, vec_source: &[f32], args: &[String]) {
+    println!("Kernel variant: {}", kernel.width());
+
+    // Pass `--tune` to benchmark local work sizes first and save the best
+    // one for this device (later runs pick it up automatically):
+    if args.iter().any(|arg| arg == "--tune") {
+        let tuning = kernel.tune(COEFF).unwrap();
+        for &(lws, avg_ns) in tuning.timings.iter() {
+            match lws {
+                Some(lws) => println!("local work size {:>5}: {:>10} ns", lws, avg_ns),
+                None => println!("local work size  auto: {:>10} ns", avg_ns),
+            }
+        }
+        match tuning.result.local_work_size {
+            Some(lws) => println!("Best local work size: {} ({:.2}x driver choice)",
+                lws, tuning.result.speedup()),
+            None => println!("Best local work size: auto (the driver's choice)"),
+        }
+    }
+
     kernel.multiply(COEFF);
 
     let mut vec_result = vec![0.0f32; WORK_SIZE];
     kernel.result_buffer.read(&mut vec_result).enq();
 
-    for idx in 0..WORK_SIZE {
-        if idx < RESULTS_TO_PRINT {
-            println!("source[{idx}]: {:.03}, \t coeff: {}, \tresult[{idx}]: {}",
-            vec_source[idx], COEFF, vec_result[idx], idx = idx);
-        }
-        assert_eq!(vec_source[idx] * COEFF, vec_result[idx]);
+    for idx in 0..RESULTS_TO_PRINT {
+        println!("source[{idx}]: {:.03}, \t coeff: {}, \tresult[{idx}]: {}",
+        vec_source[idx], COEFF, vec_result[idx], idx = idx);
     }
+
+    let report = Verifier::<f32>::new()
+        .label("multiply")
+        .compare_fn(&vec_result, |idx| vec_source[idx] * COEFF);
+    assert!(report.is_ok(), "{}", report);
 }
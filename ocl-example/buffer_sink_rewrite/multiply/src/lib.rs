@@ -1,19 +1,24 @@
 extern crate ocl;
 extern crate log;
+extern crate tuning;
 
 use std::result::Result;
-use ocl::{Buffer, MemFlags, ProQue,Error};
+use ocl::{Buffer, Kernel, MemFlags, ProQue,Error};
 use ocl::r#async::{BufferSink, WriteGuard};
 use log::info;
+use tuning::{Autotuner, Tuning, TuningDb};
 
 static MULTIPLY_SRC: &str = include_str!("kernel/multiply.cl");
+static KERNEL_NAME: &str = "multiply_by_scalar";
 
 pub struct MultiplyKernel
 {
     proque: ProQue,
     source_buffer: Buffer<i32>,
     pub result_buffer: Buffer<i32>,
-    pub buffer_sink: BufferSink<i32>
+    pub buffer_sink: BufferSink<i32>,
+    work_size: usize,
+    tuning_db: TuningDb,
 }
 
 impl MultiplyKernel
@@ -43,21 +48,42 @@ impl MultiplyKernel
             work_size)?
         };
 
+        // Tuned local work sizes, if any have been saved for this device:
+        let tuning_db = TuningDb::load_default_or_empty();
+
         Ok(MultiplyKernel {
             proque: pq,
             source_buffer: source_buffer,
             result_buffer: result_buffer,
-            buffer_sink: buffer_sink
+            buffer_sink: buffer_sink,
+            work_size: work_size,
+            tuning_db: tuning_db,
         })
     }
 
-    pub fn multiply(&mut self,coeff: i32){
-
-        let kern = self.proque.kernel_builder("multiply_by_scalar")
+    fn kernel(&self, coeff: i32) -> Result<Kernel, Error> {
+        self.proque.kernel_builder(KERNEL_NAME)
         .arg(coeff)
         .arg(&self.source_buffer)
         .arg(&self.result_buffer)
-        .build().unwrap();
+        .local_work_size(self.tuning_db.lws(self.proque.device(), KERNEL_NAME, self.work_size))
+        .build()
+    }
+
+    /// Benchmarks local work sizes for this kernel and work size and saves
+    /// the fastest to the tuning database, where later launches find it.
+    pub fn tune(&mut self, coeff: i32) -> Result<Tuning, Error> {
+        let kern = self.kernel(coeff)?;
+        let tuning = Autotuner::new(&kern)?.run(self.work_size)?;
+
+        self.tuning_db.insert(self.proque.device(), KERNEL_NAME, tuning.result.clone())?;
+        self.tuning_db.save()?;
+        Ok(tuning)
+    }
+
+    pub fn multiply(&mut self,coeff: i32){
+
+        let kern = self.kernel(coeff).unwrap();
         
         unsafe { kern.enq();}
         // Read results from the device into result_buffer's local vector:
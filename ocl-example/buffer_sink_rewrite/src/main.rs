@@ -18,6 +18,24 @@ const RESULTS_TO_PRINT: usize = 20;
 
 fn main() {
     let mut kernel = MultiplyKernel::create(WORK_SIZE).unwrap();
+
+    // Pass `--tune` to benchmark local work sizes first and save the best
+    // one for this device (later runs pick it up automatically):
+    if std::env::args().any(|arg| arg == "--tune") {
+        let tuning = kernel.tune(COEFF).unwrap();
+        for &(lws, avg_ns) in tuning.timings.iter() {
+            match lws {
+                Some(lws) => println!("local work size {:>5}: {:>10} ns", lws, avg_ns),
+                None => println!("local work size  auto: {:>10} ns", avg_ns),
+            }
+        }
+        match tuning.result.local_work_size {
+            Some(lws) => println!("Best local work size: {} ({:.2}x driver choice)",
+                lws, tuning.result.speedup()),
+            None => println!("Best local work size: auto (the driver's choice)"),
+        }
+    }
+
     let buffer_sink = kernel.buffer_sink;
     kernel.multiply(COEFF);
 
@@ -0,0 +1,554 @@
+//! Record-and-replay of OpenCL command streams.
+//!
+//! A `Capture` is handed to the code that enqueues commands and is told
+//! about every program, buffer and kernel it creates and every command it
+//! enqueues (writes, fills, copies, kernel launches, maps and reads) along
+//! with the events each command waits on. When saved, the capture is written
+//! as JSON lines, one `Entry` per line, which the `replay` binary (see
+//! `Replay`) can re-execute against any device.
+//!
+//! Event dependencies are resolved by remembering which command produced
+//! each event (see `Capture::bind_event`), so callers only ever deal with the
+//! events they already have.
+//!
+//! Every recorded object gets the next id of its kind when it is first seen.
+//! The recorder holds a reference to each object (and event) it has seen so
+//! that its handle can't be reused by a new object while recording.
+//!
+//! Write payloads are stored as a single element when every element is the
+//! same and otherwise in full, since a replay needs them to reproduce the
+//! run. Read payloads are only compared against, so those larger than the
+//! data limit are stored as a hash.
+//!
+
+extern crate ocl;
+extern crate serde;
+extern crate serde_json;
+
+mod replay;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Write, BufWriter};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::{Arc, Mutex};
+use serde::{Serialize, Deserialize};
+use ocl::{core, Buffer, Event, Kernel, Program, OclPrm, SpatialDims};
+use ocl::error::Result as OclResult;
+
+pub use crate::replay::{Replay, ReplayReport, Mismatch};
+
+/// Read payloads larger than this (in bytes) are stored as a hash.
+pub const DEFAULT_DATA_LIMIT: usize = 1 << 16;
+
+/// The index of a command within a capture.
+pub type CmdId = usize;
+
+
+/// Data written to or read from a buffer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Payload {
+    /// `len` copies of a single element.
+    Splat { len: usize, elem: String },
+    /// The complete data.
+    Data { hex: String },
+    /// Only the length and an FNV-1a hash of the data.
+    Hash { len_bytes: usize, fnv1a: u64 },
+}
+
+impl Payload {
+    /// Returns the most compact faithful representation of `bytes` (made up
+    /// of `elem_size` sized elements), falling back to a hash for anything
+    /// larger than `data_limit`.
+    pub fn new(bytes: &[u8], elem_size: usize, data_limit: usize) -> Payload {
+        if elem_size > 0 && !bytes.is_empty() && bytes.len() % elem_size == 0 {
+            let first = &bytes[..elem_size];
+            if bytes.chunks(elem_size).all(|elem| elem == first) {
+                return Payload::Splat { len: bytes.len() / elem_size, elem: to_hex(first) };
+            }
+        }
+
+        if bytes.len() <= data_limit {
+            Payload::Data { hex: to_hex(bytes) }
+        } else {
+            Payload::Hash { len_bytes: bytes.len(), fnv1a: fnv1a(bytes) }
+        }
+    }
+
+    /// Returns the bytes this payload represents or `None` if only a hash
+    /// was kept.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        match *self {
+            Payload::Splat { len, ref elem } => {
+                let elem = from_hex(elem)?;
+                let mut bytes = Vec::with_capacity(len * elem.len());
+                for _ in 0..len { bytes.extend_from_slice(&elem); }
+                Some(bytes)
+            },
+            Payload::Data { ref hex } => from_hex(hex),
+            Payload::Hash { .. } => None,
+        }
+    }
+
+    /// Returns `true` if `bytes` is the data this payload represents.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        match *self {
+            Payload::Hash { len_bytes, fnv1a: hash } => len_bytes == bytes.len() && hash == fnv1a(bytes),
+            _ => self.to_bytes().map(|own| own == bytes).unwrap_or(false),
+        }
+    }
+}
+
+
+/// A kernel argument.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Arg {
+    /// A captured buffer (by buffer id).
+    Buffer { id: usize },
+    /// A scalar or vector value (raw bytes).
+    Scalar { hex: String },
+}
+
+impl Arg {
+    /// Returns a scalar (or vector) argument.
+    pub fn scalar<T: OclPrm>(val: &T) -> Arg {
+        Arg::Scalar { hex: to_hex(as_bytes(slice::from_ref(val))) }
+    }
+}
+
+
+/// A single captured object or command.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Entry {
+    Program { id: usize, src: String },
+    Buffer { id: usize, len: usize, elem_size: usize, elem_type: String, flags: u64 },
+    /// A kernel with its default work sizes and offset (the local work size
+    /// and offset are empty if unspecified).
+    Kernel { id: usize, program: usize, name: String, global_work_size: Vec<usize>,
+        #[serde(default)] local_work_size: Vec<usize>,
+        #[serde(default)] global_work_offset: Vec<usize>,
+        args: Vec<Arg> },
+    Write { cmd: CmdId, buffer: usize, payload: Option<Payload>, waits: Vec<CmdId> },
+    Fill { cmd: CmdId, buffer: usize, pattern: String, waits: Vec<CmdId> },
+    Copy { cmd: CmdId, source: usize, target: usize, waits: Vec<CmdId> },
+    Enqueue { cmd: CmdId, kernel: usize, waits: Vec<CmdId> },
+    /// A map followed (some time later) by an unmap. For write maps the
+    /// payload is the data written while mapped, for read maps the data
+    /// observed.
+    Map { cmd: CmdId, buffer: usize, write: bool, payload: Option<Payload>, waits: Vec<CmdId> },
+    Read { cmd: CmdId, buffer: usize, payload: Option<Payload>, waits: Vec<CmdId> },
+}
+
+impl Entry {
+    /// Returns the command id if this entry is a command.
+    pub fn cmd(&self) -> Option<CmdId> {
+        match *self {
+            Entry::Program { .. } | Entry::Buffer { .. } | Entry::Kernel { .. } => None,
+            Entry::Write { cmd, .. } | Entry::Fill { cmd, .. } | Entry::Copy { cmd, .. } |
+                Entry::Enqueue { cmd, .. } | Entry::Map { cmd, .. } |
+                Entry::Read { cmd, .. } => Some(cmd),
+        }
+    }
+
+    fn payload_mut(&mut self) -> Option<&mut Option<Payload>> {
+        match *self {
+            Entry::Write { ref mut payload, .. } | Entry::Map { ref mut payload, .. } |
+                Entry::Read { ref mut payload, .. } => Some(payload),
+            _ => None,
+        }
+    }
+}
+
+
+/// Recorded objects of one kind, by raw OpenCL handle.
+struct Registry<H> {
+    // Raw handle -> (retained handle, capture id):
+    ids: HashMap<usize, (H, usize)>,
+    next_id: usize,
+}
+
+impl<H> Registry<H> {
+    fn new() -> Registry<H> {
+        Registry { ids: HashMap::new(), next_id: 0 }
+    }
+
+    fn get(&self, key: usize) -> Option<usize> {
+        self.ids.get(&key).map(|&(_, id)| id)
+    }
+
+    /// Registers `handle` (retained for as long as the recording lives) under
+    /// the next id.
+    fn insert(&mut self, key: usize, handle: H) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ids.insert(key, (handle, id));
+        id
+    }
+}
+
+
+/// The state behind an enabled `Capture`.
+struct Recorder {
+    path: PathBuf,
+    data_limit: usize,
+    entries: Vec<Entry>,
+    // Command id -> index into `entries`:
+    cmd_entries: Vec<usize>,
+    programs: Registry<core::Program>,
+    buffers: Registry<core::Mem>,
+    kernels: Registry<core::Kernel>,
+    // Raw event handle -> (retained event, command which produced it):
+    events: HashMap<usize, (Event, CmdId)>,
+    saved_len: Option<usize>,
+}
+
+impl Recorder {
+    fn push_cmd(&mut self, entry: Entry) -> CmdId {
+        debug_assert_eq!(entry.cmd(), Some(self.cmd_entries.len()));
+        self.cmd_entries.push(self.entries.len());
+        self.entries.push(entry);
+        self.cmd_entries.len() - 1
+    }
+
+    fn next_cmd(&self) -> CmdId {
+        self.cmd_entries.len()
+    }
+
+    fn waits<'e, W>(&self, waits: W) -> Vec<CmdId> where W: IntoIterator<Item = &'e Event> {
+        waits.into_iter()
+            .filter_map(|ev| self.events.get(&(ev.as_ptr() as usize)).map(|&(_, cmd)| cmd))
+            .collect()
+    }
+
+    fn buffer<T: OclPrm>(&mut self, buffer: &Buffer<T>) -> usize {
+        let key = buffer.as_ptr() as usize;
+        if let Some(id) = self.buffers.get(key) { return id; }
+
+        let id = self.buffers.insert(key, buffer.as_core().clone());
+        self.entries.push(Entry::Buffer {
+            id: id,
+            len: buffer.len(),
+            elem_size: mem::size_of::<T>(),
+            elem_type: std::any::type_name::<T>().to_owned(),
+            flags: buffer.flags().map(|flags| flags.bits()).unwrap_or(0),
+        });
+        id
+    }
+
+    fn save(&mut self) -> OclResult<()> {
+        let write = || -> std::io::Result<()> {
+            let mut out = BufWriter::new(File::create(&self.path)?);
+            for entry in self.entries.iter() {
+                serde_json::to_writer(&mut out, entry)?;
+                out.write_all(b"\n")?;
+            }
+            out.flush()
+        };
+
+        write().map_err(|err| format!("Unable to write capture file '{}': {}",
+            self.path.display(), err))?;
+        self.saved_len = Some(self.entries.len());
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    /// Saves anything not yet saved so that a capture survives a failed run.
+    fn drop(&mut self) {
+        if self.saved_len != Some(self.entries.len()) {
+            if let Err(err) = self.save() {
+                eprintln!("{}", err);
+            }
+        }
+    }
+}
+
+
+/// A handle to a command stream recording. Cloning a capture returns a
+/// handle to the same recording.
+///
+/// A disabled capture (`Capture::disabled`) does nothing and returns
+/// meaningless command ids, so code can be instrumented unconditionally.
+#[derive(Clone)]
+pub struct Capture {
+    recorder: Option<Arc<Mutex<Recorder>>>,
+}
+
+impl Capture {
+    /// Returns a capture which records nothing.
+    pub fn disabled() -> Capture {
+        Capture { recorder: None }
+    }
+
+    /// Returns a capture which will be written to `path` when `save` is
+    /// called or the last handle is dropped.
+    pub fn to_file<P: AsRef<Path>>(path: P) -> Capture {
+        Capture::with_data_limit(path, DEFAULT_DATA_LIMIT)
+    }
+
+    /// Returns a capture which stores read payloads larger than `data_limit`
+    /// bytes as a hash. Write payloads are always stored in full.
+    pub fn with_data_limit<P: AsRef<Path>>(path: P, data_limit: usize) -> Capture {
+        Capture {
+            recorder: Some(Arc::new(Mutex::new(Recorder {
+                path: path.as_ref().to_owned(),
+                data_limit: data_limit,
+                entries: Vec::with_capacity(1024),
+                cmd_entries: Vec::with_capacity(1024),
+                programs: Registry::new(),
+                buffers: Registry::new(),
+                kernels: Registry::new(),
+                events: HashMap::new(),
+                saved_len: None,
+            }))),
+        }
+    }
+
+    /// Returns `true` if this capture is recording.
+    pub fn is_enabled(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    fn with<R, F>(&self, default: R, f: F) -> R where F: FnOnce(&mut Recorder) -> R {
+        match self.recorder {
+            Some(ref recorder) => f(&mut *recorder.lock().unwrap()),
+            None => default,
+        }
+    }
+
+    /// Records a program built from `src`.
+    pub fn program(&self, program: &Program, src: &str) {
+        self.with((), |rec| {
+            let key = program.as_ptr() as usize;
+            if rec.programs.get(key).is_some() { return; }
+            let id = rec.programs.insert(key, program.as_core().clone());
+            rec.entries.push(Entry::Program { id: id, src: src.to_owned() });
+        })
+    }
+
+    /// Records a buffer (if not already recorded).
+    pub fn buffer<T: OclPrm>(&self, buffer: &Buffer<T>) {
+        self.with((), |rec| { rec.buffer(buffer); })
+    }
+
+    /// Returns a kernel argument referring to `buffer`, recording the buffer
+    /// if necessary.
+    pub fn buffer_arg<T: OclPrm>(&self, buffer: &Buffer<T>) -> Arg {
+        self.with(Arg::Buffer { id: 0 }, |rec| Arg::Buffer { id: rec.buffer(buffer) })
+    }
+
+    /// Records a kernel named `name` created from `program` (which must
+    /// already have been recorded) with the arguments `args`. The kernel's
+    /// default work sizes and offset are the ones replayed.
+    pub fn kernel(&self, kernel: &Kernel, program: &Program, name: &str, args: Vec<Arg>)
+            -> OclResult<()>
+    {
+        self.with(Ok(()), |rec| {
+            let program_id = rec.programs.get(program.as_ptr() as usize).ok_or_else(||
+                format!("Capture::kernel: Program for kernel '{}' not captured.", name))?;
+            let id = rec.kernels.insert(kernel.as_ptr() as usize, kernel.as_core().clone());
+            rec.entries.push(Entry::Kernel {
+                id: id,
+                program: program_id,
+                name: name.to_owned(),
+                global_work_size: work_size_vec(&kernel.default_global_work_size()),
+                local_work_size: work_size_vec(&kernel.default_local_work_size()),
+                global_work_offset: work_size_vec(&kernel.default_global_work_offset()),
+                args: args,
+            });
+            Ok(())
+        })
+    }
+
+    /// Records a write of `data` to `buffer`.
+    pub fn write<'e, T, W>(&self, buffer: &Buffer<T>, data: &[T], waits: W) -> CmdId
+            where T: OclPrm, W: IntoIterator<Item = &'e Event>
+    {
+        self.with(0, |rec| {
+            let cmd = rec.next_cmd();
+            // Stored in full, however large: a replay can't reproduce the run without it.
+            let payload = Payload::new(as_bytes(data), mem::size_of::<T>(), usize::max_value());
+            let entry = Entry::Write {
+                cmd: cmd,
+                buffer: rec.buffer(buffer),
+                payload: Some(payload),
+                waits: rec.waits(waits),
+            };
+            rec.push_cmd(entry)
+        })
+    }
+
+    /// Records a fill of `buffer` with `pattern`.
+    pub fn fill<'e, T, W>(&self, buffer: &Buffer<T>, pattern: T, waits: W) -> CmdId
+            where T: OclPrm, W: IntoIterator<Item = &'e Event>
+    {
+        self.with(0, |rec| {
+            let cmd = rec.next_cmd();
+            let entry = Entry::Fill {
+                cmd: cmd,
+                buffer: rec.buffer(buffer),
+                pattern: to_hex(as_bytes(slice::from_ref(&pattern))),
+                waits: rec.waits(waits),
+            };
+            rec.push_cmd(entry)
+        })
+    }
+
+    /// Records a copy from `source` to `target`.
+    pub fn copy<'e, T, W>(&self, source: &Buffer<T>, target: &Buffer<T>, waits: W) -> CmdId
+            where T: OclPrm, W: IntoIterator<Item = &'e Event>
+    {
+        self.with(0, |rec| {
+            let cmd = rec.next_cmd();
+            let entry = Entry::Copy {
+                cmd: cmd,
+                source: rec.buffer(source),
+                target: rec.buffer(target),
+                waits: rec.waits(waits),
+            };
+            rec.push_cmd(entry)
+        })
+    }
+
+    /// Records a launch of `kernel` (which must already have been recorded).
+    pub fn enqueue_kernel<'e, W>(&self, kernel: &Kernel, waits: W) -> OclResult<CmdId>
+            where W: IntoIterator<Item = &'e Event>
+    {
+        self.with(Ok(0), |rec| {
+            let kernel_id = rec.kernels.get(kernel.as_ptr() as usize)
+                .ok_or("Capture::enqueue_kernel: Kernel not captured.")?;
+            let cmd = rec.next_cmd();
+            let entry = Entry::Enqueue { cmd: cmd, kernel: kernel_id, waits: rec.waits(waits) };
+            Ok(rec.push_cmd(entry))
+        })
+    }
+
+    /// Records a map of `buffer`. Use `set_payload` to record the data
+    /// written or read while mapped.
+    pub fn map<'e, T, W>(&self, buffer: &Buffer<T>, write: bool, waits: W) -> CmdId
+            where T: OclPrm, W: IntoIterator<Item = &'e Event>
+    {
+        self.with(0, |rec| {
+            let cmd = rec.next_cmd();
+            let entry = Entry::Map {
+                cmd: cmd,
+                buffer: rec.buffer(buffer),
+                write: write,
+                payload: None,
+                waits: rec.waits(waits),
+            };
+            rec.push_cmd(entry)
+        })
+    }
+
+    /// Records a read of `buffer`. Use `set_payload` to record the data
+    /// observed once the read completes.
+    pub fn read<'e, T, W>(&self, buffer: &Buffer<T>, waits: W) -> CmdId
+            where T: OclPrm, W: IntoIterator<Item = &'e Event>
+    {
+        self.with(0, |rec| {
+            let cmd = rec.next_cmd();
+            let entry = Entry::Read {
+                cmd: cmd,
+                buffer: rec.buffer(buffer),
+                payload: None,
+                waits: rec.waits(waits),
+            };
+            rec.push_cmd(entry)
+        })
+    }
+
+    /// Records the data written by a write map (in full) or observed by a
+    /// read or read map.
+    pub fn set_payload<T: OclPrm>(&self, cmd: CmdId, data: &[T]) {
+        self.with((), |rec| {
+            let entry_idx = rec.cmd_entries[cmd];
+            let data_limit = match rec.entries[entry_idx] {
+                Entry::Map { write: true, .. } => usize::max_value(),
+                _ => rec.data_limit,
+            };
+            let payload = Payload::new(as_bytes(data), mem::size_of::<T>(), data_limit);
+            if let Some(slot) = rec.entries[entry_idx].payload_mut() {
+                *slot = Some(payload);
+            }
+        })
+    }
+
+    /// Associates `event` with `cmd` so that later commands waiting on it
+    /// are recorded as depending on `cmd`.
+    pub fn bind_event(&self, cmd: CmdId, event: &Event) {
+        self.with((), |rec| {
+            if !event.is_empty() {
+                rec.events.insert(event.as_ptr() as usize, (event.clone(), cmd));
+            }
+        })
+    }
+
+    /// Writes the capture to its file.
+    pub fn save(&self) -> OclResult<()> {
+        self.with(Ok(()), |rec| rec.save())
+    }
+
+    /// The number of commands captured so far.
+    pub fn cmd_count(&self) -> usize {
+        self.with(0, |rec| rec.cmd_entries.len())
+    }
+}
+
+
+/// Reads a capture file.
+pub fn load<P: AsRef<Path>>(path: P) -> OclResult<Vec<Entry>> {
+    use std::io::{BufRead, BufReader};
+
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| format!("Unable to open capture file '{}': {}",
+        path.display(), err))?;
+
+    let mut entries = Vec::with_capacity(1024);
+    for (line_idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() { continue; }
+        let entry = serde_json::from_str(&line).map_err(|err| format!("{}:{}: {}",
+            path.display(), line_idx + 1, err))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+
+fn work_size_vec(dims: &SpatialDims) -> Vec<usize> {
+    match *dims {
+        SpatialDims::Unspecified => Vec::new(),
+        SpatialDims::One(x) => vec![x],
+        SpatialDims::Two(x, y) => vec![x, y],
+        SpatialDims::Three(x, y, z) => vec![x, y, z],
+    }
+}
+
+fn as_bytes<T: OclPrm>(data: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * mem::size_of::<T>()) }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes { hex.push_str(&format!("{:02x}", byte)); }
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 { return None; }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
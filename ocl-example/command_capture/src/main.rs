@@ -0,0 +1,87 @@
+//! Replays a command stream captured with `command_capture::Capture`.
+//!
+//! Exits with status 1 if any replayed read differs from the captured
+//! results and 2 if the capture could not be replayed at all.
+//!
+
+extern crate ocl;
+extern crate command_capture;
+
+use std::env;
+use std::process;
+use ocl::{Platform, Device, Context};
+use ocl::error::Result as OclResult;
+use command_capture::{Replay, ReplayReport};
+
+static USAGE: &'static str = "\
+Usage: replay <CAPTURE_FILE> [OPTIONS]
+
+Options:
+    --platform <IDX>   Index of the platform to replay on (default: 0)
+    --device <IDX>     Index of the device within the platform (default: 0)";
+
+
+fn parse_idx(args: &mut dyn Iterator<Item = String>, name: &str) -> OclResult<usize> {
+    args.next().and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| format!("Invalid or missing value for '{}'.", name).into())
+}
+
+fn replay() -> OclResult<ReplayReport> {
+    let mut path = None;
+    let mut platform_idx = 0;
+    let mut device_idx = 0;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform_idx = parse_idx(&mut args, "--platform")?,
+            "--device" => device_idx = parse_idx(&mut args, "--device")?,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let path = path.ok_or(USAGE)?;
+    let replay = Replay::load(&path)?;
+
+    let platform = *Platform::list().get(platform_idx)
+        .ok_or_else(|| format!("Platform index {} is out of range.", platform_idx))?;
+    let device = *Device::list_all(platform)?.get(device_idx)
+        .ok_or_else(|| format!("Device index {} is out of range.", device_idx))?;
+
+    println!("Platform [{}]: {}", platform_idx, platform.name()?);
+    println!("Device [{}]: {} {}", device_idx, device.vendor()?, device.name()?);
+    println!("Replaying {} entries from '{}'...", replay.entries().len(), path);
+
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+
+    replay.run(&context, device)
+}
+
+pub fn main() {
+    match replay() {
+        Ok(report) => {
+            println!("Replayed {} commands: {} reads checked, {} unchecked.",
+                report.commands, report.reads_checked, report.reads_unchecked);
+
+            if !report.writes_unknown.is_empty() {
+                println!("Warning: {} writes were not captured in full and were replayed \
+                    as zeros: {:?}", report.writes_unknown.len(), report.writes_unknown);
+            }
+
+            for mismatch in report.mismatches.iter() {
+                println!("Mismatch: Read [{}] of buffer [{}]: expected {:?}, got {:?}",
+                    mismatch.cmd, mismatch.buffer, mismatch.expected, mismatch.actual);
+            }
+
+            if !report.is_ok() { process::exit(1); }
+        },
+        Err(err) => {
+            println!("{}", err);
+            process::exit(2);
+        },
+    }
+}
@@ -0,0 +1,272 @@
+//! Re-execution of captured command streams.
+//!
+//! Buffers are recreated as plain byte buffers of the same size (sub-buffers
+//! become stand-alone buffers), maps are replayed as the equivalent write or
+//! blocking read and fills are replayed as writes of the expanded pattern.
+//! Each command waits on the replayed events of the commands it originally
+//! depended on.
+//!
+
+use std::collections::HashMap;
+use std::path::Path;
+use ocl::{core, Context, Device, Queue, Program, Buffer, Event, EventList, MemFlags};
+use ocl::core::ArgVal;
+use ocl::ffi::c_void;
+use ocl::flags::CommandQueueProperties;
+use ocl::error::Result as OclResult;
+use crate::{Entry, Payload, Arg, CmdId, DEFAULT_DATA_LIMIT};
+
+
+/// A read whose replayed result differs from the captured one.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    pub cmd: CmdId,
+    pub buffer: usize,
+    pub expected: Payload,
+    pub actual: Payload,
+}
+
+/// The outcome of a replay.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayReport {
+    /// Number of commands executed.
+    pub commands: usize,
+    /// Reads whose results were compared against the capture.
+    pub reads_checked: usize,
+    /// Reads with no recorded payload (e.g. the run failed before the read
+    /// completed).
+    pub reads_unchecked: usize,
+    /// Writes whose payload was not recorded in full (e.g. the run failed
+    /// before a write map's data was set) and which were replayed as zeros.
+    pub writes_unknown: Vec<CmdId>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ReplayReport {
+    /// Returns `true` if every checked read matched the capture.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+
+/// A loaded capture, ready to be replayed.
+pub struct Replay {
+    entries: Vec<Entry>,
+}
+
+impl Replay {
+    /// Loads a capture file.
+    pub fn load<P: AsRef<Path>>(path: P) -> OclResult<Replay> {
+        Ok(Replay { entries: crate::load(path)? })
+    }
+
+    /// The captured entries.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Re-executes every captured command on `device`.
+    pub fn run(&self, context: &Context, device: Device) -> OclResult<ReplayReport> {
+        let queue_flags = Some(CommandQueueProperties::new().out_of_order());
+        let queue = Queue::new(context, device, queue_flags)
+            .or_else(|_| Queue::new(context, device, None))?;
+
+        let mut programs: HashMap<usize, Program> = HashMap::new();
+        let mut buffers: HashMap<usize, (Buffer<u8>, usize)> = HashMap::new();
+        let mut kernels: HashMap<usize, (core::Kernel, WorkSizes)> = HashMap::new();
+        let mut events: HashMap<CmdId, Event> = HashMap::new();
+        let mut report = ReplayReport::default();
+
+        // Host access and host pointer flags make no sense for the replayed
+        // buffers, which are always written and read directly:
+        let stripped_flags = MemFlags::new().host_write_only().host_read_only().host_no_access()
+            .use_host_ptr().copy_host_ptr();
+
+        for entry in self.entries.iter() {
+            let mut ev = Event::empty();
+
+            match *entry {
+                Entry::Program { id, ref src } => {
+                    let program = Program::builder()
+                        .devices(device)
+                        .src(src.clone())
+                        .build(context)?;
+                    programs.insert(id, program);
+                    continue;
+                },
+                Entry::Buffer { id, len, elem_size, flags, .. } => {
+                    let mut flags = MemFlags::from_bits_truncate(flags) & !stripped_flags;
+                    if flags.is_empty() { flags = MemFlags::new().read_write(); }
+
+                    let buffer = Buffer::<u8>::builder()
+                        .queue(queue.clone())
+                        .flags(flags)
+                        .len(len * elem_size)
+                        .build()?;
+                    buffers.insert(id, (buffer, elem_size));
+                    continue;
+                },
+                Entry::Kernel { id, program, ref name, ref global_work_size, ref local_work_size,
+                        ref global_work_offset, ref args } => {
+                    let program = programs.get(&program)
+                        .ok_or_else(|| format!("Replay: Kernel '{}' refers to an unknown program.", name))?;
+                    let kernel = core::create_kernel(program.as_core(), name)?;
+
+                    for (arg_idx, arg) in args.iter().enumerate() {
+                        match *arg {
+                            Arg::Buffer { id } => {
+                                let (buffer, _) = get_buffer(&buffers, id)?;
+                                core::set_kernel_arg(&kernel, arg_idx as u32,
+                                    ArgVal::mem(buffer.as_core()))?;
+                            },
+                            Arg::Scalar { ref hex } => {
+                                let bytes = crate::from_hex(hex)
+                                    .ok_or_else(|| format!("Replay: Invalid argument for kernel '{}'.", name))?;
+                                unsafe {
+                                    core::set_kernel_arg(&kernel, arg_idx as u32,
+                                        ArgVal::from_raw(bytes.len(), bytes.as_ptr() as *const c_void, false))?;
+                                }
+                            },
+                        }
+                    }
+
+                    let sizes = WorkSizes::new(global_work_size, local_work_size,
+                        global_work_offset).ok_or_else(|| format!("Replay: Invalid work sizes \
+                        for kernel '{}'.", name))?;
+                    kernels.insert(id, (kernel, sizes));
+                    continue;
+                },
+                Entry::Write { buffer, ref payload, ref waits, .. } |
+                        Entry::Map { buffer, write: true, ref payload, ref waits, .. } => {
+                    let (buf, _) = get_buffer(&buffers, buffer)?;
+                    let bytes = match payload.as_ref().and_then(|payload| payload.to_bytes()) {
+                        Some(bytes) => bytes,
+                        None => {
+                            report.writes_unknown.push(entry.cmd().unwrap());
+                            vec![0u8; buf.len()]
+                        },
+                    };
+
+                    buf.cmd().write(&bytes[..bytes.len().min(buf.len())])
+                        .ewait(&wait_list(&events, waits))
+                        .enew(&mut ev)
+                        .enq()?;
+                },
+                Entry::Fill { buffer, ref pattern, ref waits, .. } => {
+                    let (buf, _) = get_buffer(&buffers, buffer)?;
+                    let pattern = crate::from_hex(pattern).filter(|pattern| !pattern.is_empty())
+                        .ok_or("Replay: Invalid fill pattern.")?;
+                    let bytes: Vec<u8> = pattern.iter().cloned().cycle().take(buf.len()).collect();
+
+                    buf.cmd().write(&bytes)
+                        .ewait(&wait_list(&events, waits))
+                        .enew(&mut ev)
+                        .enq()?;
+                },
+                Entry::Copy { source, target, ref waits, .. } => {
+                    let (src_buf, _) = get_buffer(&buffers, source)?;
+                    let (tar_buf, _) = get_buffer(&buffers, target)?;
+
+                    src_buf.cmd().copy(tar_buf, None, Some(src_buf.len().min(tar_buf.len())))
+                        .ewait(&wait_list(&events, waits))
+                        .enew(&mut ev)
+                        .enq()?;
+                },
+                Entry::Enqueue { kernel, ref waits, .. } => {
+                    let (ref kernel, ref sizes) = *kernels.get(&kernel)
+                        .ok_or("Replay: Launch of an unknown kernel.")?;
+
+                    unsafe {
+                        core::enqueue_kernel(queue.as_core(), kernel, sizes.dims, sizes.offset,
+                            &sizes.global, sizes.local, Some(&wait_list(&events, waits)),
+                            Some(&mut ev))?;
+                    }
+                },
+                Entry::Read { buffer, ref payload, ref waits, .. } |
+                        Entry::Map { buffer, write: false, ref payload, ref waits, .. } => {
+                    let (buf, elem_size) = get_buffer(&buffers, buffer)?;
+                    let mut data = vec![0u8; buf.len()];
+
+                    buf.cmd().read(&mut data)
+                        .ewait(&wait_list(&events, waits))
+                        .enew(&mut ev)
+                        .enq()?;
+
+                    match *payload {
+                        Some(ref expected) => {
+                            report.reads_checked += 1;
+                            if !expected.matches(&data) {
+                                report.mismatches.push(Mismatch {
+                                    cmd: entry.cmd().unwrap(),
+                                    buffer: buffer,
+                                    expected: expected.clone(),
+                                    actual: Payload::new(&data, *elem_size, DEFAULT_DATA_LIMIT),
+                                });
+                            }
+                        },
+                        None => report.reads_unchecked += 1,
+                    }
+                },
+            }
+
+            events.insert(entry.cmd().unwrap(), ev);
+            report.commands += 1;
+        }
+
+        queue.finish()?;
+        Ok(report)
+    }
+}
+
+
+/// A kernel's captured work sizes, padded to three dimensions.
+struct WorkSizes {
+    dims: u32,
+    global: [usize; 3],
+    local: Option<[usize; 3]>,
+    offset: Option<[usize; 3]>,
+}
+
+impl WorkSizes {
+    /// Returns `None` unless the global work size has one to three
+    /// dimensions and the local work size and offset are empty or have as
+    /// many.
+    fn new(global: &[usize], local: &[usize], offset: &[usize]) -> Option<WorkSizes> {
+        let dims = global.len();
+        if dims == 0 || dims > 3 { return None; }
+
+        let pad = |sizes: &[usize], fill: usize| -> Option<Option<[usize; 3]>> {
+            match sizes.len() {
+                0 => Some(None),
+                len if len == dims => {
+                    let mut padded = [fill; 3];
+                    padded[..dims].copy_from_slice(sizes);
+                    Some(Some(padded))
+                },
+                _ => None,
+            }
+        };
+
+        Some(WorkSizes {
+            dims: dims as u32,
+            global: pad(global, 1)??,
+            local: pad(local, 1)?,
+            offset: pad(offset, 0)?,
+        })
+    }
+}
+
+fn get_buffer(buffers: &HashMap<usize, (Buffer<u8>, usize)>, id: usize)
+        -> OclResult<&(Buffer<u8>, usize)>
+{
+    buffers.get(&id).ok_or_else(|| format!("Replay: Unknown buffer [{}].", id).into())
+}
+
+fn wait_list(events: &HashMap<CmdId, Event>, waits: &[CmdId]) -> EventList {
+    let mut list = EventList::new();
+    for cmd in waits {
+        if let Some(ev) = events.get(cmd) { list.push(ev.clone()); }
+    }
+    list
+}
@@ -0,0 +1,483 @@
+//! Images that fall back to buffers.
+//!
+//! `FallbackImage` checks `DeviceInfo::ImageSupport` and
+//! `Image::supported_formats` before creating an image. If the device has
+//! no image support or lacks the requested format, the texels are stored
+//! in a plain `Buffer` instead, laid out row by row with a row and slice
+//! pitch (see `Layout`), and reads, writes and copies are emulated with
+//! one rectangular buffer command each. Code written against
+//! `FallbackImage` therefore runs on either kind of device:
+//!
+//! ```ignore
+//! let image = FallbackImage::<u8>::builder()
+//!     .dims(2048)
+//!     .queue(queue.clone())
+//!     .build()?;
+//!
+//! if let Some(reason) = image.fallback_reason() {
+//!     println!("Emulating image: {}", reason);
+//! }
+//! image.write(&texels)?;
+//! ```
+//!
+//! Only channel orders with one texel component per channel (see
+//! `channel_count`) can be emulated.
+//!
+
+extern crate ocl;
+
+use std::fmt;
+use std::mem;
+use ocl::{Buffer, Event, Image, Queue, OclPrm, SpatialDims};
+use ocl::core::{self, ImageFormat};
+use ocl::enums::{ImageChannelOrder, ImageChannelDataType, MemObjectType, DeviceInfo,
+    DeviceInfoResult};
+use ocl::error::Result as OclResult;
+use ocl::flags::MemFlags;
+
+
+/// The number of texel components per pixel for `order`, or `None` for
+/// packed or depth orders.
+pub fn channel_count(order: ImageChannelOrder) -> Option<usize> {
+    use ocl::enums::ImageChannelOrder::*;
+    match order {
+        R | A | Intensity | Luminance | Rx | Depth => Some(1),
+        Rg | Ra | Rgx => Some(2),
+        Rgba | Bgra | Argb | Abgr | Srgba | Sbgra | Rgbx | Srgbx => Some(4),
+        _ => None,
+    }
+}
+
+/// The image type implied by the number of dimensions.
+fn image_type_for(dims: &SpatialDims) -> MemObjectType {
+    match *dims {
+        SpatialDims::Three(..) => MemObjectType::Image3d,
+        SpatialDims::Two(..) => MemObjectType::Image2d,
+        _ => MemObjectType::Image1d,
+    }
+}
+
+
+/// The layout of an emulated image within its buffer, in texel
+/// components (`T`s).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// Width, height and depth in pixels (unused dimensions are 1).
+    pub dims: [usize; 3],
+    /// Components per pixel.
+    pub channels: usize,
+    /// Distance between the starts of consecutive rows.
+    pub row_pitch: usize,
+    /// Distance between the starts of consecutive slices.
+    pub slice_pitch: usize,
+}
+
+impl Layout {
+    /// A layout with rows padded to a multiple of `pitch_alignment` pixels.
+    pub fn new(dims: [usize; 3], channels: usize, pitch_alignment: usize) -> Layout {
+        let align = pitch_alignment.max(1);
+        let row_pitch = (dims[0] + align - 1) / align * align * channels;
+        Layout { dims, channels, row_pitch, slice_pitch: row_pitch * dims[1] }
+    }
+
+    /// The buffer length needed.
+    pub fn len(&self) -> usize {
+        self.slice_pitch * self.dims[2]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The buffer offset of the pixel at `origin`.
+    pub fn offset(&self, origin: [usize; 3]) -> usize {
+        origin[2] * self.slice_pitch + origin[1] * self.row_pitch + origin[0] * self.channels
+    }
+
+    fn check_region(&self, origin: [usize; 3], region: [usize; 3]) -> OclResult<()> {
+        for d in 0..3 {
+            if origin[d] + region[d] > self.dims[d] {
+                return Err(format!("FallbackImage: Region {:?} at {:?} exceeds the image \
+                    dimensions {:?}.", region, origin, self.dims).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// `origin` or `region` (in pixels) as a buffer rect origin or region,
+    /// whose first dimension counts texel components.
+    fn rect(&self, pixels: [usize; 3]) -> [usize; 3] {
+        [pixels[0] * self.channels, pixels[1], pixels[2]]
+    }
+
+    /// The row and slice pitch in bytes, for buffer rect commands.
+    fn pitch_bytes<T>(&self) -> (usize, usize) {
+        (self.row_pitch * mem::size_of::<T>(), self.slice_pitch * mem::size_of::<T>())
+    }
+}
+
+/// The row and slice pitch in bytes of tightly packed host data for
+/// `region` (in pixels).
+fn packed_pitch_bytes<T>(region: [usize; 3], channels: usize) -> (usize, usize) {
+    let row_pitch = region[0] * channels * mem::size_of::<T>();
+    (row_pitch, row_pitch * region[1])
+}
+
+
+enum Storage<T: OclPrm> {
+    Image(Image<T>),
+    Buffer(Buffer<T>, Layout),
+}
+
+
+/// Builds a `FallbackImage`.
+pub struct FallbackImageBuilder<'a, T: OclPrm> {
+    format: ImageFormat,
+    image_type: Option<MemObjectType>,
+    dims: SpatialDims,
+    flags: MemFlags,
+    queue: Option<Queue>,
+    host_data: Option<&'a [T]>,
+    force_buffer: bool,
+}
+
+impl<'a, T: OclPrm> FallbackImageBuilder<'a, T> {
+    pub fn new() -> FallbackImageBuilder<'a, T> {
+        FallbackImageBuilder {
+            format: ImageFormat::new_rgba(),
+            image_type: None,
+            dims: SpatialDims::Unspecified,
+            flags: MemFlags::new().read_write(),
+            queue: None,
+            host_data: None,
+            force_buffer: false,
+        }
+    }
+
+    pub fn channel_order(mut self, order: ImageChannelOrder) -> FallbackImageBuilder<'a, T> {
+        self.format.channel_order = order;
+        self
+    }
+
+    pub fn channel_data_type(mut self, data_type: ImageChannelDataType)
+            -> FallbackImageBuilder<'a, T>
+    {
+        self.format.channel_data_type = data_type;
+        self
+    }
+
+    /// Defaults to a 1D, 2D or 3D image depending on `dims`.
+    pub fn image_type(mut self, image_type: MemObjectType) -> FallbackImageBuilder<'a, T> {
+        self.image_type = Some(image_type);
+        self
+    }
+
+    pub fn dims<D: Into<SpatialDims>>(mut self, dims: D) -> FallbackImageBuilder<'a, T> {
+        self.dims = dims.into();
+        self
+    }
+
+    /// Access flags (default: read-write). Host pointer flags are added as
+    /// needed.
+    pub fn flags(mut self, flags: MemFlags) -> FallbackImageBuilder<'a, T> {
+        self.flags = flags;
+        self
+    }
+
+    pub fn queue(mut self, queue: Queue) -> FallbackImageBuilder<'a, T> {
+        self.queue = Some(queue);
+        self
+    }
+
+    /// Initial texels, tightly packed.
+    pub fn copy_host_slice(mut self, data: &'a [T]) -> FallbackImageBuilder<'a, T> {
+        self.host_data = Some(data);
+        self
+    }
+
+    /// Always emulate (for testing the fallback on devices with image
+    /// support).
+    pub fn force_buffer(mut self) -> FallbackImageBuilder<'a, T> {
+        self.force_buffer = true;
+        self
+    }
+
+    /// Returns why an image of this format cannot be created, or `None` if
+    /// it can.
+    fn unsupported_reason(&self, queue: &Queue, image_type: MemObjectType)
+            -> OclResult<Option<String>>
+    {
+        if self.force_buffer {
+            return Ok(Some("buffer storage requested".to_owned()));
+        }
+
+        match queue.device().info(DeviceInfo::ImageSupport)? {
+            DeviceInfoResult::ImageSupport(true) => (),
+            _ => return Ok(Some("the device has no image support".to_owned())),
+        }
+
+        let supported = Image::<T>::supported_formats(&queue.context(), self.flags, image_type)?;
+        let found = supported.iter().any(|format| match *format {
+            Ok(ref f) => f.channel_order == self.format.channel_order
+                && f.channel_data_type == self.format.channel_data_type,
+            Err(_) => false,
+        });
+
+        if found {
+            Ok(None)
+        } else {
+            Ok(Some(format!("{:?}/{:?} is not a supported {:?} format",
+                self.format.channel_order, self.format.channel_data_type, image_type)))
+        }
+    }
+
+    pub fn build(self) -> OclResult<FallbackImage<T>> {
+        let queue = self.queue.clone().ok_or("FallbackImage: A queue is required.")?;
+        let lens = self.dims.to_lens()?;
+        let image_type = self.image_type.unwrap_or_else(|| image_type_for(&self.dims));
+        let reason = self.unsupported_reason(&queue, image_type)?;
+
+        let storage = match reason {
+            None => {
+                let mut builder = Image::<T>::builder();
+                builder.channel_order(self.format.channel_order)
+                    .channel_data_type(self.format.channel_data_type)
+                    .image_type(image_type)
+                    .dims(self.dims)
+                    .flags(self.flags)
+                    .queue(queue.clone());
+                if let Some(data) = self.host_data {
+                    builder.copy_host_slice(data);
+                }
+                Storage::Image(builder.build()?)
+            },
+            Some(ref reason) => {
+                let channels = channel_count(self.format.channel_order)
+                    .ok_or_else(|| format!("FallbackImage: Cannot emulate {:?} images ({}).",
+                        self.format.channel_order, reason))?;
+                let alignment = match queue.device().info(DeviceInfo::ImagePitchAlignment) {
+                    Ok(DeviceInfoResult::ImagePitchAlignment(align)) => align as usize,
+                    _ => 1,
+                };
+                let layout = Layout::new(lens, channels, alignment);
+
+                let buffer = Buffer::<T>::builder()
+                    .queue(queue.clone())
+                    .flags(self.flags)
+                    .len(layout.len())
+                    .fill_val(T::default())
+                    .build()?;
+
+                Storage::Buffer(buffer, layout)
+            },
+        };
+
+        let image = FallbackImage {
+            storage: storage,
+            format: self.format,
+            image_type: image_type,
+            dims: lens,
+            queue: queue,
+            fallback_reason: reason.unwrap_or_default(),
+        };
+
+        // Images were initialized by the builder above:
+        if let (true, Some(data)) = (image.is_emulated(), self.host_data) {
+            image.write(data)?;
+        }
+        Ok(image)
+    }
+}
+
+impl<'a, T: OclPrm> Default for FallbackImageBuilder<'a, T> {
+    fn default() -> FallbackImageBuilder<'a, T> {
+        FallbackImageBuilder::new()
+    }
+}
+
+
+/// An `Image`, or a buffer emulating one.
+pub struct FallbackImage<T: OclPrm> {
+    storage: Storage<T>,
+    format: ImageFormat,
+    image_type: MemObjectType,
+    dims: [usize; 3],
+    queue: Queue,
+    fallback_reason: String,
+}
+
+impl<T: OclPrm> FallbackImage<T> {
+    pub fn builder<'a>() -> FallbackImageBuilder<'a, T> {
+        FallbackImageBuilder::new()
+    }
+
+    /// Returns `true` if the texels live in a buffer.
+    pub fn is_emulated(&self) -> bool {
+        match self.storage {
+            Storage::Image(_) => false,
+            Storage::Buffer(..) => true,
+        }
+    }
+
+    /// Why the image is emulated, if it is.
+    pub fn fallback_reason(&self) -> Option<&str> {
+        if self.is_emulated() { Some(&self.fallback_reason) } else { None }
+    }
+
+    /// The real image, if not emulated.
+    pub fn as_image(&self) -> Option<&Image<T>> {
+        match self.storage {
+            Storage::Image(ref image) => Some(image),
+            Storage::Buffer(..) => None,
+        }
+    }
+
+    /// The emulating buffer and its layout, if emulated.
+    pub fn as_buffer(&self) -> Option<(&Buffer<T>, &Layout)> {
+        match self.storage {
+            Storage::Image(_) => None,
+            Storage::Buffer(ref buffer, ref layout) => Some((buffer, layout)),
+        }
+    }
+
+    pub fn format(&self) -> &ImageFormat {
+        &self.format
+    }
+
+    pub fn image_type(&self) -> MemObjectType {
+        self.image_type
+    }
+
+    /// Width, height and depth (unused dimensions are 1).
+    pub fn dims(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    /// Texel components per pixel (when known).
+    pub fn channels(&self) -> Option<usize> {
+        channel_count(self.format.channel_order)
+    }
+
+    fn full_region(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    /// Writes the whole image from tightly packed texels.
+    pub fn write(&self, data: &[T]) -> OclResult<()> {
+        self.write_region([0, 0, 0], self.full_region(), data)
+    }
+
+    /// Reads the whole image into tightly packed texels.
+    pub fn read(&self, data: &mut [T]) -> OclResult<()> {
+        self.read_region([0, 0, 0], self.full_region(), data)
+    }
+
+    /// Writes `region` (in pixels) at `origin` from tightly packed texels.
+    pub fn write_region(&self, origin: [usize; 3], region: [usize; 3], data: &[T]) -> OclResult<()> {
+        match self.storage {
+            Storage::Image(ref image) => {
+                image.cmd().write(data).origin(origin).region(region).queue(&self.queue).enq()
+            },
+            Storage::Buffer(ref buffer, ref layout) => {
+                layout.check_region(origin, region)?;
+                check_len(data.len(), region, layout.channels)?;
+                let (buffer_row_pitch, buffer_slc_pitch) = layout.pitch_bytes::<T>();
+                let (host_row_pitch, host_slc_pitch) =
+                    packed_pitch_bytes::<T>(region, layout.channels);
+                unsafe {
+                    core::enqueue_write_buffer_rect(self.queue.as_core(), buffer, true,
+                        layout.rect(origin), [0, 0, 0], layout.rect(region), buffer_row_pitch,
+                        buffer_slc_pitch, host_row_pitch, host_slc_pitch, data, None::<Event>,
+                        None::<&mut Event>)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Reads `region` (in pixels) at `origin` into tightly packed texels.
+    pub fn read_region(&self, origin: [usize; 3], region: [usize; 3], data: &mut [T])
+            -> OclResult<()>
+    {
+        match self.storage {
+            Storage::Image(ref image) => {
+                image.cmd().read(data).origin(origin).region(region).queue(&self.queue).enq()
+            },
+            Storage::Buffer(ref buffer, ref layout) => {
+                layout.check_region(origin, region)?;
+                check_len(data.len(), region, layout.channels)?;
+                let (buffer_row_pitch, buffer_slc_pitch) = layout.pitch_bytes::<T>();
+                let (host_row_pitch, host_slc_pitch) =
+                    packed_pitch_bytes::<T>(region, layout.channels);
+                unsafe {
+                    core::enqueue_read_buffer_rect(self.queue.as_core(), buffer, true,
+                        layout.rect(origin), [0, 0, 0], layout.rect(region), buffer_row_pitch,
+                        buffer_slc_pitch, host_row_pitch, host_slc_pitch, data, None::<Event>,
+                        None::<&mut Event>)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Copies `region` (in pixels) from `src_origin` in this image to
+    /// `dst_origin` in `dst`. Both must have the same format.
+    pub fn copy_to(&self, dst: &FallbackImage<T>, src_origin: [usize; 3],
+            dst_origin: [usize; 3], region: [usize; 3]) -> OclResult<()>
+    {
+        if self.format.channel_order != dst.format.channel_order
+                || self.format.channel_data_type != dst.format.channel_data_type {
+            return Err("FallbackImage: Cannot copy between images of different formats.".into());
+        }
+
+        match (&self.storage, &dst.storage) {
+            (&Storage::Image(ref src), &Storage::Image(ref dst_image)) => {
+                src.cmd().copy(dst_image, dst_origin).origin(src_origin).region(region)
+                    .queue(&self.queue).enq()
+            },
+            (&Storage::Buffer(ref src, ref src_layout), &Storage::Buffer(ref dst_buf, ref dst_layout)) => {
+                src_layout.check_region(src_origin, region)?;
+                dst_layout.check_region(dst_origin, region)?;
+                let (src_row_pitch, src_slc_pitch) = src_layout.pitch_bytes::<T>();
+                let (dst_row_pitch, dst_slc_pitch) = dst_layout.pitch_bytes::<T>();
+                core::enqueue_copy_buffer_rect(self.queue.as_core(), src, dst_buf,
+                    src_layout.rect(src_origin),
+                    dst_layout.rect(dst_origin), src_layout.rect(region), src_row_pitch,
+                    src_slc_pitch, dst_row_pitch, dst_slc_pitch, None::<Event>,
+                    None::<&mut Event>)?;
+                Ok(())
+            },
+            // One real and one emulated image: go through the host.
+            _ => {
+                let channels = self.channels().ok_or("FallbackImage: Unknown channel count.")?;
+                let mut data = vec![T::default(); region[0] * region[1] * region[2] * channels];
+                self.read_region(src_origin, region, &mut data)?;
+                dst.write_region(dst_origin, region, &data)
+            },
+        }
+    }
+}
+
+impl<T: OclPrm> fmt::Display for FallbackImage<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.storage {
+            Storage::Image(ref image) => write!(f, "{}", image),
+            Storage::Buffer(ref buffer, ref layout) => {
+                write!(f, "Image (emulated: {}) {{ Type: {:?}, Format: {:?}/{:?}, Dims: {:?}, \
+                    RowPitch: {}, SlicePitch: {} }}\n{}", self.fallback_reason, self.image_type,
+                    self.format.channel_order, self.format.channel_data_type, layout.dims,
+                    layout.row_pitch, layout.slice_pitch, buffer)
+            },
+        }
+    }
+}
+
+
+fn check_len(len: usize, region: [usize; 3], channels: usize) -> OclResult<()> {
+    let expected = region[0] * region[1] * region[2] * channels;
+    if len != expected {
+        return Err(format!("FallbackImage: Expected {} texel components for region {:?}, \
+            found {}.", expected, region, len).into());
+    }
+    Ok(())
+}
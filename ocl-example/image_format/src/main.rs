@@ -1,8 +1,36 @@
 #[macro_use] extern crate colorify;
 extern crate ocl;
+extern crate serde;
+extern crate serde_json;
+extern crate image_io;
+
+mod matrix;
+
+use std::env;
+use std::fs;
 use ocl::{Result as OclResult, Platform, Device, Context, Image, Queue};
 use ocl::enums::MemObjectType;
-use ocl::core::{OclPrm};
+use ocl::flags::MemFlags;
+use image_io::{HostImage, DeviceImage};
+use crate::matrix::CapabilityMatrix;
+
+static USAGE: &'static str = "\
+Usage: image_format [COMMAND]
+
+Commands:
+    list                      Print the read-write 2D image formats of every
+                              device (default)
+    matrix [OPTIONS]          Query every image type and access flag of every
+                              device
+        --format <json|csv>   Output format (default: json)
+        --out <FILE>          Write to FILE instead of stdout
+    diff [FILE...] [--all]    Compare devices side by side, either the local
+                              devices or those in the given matrix JSON files
+                              (e.g. saved on other machines). Only formats
+                              the devices disagree on are shown unless
+                              `--all` is passed.
+    roundtrip <IN> <OUT>      Load a PNG/PPM/PGM image into an image on the
+                              first device, read it back and save it";
 
 fn img_formats() -> OclResult<()> {
     for (p_idx, platform) in Platform::list().into_iter().enumerate() {
@@ -16,1000 +44,107 @@ fn img_formats() -> OclResult<()> {
                 MemObjectType::Image2d)?;
 
             println!("Image Formats: {:#?}.", sup_img_formats);
+        }
+    }
+    Ok(())
+}
 
-            // let queue = Queue::new(&context, device, Some(ocl::core::QUEUE_PROFILING_ENABLE))?;
+fn capability_matrix(args: &[String]) -> OclResult<()> {
+    let mut csv = false;
+    let mut out = None;
+    let mut args = args.iter();
 
-            // let image = Image::<u8>::builder()
-            //     .dims(2048)
-            //     .queue(queue.clone())
-            //     .build()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => csv = match args.next().map(|s| s.as_str()) {
+                Some("json") => false,
+                Some("csv") => true,
+                _ => return Err("'--format' must be 'json' or 'csv'.".into()),
+            },
+            "--out" => out = Some(args.next().ok_or("Missing value for '--out'.")?),
+            other => return Err(format!("Unknown argument: '{}'\n\n{}", other, USAGE).into()),
+        }
+    }
+
+    let matrix = CapabilityMatrix::query()?;
+    let text = if csv { matrix.to_csv() } else { matrix.to_json() };
+
+    match out {
+        Some(path) => {
+            fs::write(path, text).map_err(|err| format!("Unable to write '{}': {}", path, err))?;
+            printlnc!(white_bold: "Wrote the capability matrix of {} devices to '{}'.",
+                matrix.devices.len(), path);
+        },
+        None => println!("{}", text),
+    }
+    Ok(())
+}
 
-            // fn print_image_info<S: OclPrm>(image: &Image<S>) {
-            //         printlnc!(peach: "{}", image);
-            // }
-            // print_image_info(&image);
+fn capability_diff(args: &[String]) -> OclResult<()> {
+    let all = args.iter().any(|arg| arg == "--all");
+    let files: Vec<_> = args.iter().filter(|arg| *arg != "--all").collect();
+
+    let matrix = if files.is_empty() {
+        CapabilityMatrix::query()?
+    } else {
+        let mut matrix = CapabilityMatrix::default();
+        for path in files {
+            matrix.merge(CapabilityMatrix::load(path)?);
         }
+        matrix
+    };
+
+    if matrix.devices.is_empty() {
+        return Err("No devices to compare.".into());
     }
+
+    print!("{}", matrix.diff(all));
+    Ok(())
+}
+
+fn roundtrip(args: &[String]) -> OclResult<()> {
+    let (src_path, dst_path) = match args {
+        [src, dst] => (src, dst),
+        _ => return Err(USAGE.into()),
+    };
+
+    let host = HostImage::load(src_path)?;
+    printlnc!(white_bold: "Loaded '{}': {}x{}, {} channel(s), max {}.", src_path, host.width(),
+        host.height(), host.channels(), host.max_val());
+
+    let platform = Platform::default();
+    let device = Device::first(platform)?;
+    let context = Context::builder().platform(platform).devices(device).build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    let image = if host.max_val() > 255 {
+        DeviceImage::<u16>::upload(&host, &queue, MemFlags::new().read_write())?.download()?
+    } else {
+        let image = DeviceImage::<u8>::upload(&host, &queue, MemFlags::new().read_write())?;
+        printlnc!(teal: "Device image format: {:?}.", image.format());
+        image.download()?
+    };
+
+    image.save(dst_path)?;
+    printlnc!(white_bold: "Saved '{}' ({}).", dst_path,
+        if image == host { "identical" } else { "converted" });
     Ok(())
 }
 
 pub fn main() {
-    match img_formats() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let res = match args.first().map(|s| s.as_str()) {
+        None | Some("list") => img_formats(),
+        Some("matrix") => capability_matrix(&args[1..]),
+        Some("diff") => capability_diff(&args[1..]),
+        Some("roundtrip") => roundtrip(&args[1..]),
+        Some("--help") | Some("-h") => { println!("{}", USAGE); Ok(()) },
+        Some(other) => Err(format!("Unknown command: '{}'\n\n{}", other, USAGE).into()),
+    };
+
+    match res {
         Ok(_) => (),
         Err(err) => println!("{}", err),
     }
 }
-
-
-// Platform [0]: Intel(R) OpenCL
-// Device [0]: Intel(R) Corporation Intel(R) HD Graphics 520
-// Image Formats: [
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Bgra,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Depth,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Depth,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-// ].
-// Platform [0]: Intel(R) OpenCL
-// Device [1]: Intel(R) Corporation Intel(R) Core(TM) i7-6500U CPU @ 2.50GHz
-// Image Formats: [
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Bgra,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Depth,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Depth,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-// ].
-// Platform [1]: AMD Accelerated Parallel Processing
-// Device [0]: Advanced Micro Devices, Inc. Iceland
-// Image Formats: [
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: R,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: A,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rg,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: SignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: UnsignedInt32,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgba,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Argb,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Argb,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Argb,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Argb,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Bgra,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Bgra,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Bgra,
-//             channel_data_type: SignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Bgra,
-//             channel_data_type: UnsignedInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Luminance,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: SnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: SnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: UnormInt8,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: HalfFloat,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Intensity,
-//             channel_data_type: Float,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Rgb,
-//             channel_data_type: UnormInt101010,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Depth,
-//             channel_data_type: UnormInt16,
-//         },
-//     ),
-//     Ok(
-//         ImageFormat {
-//             channel_order: Depth,
-//             channel_data_type: Float,
-//         },
-//     ),
-// ]
\ No newline at end of file
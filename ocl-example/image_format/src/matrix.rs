@@ -0,0 +1,321 @@
+//! Image format capability matrix.
+//!
+//! For every device, lists the supported image formats of every image type
+//! (`MemObjectType`) under each access flag. A matrix can be written as JSON
+//! or CSV, matrices saved on different machines can be merged, and the
+//! merged result compared side by side to find the formats every device
+//! supports.
+//!
+//! Drivers reject queries for image types they don't know (e.g. 1D and array
+//! images on OpenCL 1.1 devices). Such failures are recorded per image type
+//! and access flag and the remaining combinations are still queried. A
+//! device which can't be queried at all (e.g. no context can be created on
+//! it) is recorded with its error and the remaining devices are still
+//! queried.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use ocl::{Result as OclResult, Platform, Device, Context, Image};
+use ocl::enums::MemObjectType;
+use ocl::flags::MemFlags;
+
+
+/// Image types queried, with the names used in output.
+pub const IMAGE_TYPES: [(MemObjectType, &str); 6] = [
+    (MemObjectType::Image1d, "image1d"),
+    (MemObjectType::Image1dBuffer, "image1d_buffer"),
+    (MemObjectType::Image1dArray, "image1d_array"),
+    (MemObjectType::Image2d, "image2d"),
+    (MemObjectType::Image2dArray, "image2d_array"),
+    (MemObjectType::Image3d, "image3d"),
+];
+
+/// Access flags queried, with the names used in output.
+pub const ACCESS: [&str; 3] = ["read_only", "write_only", "read_write"];
+
+fn access_flags(access: &str) -> MemFlags {
+    match access {
+        "read_only" => MemFlags::new().read_only(),
+        "write_only" => MemFlags::new().write_only(),
+        _ => MemFlags::new().read_write(),
+    }
+}
+
+
+/// One supported combination of image type, access and format.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FormatSupport {
+    pub image_type: String,
+    pub access: String,
+    pub channel_order: String,
+    pub channel_data_type: String,
+}
+
+impl FormatSupport {
+    /// The key of the image type and access in `DeviceFormats::errors`.
+    fn query_key(&self) -> String {
+        query_key(&self.image_type, &self.access)
+    }
+
+    fn row_label(&self) -> String {
+        format!("{} {} {}/{}", self.image_type, self.access, self.channel_order,
+            self.channel_data_type)
+    }
+}
+
+
+/// Everything a single device supports.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceFormats {
+    pub platform: String,
+    pub vendor: String,
+    pub device: String,
+    pub formats: BTreeSet<FormatSupport>,
+    /// Formats the driver reported but `ocl` could not parse.
+    #[serde(default)]
+    pub unrecognized: usize,
+    /// Failed queries, by image type and access (e.g. "image1d read_only").
+    #[serde(default)]
+    pub errors: BTreeMap<String, String>,
+    /// Why the device could not be queried at all, if it couldn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DeviceFormats {
+    /// Queries every image type and access flag on `device`.
+    pub fn query(platform: Platform, device: Device) -> OclResult<DeviceFormats> {
+        let context = Context::builder().platform(platform).devices(device).build()?;
+        let mut formats = BTreeSet::new();
+        let mut unrecognized = 0;
+        let mut errors = BTreeMap::new();
+
+        for &(image_type, type_name) in IMAGE_TYPES.iter() {
+            for &access in ACCESS.iter() {
+                let supported = match Image::<u8>::supported_formats(&context,
+                        access_flags(access), image_type) {
+                    Ok(supported) => supported,
+                    Err(err) => {
+                        errors.insert(query_key(type_name, access), err.to_string());
+                        continue;
+                    },
+                };
+
+                for format in supported {
+                    match format {
+                        Ok(format) => {
+                            formats.insert(FormatSupport {
+                                image_type: type_name.to_owned(),
+                                access: access.to_owned(),
+                                channel_order: format!("{:?}", format.channel_order),
+                                channel_data_type: format!("{:?}", format.channel_data_type),
+                            });
+                        },
+                        Err(_) => unrecognized += 1,
+                    }
+                }
+            }
+        }
+
+        Ok(DeviceFormats {
+            platform: platform.name()?,
+            vendor: device.vendor()?,
+            device: device.name()?,
+            formats: formats,
+            unrecognized: unrecognized,
+            errors: errors,
+            error: None,
+        })
+    }
+
+    /// A device which failed to be queried, named as far as possible.
+    fn failed(platform: Platform, device: Device, error: String) -> DeviceFormats {
+        let unknown = |_| "<unknown>".to_owned();
+        DeviceFormats {
+            platform: platform.name().unwrap_or_else(unknown),
+            vendor: device.vendor().unwrap_or_else(unknown),
+            device: device.name().unwrap_or_else(unknown),
+            formats: BTreeSet::new(),
+            unrecognized: 0,
+            errors: BTreeMap::new(),
+            error: Some(error),
+        }
+    }
+
+    /// Returns `true` if the device failed to list formats for the image
+    /// type and access of `fmt`.
+    fn failed_query(&self, fmt: &FormatSupport) -> bool {
+        self.error.is_some() || self.errors.contains_key(&fmt.query_key())
+    }
+
+    /// A short name for tables.
+    pub fn label(&self) -> String {
+        format!("{} {} ({})", self.vendor, self.device, self.platform)
+    }
+}
+
+
+/// Supported formats for any number of devices.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CapabilityMatrix {
+    pub devices: Vec<DeviceFormats>,
+}
+
+impl CapabilityMatrix {
+    /// Queries every device on every platform.
+    pub fn query() -> OclResult<CapabilityMatrix> {
+        let mut devices = Vec::new();
+        for platform in Platform::list() {
+            for device in Device::list_all(&platform)? {
+                let formats = DeviceFormats::query(platform, device)
+                    .unwrap_or_else(|err| DeviceFormats::failed(platform, device, err.to_string()));
+                devices.push(formats);
+            }
+        }
+        Ok(CapabilityMatrix { devices: devices })
+    }
+
+    /// Reads a matrix saved with `to_json`.
+    pub fn load<P: AsRef<Path>>(path: P) -> OclResult<CapabilityMatrix> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| format!("Unable to open '{}': {}",
+            path.display(), err))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| format!("Invalid capability matrix '{}': {}", path.display(), err).into())
+    }
+
+    /// Appends the devices of `other`.
+    pub fn merge(&mut self, other: CapabilityMatrix) {
+        self.devices.extend(other.devices);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// One row per device and supported combination (status `supported`),
+    /// failed query (`query_failed`, with the error as detail), device which
+    /// could not be queried (`device_failed`) and device with formats `ocl`
+    /// could not parse (`unrecognized`, with their number as detail).
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("platform,vendor,device,image_type,access,channel_order,\
+            channel_data_type,status,detail\n");
+        for dev in self.devices.iter() {
+            let mut row = |fields: [&str; 6]| {
+                let mut row = vec![csv_field(&dev.platform), csv_field(&dev.vendor),
+                    csv_field(&dev.device)];
+                row.extend(fields.iter().map(|field| csv_field(field)));
+                csv.push_str(&row.join(","));
+                csv.push('\n');
+            };
+
+            if let Some(ref err) = dev.error {
+                row(["", "", "", "", "device_failed", err]);
+            }
+            for fmt in dev.formats.iter() {
+                row([&fmt.image_type, &fmt.access, &fmt.channel_order, &fmt.channel_data_type,
+                    "supported", ""]);
+            }
+            for (key, err) in dev.errors.iter() {
+                let mut parts = key.splitn(2, ' ');
+                let (image_type, access) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+                row([image_type, access, "", "", "query_failed", err]);
+            }
+            if dev.unrecognized > 0 {
+                row(["", "", "", "", "unrecognized", &dev.unrecognized.to_string()]);
+            }
+        }
+        csv
+    }
+
+    /// Returns, for every combination supported by at least one device,
+    /// which devices support it.
+    pub fn support(&self) -> BTreeMap<&FormatSupport, Vec<bool>> {
+        let mut support = BTreeMap::new();
+        for dev in self.devices.iter() {
+            for fmt in dev.formats.iter() {
+                support.entry(fmt).or_insert_with(|| {
+                    self.devices.iter().map(|d| d.formats.contains(fmt)).collect()
+                });
+            }
+        }
+        support
+    }
+
+    /// The combinations supported by every device.
+    pub fn portable(&self) -> Vec<&FormatSupport> {
+        self.support().into_iter()
+            .filter(|&(_, ref devs)| devs.iter().all(|&supported| supported))
+            .map(|(fmt, _)| fmt)
+            .collect()
+    }
+
+    /// A side-by-side table of the devices. Only combinations on which the
+    /// devices disagree are listed unless `all` is set.
+    pub fn diff(&self, all: bool) -> String {
+        let support = self.support();
+        let rows: Vec<_> = support.iter()
+            .filter(|&(_, devs)| all || !devs.iter().all(|&supported| supported))
+            .collect();
+        let label_width = rows.iter().map(|&(fmt, _)| fmt.row_label().len()).max().unwrap_or(0);
+
+        let mut out = String::new();
+        for (d_idx, dev) in self.devices.iter().enumerate() {
+            out.push_str(&format!("[D{}] {}\n", d_idx, dev.label()));
+            if let Some(ref err) = dev.error {
+                out.push_str(&format!("     query failed: {}\n", err));
+            }
+            for (key, err) in dev.errors.iter() {
+                out.push_str(&format!("     {}: query failed: {}\n", key, err));
+            }
+        }
+        out.push('\n');
+
+        out.push_str(&format!("{:w$}", "", w = label_width));
+        for d_idx in 0..self.devices.len() {
+            out.push_str(&format!(" {:>4}", format!("D{}", d_idx)));
+        }
+        out.push('\n');
+
+        for &(fmt, devs) in rows.iter() {
+            out.push_str(&format!("{:w$}", fmt.row_label(), w = label_width));
+            for (dev, &supported) in self.devices.iter().zip(devs.iter()) {
+                let mark = if supported {
+                    "x"
+                } else if dev.failed_query(fmt) {
+                    "?"
+                } else {
+                    "-"
+                };
+                out.push_str(&format!(" {:>4}", mark));
+            }
+            out.push('\n');
+        }
+
+        let portable = support.values().filter(|devs| devs.iter().all(|&s| s)).count();
+        out.push_str(&format!("\n{} of {} combinations are supported by all {} devices.\n",
+            portable, support.len(), self.devices.len()));
+        if self.devices.iter().any(|dev| dev.error.is_some() || !dev.errors.is_empty()) {
+            out.push_str("('?': the device failed to list formats for that image type and \
+                access.)\n");
+        }
+        out
+    }
+}
+
+
+fn query_key(image_type: &str, access: &str) -> String {
+    format!("{} {}", image_type, access)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -0,0 +1,206 @@
+//! Uploading `HostImage`s into, and downloading them from, `Image<T>`.
+
+use ocl::{Context, Image, Queue, OclPrm};
+use ocl::core::ImageFormat;
+use ocl::enums::{ImageChannelOrder, ImageChannelDataType, MemObjectType};
+use ocl::error::Result as OclResult;
+use ocl::flags::MemFlags;
+use crate::HostImage;
+
+
+/// A texel component type and the channel data types stored in it.
+pub trait Texel: OclPrm {
+    /// Matching channel data types, most preferred first.
+    const DATA_TYPES: &'static [ImageChannelDataType];
+    /// The host sample range a downloaded image defaults to.
+    const MAX_VAL: u16;
+
+    /// Converts from a normalized (`0.0..=1.0`) value.
+    fn from_unit(v: f32) -> Self;
+    /// Converts to a normalized value.
+    fn to_unit(self) -> f32;
+}
+
+impl Texel for u8 {
+    const DATA_TYPES: &'static [ImageChannelDataType] = &[ImageChannelDataType::UnormInt8,
+        ImageChannelDataType::UnsignedInt8];
+    const MAX_VAL: u16 = 255;
+
+    fn from_unit(v: f32) -> u8 { (v.max(0.).min(1.) * 255.).round() as u8 }
+    fn to_unit(self) -> f32 { self as f32 / 255. }
+}
+
+impl Texel for u16 {
+    const DATA_TYPES: &'static [ImageChannelDataType] = &[ImageChannelDataType::UnormInt16,
+        ImageChannelDataType::UnsignedInt16];
+    const MAX_VAL: u16 = 65535;
+
+    fn from_unit(v: f32) -> u16 { (v.max(0.).min(1.) * 65535.).round() as u16 }
+    fn to_unit(self) -> f32 { self as f32 / 65535. }
+}
+
+impl Texel for f32 {
+    const DATA_TYPES: &'static [ImageChannelDataType] = &[ImageChannelDataType::Float];
+    const MAX_VAL: u16 = 65535;
+
+    fn from_unit(v: f32) -> f32 { v }
+    fn to_unit(self) -> f32 { self }
+}
+
+
+/// Channel orders able to hold `channels` host channels, most preferred
+/// first. Three channel data is padded to four since `Rgb` is only valid
+/// with packed data types.
+pub fn channel_orders(channels: usize) -> &'static [ImageChannelOrder] {
+    match channels {
+        1 => &[ImageChannelOrder::R, ImageChannelOrder::Luminance, ImageChannelOrder::Intensity,
+            ImageChannelOrder::Rgba, ImageChannelOrder::Bgra],
+        2 => &[ImageChannelOrder::Ra, ImageChannelOrder::Rgba, ImageChannelOrder::Bgra],
+        3 => &[ImageChannelOrder::Rgba, ImageChannelOrder::Bgra],
+        _ => &[ImageChannelOrder::Rgba, ImageChannelOrder::Bgra, ImageChannelOrder::Argb],
+    }
+}
+
+/// The RGBA component (0 - 3) stored in each channel of `order`, or `None`
+/// for orders this module does not handle.
+pub fn order_channels(order: ImageChannelOrder) -> Option<&'static [usize]> {
+    match order {
+        ImageChannelOrder::R | ImageChannelOrder::Luminance |
+            ImageChannelOrder::Intensity => Some(&[0]),
+        ImageChannelOrder::A => Some(&[3]),
+        ImageChannelOrder::Rg => Some(&[0, 1]),
+        ImageChannelOrder::Ra => Some(&[0, 3]),
+        ImageChannelOrder::Rgba => Some(&[0, 1, 2, 3]),
+        ImageChannelOrder::Bgra => Some(&[2, 1, 0, 3]),
+        ImageChannelOrder::Argb => Some(&[3, 0, 1, 2]),
+        _ => None,
+    }
+}
+
+/// Returns the preferred 2D image format supported by `context` for
+/// `channels` host channels stored as `T`.
+pub fn choose_format<T: Texel>(context: &Context, flags: MemFlags, channels: usize)
+        -> OclResult<ImageFormat>
+{
+    let supported: Vec<ImageFormat> = Image::<T>::supported_formats(context, flags,
+            MemObjectType::Image2d)?
+        .into_iter()
+        .filter_map(|format| format.ok())
+        .collect();
+
+    for &order in channel_orders(channels) {
+        for &data_type in T::DATA_TYPES {
+            if supported.iter().any(|f| f.channel_order == order && f.channel_data_type == data_type) {
+                return Ok(ImageFormat::new(order, data_type));
+            }
+        }
+    }
+
+    Err(format!("No supported image format can hold {} channel(s) as {:?} (supported: {:?}).",
+        channels, T::DATA_TYPES, supported).into())
+}
+
+
+/// A 2D device image along with the format it was created with and the
+/// shape of the host image it holds.
+pub struct DeviceImage<T: Texel> {
+    image: Image<T>,
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+    host_channels: usize,
+    host_max_val: u16,
+}
+
+impl<T: Texel> DeviceImage<T> {
+    /// Creates an image holding `host`, converting its samples to the
+    /// chosen format.
+    pub fn upload(host: &HostImage, queue: &Queue, flags: MemFlags) -> OclResult<DeviceImage<T>> {
+        let format = choose_format::<T>(&queue.context(), flags, host.channels())?;
+        let components = order_channels(format.channel_order).unwrap();
+
+        let mut texels = Vec::with_capacity(host.len() * components.len());
+        for idx in 0..host.len() {
+            let rgba = host.rgba(idx);
+            texels.extend(components.iter().map(|&c| T::from_unit(rgba[c])));
+        }
+
+        DeviceImage::build(format, host.width(), host.height(), host.channels(), host.max_val(),
+            queue, flags, Some(&texels))
+    }
+
+    /// Creates an uninitialized image (e.g. for kernel output) able to hold
+    /// a `channels` channel host image.
+    pub fn new(width: usize, height: usize, channels: usize, queue: &Queue, flags: MemFlags)
+            -> OclResult<DeviceImage<T>>
+    {
+        let format = choose_format::<T>(&queue.context(), flags, channels)?;
+        DeviceImage::build(format, width, height, channels, T::MAX_VAL, queue, flags, None)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build(format: ImageFormat, width: usize, height: usize, host_channels: usize,
+            host_max_val: u16, queue: &Queue, flags: MemFlags, texels: Option<&[T]>)
+            -> OclResult<DeviceImage<T>>
+    {
+        let mut builder = Image::<T>::builder();
+        builder.channel_order(format.channel_order)
+            .channel_data_type(format.channel_data_type)
+            .image_type(MemObjectType::Image2d)
+            .dims((width, height))
+            .flags(flags)
+            .queue(queue.clone());
+        if let Some(texels) = texels {
+            builder.copy_host_slice(texels);
+        }
+
+        Ok(DeviceImage {
+            image: builder.build()?,
+            format: format,
+            width: width,
+            height: height,
+            host_channels: host_channels,
+            host_max_val: host_max_val,
+        })
+    }
+
+    /// Reads the image back into a host image with the original channel
+    /// count and sample range.
+    pub fn download(&self) -> OclResult<HostImage> {
+        let components = order_channels(self.format.channel_order).unwrap();
+        let mut texels = vec![T::default(); self.width * self.height * components.len()];
+        self.image.read(&mut texels).enq()?;
+
+        let pixels: Vec<[f32; 4]> = texels.chunks(components.len()).map(|texel| {
+            let mut rgba = [0., 0., 0., 1.];
+            for (&c, &v) in components.iter().zip(texel.iter()) {
+                rgba[c] = v.to_unit();
+            }
+            // Single channel orders hold gray:
+            if !components.contains(&1) {
+                rgba[1] = rgba[0];
+                rgba[2] = rgba[0];
+            }
+            rgba
+        }).collect();
+
+        HostImage::from_rgba(self.width, self.height, self.host_channels, self.host_max_val,
+            &pixels)
+    }
+
+    pub fn image(&self) -> &Image<T> {
+        &self.image
+    }
+
+    pub fn format(&self) -> &ImageFormat {
+        &self.format
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
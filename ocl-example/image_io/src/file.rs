@@ -0,0 +1,217 @@
+//! PNG, binary PNM and raw file reading and writing.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use ocl::error::{Error as OclError, Result as OclResult};
+use crate::HostImage;
+
+
+/// The layout of a headerless raw file: `width * height * channels`
+/// samples, one byte each when `max_val` is at most 255, otherwise two
+/// (little endian).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RawSpec {
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+    pub max_val: u16,
+}
+
+impl RawSpec {
+    fn sample_size(&self) -> usize {
+        if self.max_val > 255 { 2 } else { 1 }
+    }
+}
+
+
+pub(crate) fn extension(path: &Path) -> String {
+    path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase()
+}
+
+fn io_err(path: &Path, err: impl ToString) -> OclError {
+    format!("'{}': {}", path.display(), err.to_string()).into()
+}
+
+
+pub(crate) fn load_png(path: &Path) -> OclResult<HostImage> {
+    let file = File::open(path).map_err(|err| io_err(path, err))?;
+    let mut decoder = png::Decoder::new(file);
+    // Expand palettes and sub-byte depths to plain 8 bit samples:
+    decoder.set_transformations(png::Transformations::EXPAND);
+
+    let (info, mut reader) = decoder.read_info().map_err(|err| io_err(path, err))?;
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).map_err(|err| io_err(path, err))?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB => 3,
+        png::ColorType::RGBA => 4,
+        png::ColorType::Indexed => return Err(io_err(path, "Unexpanded indexed color.")),
+    };
+    let (width, height) = (info.width as usize, info.height as usize);
+    let row_len = width * channels;
+
+    let (max_val, samples) = match info.bit_depth {
+        png::BitDepth::Sixteen => (u16::max_value(), buf.chunks(info.line_size)
+            .flat_map(|row| row[..row_len * 2].chunks(2).map(|s| u16::from_be_bytes([s[0], s[1]])))
+            .collect()),
+        _ => (255, buf.chunks(info.line_size)
+            .flat_map(|row| row[..row_len].iter().map(|&s| s as u16))
+            .collect()),
+    };
+
+    HostImage::new(width, height, channels, max_val, samples)
+}
+
+pub(crate) fn save_png(image: &HostImage, path: &Path) -> OclResult<()> {
+    let file = File::create(path).map_err(|err| io_err(path, err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width() as u32,
+        image.height() as u32);
+
+    encoder.set_color(match image.channels() {
+        1 => png::ColorType::Grayscale,
+        2 => png::ColorType::GrayscaleAlpha,
+        3 => png::ColorType::RGB,
+        _ => png::ColorType::RGBA,
+    });
+
+    // PNG samples are always full range, so rescale other maximums:
+    let (depth, max_out) = if image.max_val() > 255 {
+        (png::BitDepth::Sixteen, u16::max_value())
+    } else {
+        (png::BitDepth::Eight, 255)
+    };
+    encoder.set_depth(depth);
+
+    let scale = |s: u16| ((s as u32 * max_out as u32 + image.max_val() as u32 / 2)
+        / image.max_val() as u32) as u16;
+    let data: Vec<u8> = match depth {
+        png::BitDepth::Sixteen => image.samples().iter()
+            .flat_map(|&s| scale(s).to_be_bytes().to_vec())
+            .collect(),
+        _ => image.samples().iter().map(|&s| scale(s) as u8).collect(),
+    };
+
+    let mut writer = encoder.write_header().map_err(|err| io_err(path, err))?;
+    writer.write_image_data(&data).map_err(|err| io_err(path, err))
+}
+
+
+/// Reads binary PGM (`P5`) and PPM (`P6`) files.
+pub(crate) fn load_pnm(path: &Path) -> OclResult<HostImage> {
+    let bytes = fs::read(path).map_err(|err| io_err(path, err))?;
+
+    // Header: magic, width, height, maxval, separated by whitespace and
+    // `#` comments, followed by exactly one whitespace byte.
+    let mut pos = 0;
+    let mut fields = Vec::with_capacity(4);
+    while fields.len() < 4 {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'#') {
+            if bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' { pos += 1; }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() { pos += 1; }
+        if start == pos { return Err(io_err(path, "Truncated PNM header.")); }
+        fields.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+    }
+    pos += 1;
+
+    let channels = match fields[0].as_str() {
+        "P5" => 1,
+        "P6" => 3,
+        magic => return Err(io_err(path, format!("Unsupported PNM type '{}' (only binary \
+            P5 and P6 are supported).", magic))),
+    };
+    let parse = |field: &str| field.parse::<usize>()
+        .map_err(|_| io_err(path, format!("Invalid PNM header field '{}'.", field)));
+    let (width, height, max_val) = (parse(&fields[1])?, parse(&fields[2])?, parse(&fields[3])?);
+    if max_val == 0 || max_val > u16::max_value() as usize {
+        return Err(io_err(path, format!("Invalid PNM maxval {}.", max_val)));
+    }
+
+    let spec = RawSpec { width, height, channels, max_val: max_val as u16 };
+    let data = bytes.get(pos..).unwrap_or(&[]);
+    // PNM 16 bit samples are big endian:
+    decode_samples(data, spec, true).map_err(|err| io_err(path, err))
+}
+
+/// Writes binary PGM (1 or 2 channels) or PPM (3 or 4 channels), dropping
+/// alpha.
+pub(crate) fn save_pnm(image: &HostImage, path: &Path) -> OclResult<()> {
+    let (magic, keep) = match image.channels() {
+        1 | 2 => ("P5", 1),
+        _ => ("P6", 3),
+    };
+
+    let write = || -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{}\n{} {}\n{}\n", magic, image.width(), image.height(), image.max_val())?;
+        for px in image.samples().chunks(image.channels()) {
+            for &s in px[..keep].iter() {
+                if image.max_val() > 255 {
+                    out.write_all(&s.to_be_bytes())?;
+                } else {
+                    out.write_all(&[s as u8])?;
+                }
+            }
+        }
+        out.flush()
+    };
+
+    write().map_err(|err| io_err(path, err))
+}
+
+
+pub(crate) fn load_raw(path: &Path, spec: RawSpec) -> OclResult<HostImage> {
+    let bytes = fs::read(path).map_err(|err| io_err(path, err))?;
+    decode_samples(&bytes, spec, false).map_err(|err| io_err(path, err))
+}
+
+pub(crate) fn save_raw(image: &HostImage, path: &Path) -> OclResult<RawSpec> {
+    let spec = RawSpec {
+        width: image.width(),
+        height: image.height(),
+        channels: image.channels(),
+        max_val: image.max_val(),
+    };
+    let bytes: Vec<u8> = if spec.sample_size() == 2 {
+        image.samples().iter().flat_map(|&s| s.to_le_bytes().to_vec()).collect()
+    } else {
+        image.samples().iter().map(|&s| s as u8).collect()
+    };
+
+    fs::write(path, bytes).map_err(|err| io_err(path, err))?;
+    Ok(spec)
+}
+
+
+fn decode_samples(data: &[u8], spec: RawSpec, big_endian: bool) -> OclResult<HostImage> {
+    // The dimensions come from an untrusted header (or the command line):
+    let len = spec.width.checked_mul(spec.height)
+        .and_then(|pixels| pixels.checked_mul(spec.channels))
+        .and_then(|samples| samples.checked_mul(spec.sample_size()))
+        .ok_or_else(|| format!("Image dimensions {}x{}x{} are too large.", spec.width,
+            spec.height, spec.channels))?;
+    if data.len() < len {
+        return Err(format!("Expected {} bytes of samples, found {}.", len, data.len()).into());
+    }
+
+    let samples = if spec.sample_size() == 2 {
+        data[..len].chunks(2).map(|s| if big_endian {
+            u16::from_be_bytes([s[0], s[1]])
+        } else {
+            u16::from_le_bytes([s[0], s[1]])
+        }).collect()
+    } else {
+        data[..len].iter().map(|&s| s as u16).collect()
+    };
+
+    HostImage::new(spec.width, spec.height, spec.channels, spec.max_val, samples)
+}
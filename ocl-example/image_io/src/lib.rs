@@ -0,0 +1,163 @@
+//! Moving real pixels between files and OpenCL images.
+//!
+//! A `HostImage` is loaded from a PNG, binary PPM/PGM or headerless raw
+//! file, uploaded into an `Image<T>` with `DeviceImage::upload`, and read
+//! back and saved the same way:
+//!
+//! ```ignore
+//! let host = HostImage::load("lena.png")?;
+//! let image = DeviceImage::<u8>::upload(&host, &queue, MemFlags::new().read_write())?;
+//! // ... run kernels on `image.image()` ...
+//! image.download()?.save("out.png")?;
+//! ```
+//!
+//! The device format is picked from `Image::supported_formats`: the first
+//! supported channel order able to hold the file's channels (see
+//! `channel_orders`) combined with the first supported channel data type
+//! matching the texel type `T` (see `Texel`). Channels are reordered,
+//! padded and rescaled on the host as needed.
+//!
+
+extern crate ocl;
+extern crate png;
+
+mod device;
+mod file;
+
+use std::path::Path;
+use ocl::error::Result as OclResult;
+
+pub use crate::device::{Texel, DeviceImage, channel_orders, choose_format, order_channels};
+pub use crate::file::RawSpec;
+
+
+/// An image in host memory: `channels` samples per pixel, row-major, each
+/// in `0..=max_val`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostImage {
+    width: usize,
+    height: usize,
+    channels: usize,
+    max_val: u16,
+    samples: Vec<u16>,
+}
+
+impl HostImage {
+    /// Returns a new image. `channels` is 1 (gray), 2 (gray, alpha),
+    /// 3 (RGB) or 4 (RGBA).
+    pub fn new(width: usize, height: usize, channels: usize, max_val: u16, samples: Vec<u16>)
+            -> OclResult<HostImage>
+    {
+        if channels < 1 || channels > 4 {
+            return Err(format!("HostImage: Unsupported channel count: {}.", channels).into());
+        }
+        if samples.len() != width * height * channels {
+            return Err(format!("HostImage: Expected {} samples ({}x{}x{}), found {}.",
+                width * height * channels, width, height, channels, samples.len()).into());
+        }
+        if max_val == 0 {
+            return Err("HostImage: The maximum sample value must be non-zero.".into());
+        }
+
+        Ok(HostImage { width, height, channels, max_val, samples })
+    }
+
+    /// Builds an image from normalized RGBA pixels, keeping `channels` of
+    /// them (see `rgba`).
+    pub fn from_rgba(width: usize, height: usize, channels: usize, max_val: u16,
+            pixels: &[[f32; 4]]) -> OclResult<HostImage>
+    {
+        let to_sample = |v: f32| (v.max(0.).min(1.) * max_val as f32).round() as u16;
+        let mut samples = Vec::with_capacity(pixels.len() * channels);
+
+        for px in pixels {
+            let gray = || (px[0] + px[1] + px[2]) / 3.;
+            match channels {
+                1 => samples.push(to_sample(gray())),
+                2 => samples.extend_from_slice(&[to_sample(gray()), to_sample(px[3])]),
+                3 => samples.extend(px[..3].iter().map(|&v| to_sample(v))),
+                _ => samples.extend(px.iter().map(|&v| to_sample(v))),
+            }
+        }
+
+        HostImage::new(width, height, channels, max_val, samples)
+    }
+
+    /// Loads a `.png`, `.ppm`, `.pgm` or `.pnm` file (by extension). Use
+    /// `load_raw` for raw files.
+    pub fn load<P: AsRef<Path>>(path: P) -> OclResult<HostImage> {
+        let path = path.as_ref();
+        match file::extension(path).as_str() {
+            "png" => file::load_png(path),
+            "ppm" | "pgm" | "pnm" => file::load_pnm(path),
+            ext => Err(format!("HostImage: Unsupported file type '{}' ('{}'); raw files \
+                need `HostImage::load_raw`.", ext, path.display()).into()),
+        }
+    }
+
+    /// Loads a headerless raw file.
+    pub fn load_raw<P: AsRef<Path>>(path: P, spec: RawSpec) -> OclResult<HostImage> {
+        file::load_raw(path.as_ref(), spec)
+    }
+
+    /// Saves as `.png`, `.ppm`, `.pgm` or `.pnm` (by extension). PPM/PGM
+    /// files cannot hold alpha, which is dropped.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> OclResult<()> {
+        let path = path.as_ref();
+        match file::extension(path).as_str() {
+            "png" => file::save_png(self, path),
+            "ppm" | "pgm" | "pnm" => file::save_pnm(self, path),
+            ext => Err(format!("HostImage: Unsupported file type '{}' ('{}'); raw files \
+                need `HostImage::save_raw`.", ext, path.display()).into()),
+        }
+    }
+
+    /// Saves the samples as a headerless raw file (8 bit if `max_val` fits,
+    /// otherwise 16 bit little endian), returning the spec to load it with.
+    pub fn save_raw<P: AsRef<Path>>(&self, path: P) -> OclResult<RawSpec> {
+        file::save_raw(self, path.as_ref())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn max_val(&self) -> u16 {
+        self.max_val
+    }
+
+    pub fn samples(&self) -> &[u16] {
+        &self.samples
+    }
+
+    /// The number of pixels.
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns pixel `idx` as normalized RGBA. Gray is replicated across
+    /// red, green and blue and missing alpha is opaque.
+    pub fn rgba(&self, idx: usize) -> [f32; 4] {
+        let px = &self.samples[idx * self.channels..(idx + 1) * self.channels];
+        let unit = |s: u16| s as f32 / self.max_val as f32;
+
+        match self.channels {
+            1 => [unit(px[0]), unit(px[0]), unit(px[0]), 1.],
+            2 => [unit(px[0]), unit(px[0]), unit(px[0]), unit(px[1])],
+            3 => [unit(px[0]), unit(px[1]), unit(px[2]), 1.],
+            _ => [unit(px[0]), unit(px[1]), unit(px[2]), unit(px[3])],
+        }
+    }
+}
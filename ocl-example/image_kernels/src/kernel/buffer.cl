@@ -0,0 +1,68 @@
+// Buffer variants for devices without image support. Frames are row-major
+// `float4` RGBA pixels; edges are clamped and bilinear filtering is done by
+// hand, matching the image variants' samplers.
+
+__kernel void convolve_h(__global float4 const* src, __global float4* dst, int width,
+        int height, __constant float* weights, int radius)
+{
+    int const x = get_global_id(0);
+    int const y = get_global_id(1);
+    float4 sum = (float4)(0.0f);
+    for (int k = -radius; k <= radius; k++) {
+        sum += weights[k + radius] * src[y * width + clamp(x + k, 0, width - 1)];
+    }
+    dst[y * width + x] = sum;
+}
+
+__kernel void convolve_v(__global float4 const* src, __global float4* dst, int width,
+        int height, __constant float* weights, int radius)
+{
+    int const x = get_global_id(0);
+    int const y = get_global_id(1);
+    float4 sum = (float4)(0.0f);
+    for (int k = -radius; k <= radius; k++) {
+        sum += weights[k + radius] * src[clamp(y + k, 0, height - 1) * width + x];
+    }
+    dst[y * width + x] = sum;
+}
+
+__kernel void resize_bilinear(__global float4 const* src, int src_width, int src_height,
+        __global float4* dst, int dst_width, int dst_height)
+{
+    int const x = get_global_id(0);
+    int const y = get_global_id(1);
+    float const u = (x + 0.5f) * src_width / dst_width - 0.5f;
+    float const v = (y + 0.5f) * src_height / dst_height - 0.5f;
+    int const x0 = (int)floor(u);
+    int const y0 = (int)floor(v);
+    float const a = u - x0;
+    float const b = v - y0;
+    int const xa = clamp(x0, 0, src_width - 1);
+    int const xb = clamp(x0 + 1, 0, src_width - 1);
+    int const ya = clamp(y0, 0, src_height - 1);
+    int const yb = clamp(y0 + 1, 0, src_height - 1);
+
+    float4 const top = mix(src[ya * src_width + xa], src[ya * src_width + xb], a);
+    float4 const bottom = mix(src[yb * src_width + xa], src[yb * src_width + xb], a);
+    dst[y * dst_width + x] = mix(top, bottom, b);
+}
+
+__kernel void rgb_to_gray(__global float4 const* src, __global float4* dst) {
+    uint const idx = get_global_id(0);
+    dst[idx] = to_gray(src[idx]);
+}
+
+__kernel void rgb_to_yuv(__global float4 const* src, __global float4* dst) {
+    uint const idx = get_global_id(0);
+    dst[idx] = to_yuv(src[idx]);
+}
+
+__kernel void yuv_to_rgb(__global float4 const* src, __global float4* dst) {
+    uint const idx = get_global_id(0);
+    dst[idx] = to_rgb(src[idx]);
+}
+
+__kernel void histogram(__global float4 const* src, __global uint* hist, int bins) {
+    uint const idx = get_global_id(0);
+    atomic_inc(&hist[hist_bin(src[idx], bins)]);
+}
@@ -0,0 +1,26 @@
+// Color conversions shared by the image and buffer kernels (BT.601, full
+// range). YUV is stored with U and V offset by 0.5 so that it fits
+// normalized formats.
+
+float4 to_gray(float4 c) {
+    float const y = dot(c.xyz, (float3)(0.299f, 0.587f, 0.114f));
+    return (float4)(y, y, y, c.w);
+}
+
+float4 to_yuv(float4 c) {
+    float const y = dot(c.xyz, (float3)(0.299f, 0.587f, 0.114f));
+    float const u = dot(c.xyz, (float3)(-0.14713f, -0.28886f, 0.436f));
+    float const v = dot(c.xyz, (float3)(0.615f, -0.51499f, -0.10001f));
+    return (float4)(y, u + 0.5f, v + 0.5f, c.w);
+}
+
+float4 to_rgb(float4 c) {
+    float const y = c.x;
+    float const u = c.y - 0.5f;
+    float const v = c.z - 0.5f;
+    return (float4)(y + 1.13983f * v, y - 0.39465f * u - 0.58060f * v, y + 2.03211f * u, c.w);
+}
+
+int hist_bin(float4 c, int bins) {
+    return clamp((int)(to_gray(c).x * bins), 0, bins - 1);
+}
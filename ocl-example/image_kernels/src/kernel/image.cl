@@ -0,0 +1,51 @@
+// Image variants. Convolution, color and histogram kernels take an
+// unnormalized, clamp-to-edge, nearest sampler; `resize_bilinear` takes a
+// normalized, clamp-to-edge, linear one.
+
+__kernel void convolve_h(read_only image2d_t src, write_only image2d_t dst, sampler_t smp,
+        __constant float* weights, int radius)
+{
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    float4 sum = (float4)(0.0f);
+    for (int k = -radius; k <= radius; k++) {
+        sum += weights[k + radius] * read_imagef(src, smp, pos + (int2)(k, 0));
+    }
+    write_imagef(dst, pos, sum);
+}
+
+__kernel void convolve_v(read_only image2d_t src, write_only image2d_t dst, sampler_t smp,
+        __constant float* weights, int radius)
+{
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    float4 sum = (float4)(0.0f);
+    for (int k = -radius; k <= radius; k++) {
+        sum += weights[k + radius] * read_imagef(src, smp, pos + (int2)(0, k));
+    }
+    write_imagef(dst, pos, sum);
+}
+
+__kernel void resize_bilinear(read_only image2d_t src, write_only image2d_t dst, sampler_t smp) {
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    float2 const coord = (convert_float2(pos) + 0.5f) / convert_float2(get_image_dim(dst));
+    write_imagef(dst, pos, read_imagef(src, smp, coord));
+}
+
+__kernel void rgb_to_gray(read_only image2d_t src, write_only image2d_t dst, sampler_t smp) {
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    write_imagef(dst, pos, to_gray(read_imagef(src, smp, pos)));
+}
+
+__kernel void rgb_to_yuv(read_only image2d_t src, write_only image2d_t dst, sampler_t smp) {
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    write_imagef(dst, pos, to_yuv(read_imagef(src, smp, pos)));
+}
+
+__kernel void yuv_to_rgb(read_only image2d_t src, write_only image2d_t dst, sampler_t smp) {
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    write_imagef(dst, pos, to_rgb(read_imagef(src, smp, pos)));
+}
+
+__kernel void histogram(read_only image2d_t src, sampler_t smp, __global uint* hist, int bins) {
+    int2 const pos = (int2)(get_global_id(0), get_global_id(1));
+    atomic_inc(&hist[hist_bin(read_imagef(src, smp, pos), bins)]);
+}
@@ -0,0 +1,380 @@
+//! Common image processing kernels.
+//!
+//! `ImageKernels` runs separable (Gaussian or box) convolution, bilinear
+//! resizing, RGB to gray/YUV (and back) conversion and luma histograms on
+//! `DeviceFrame`s, which are RGBA float `Image`s read through samplers or,
+//! on devices reporting no `ImageSupport`, plain `float4` buffers with the
+//! same edge handling. `reference` holds matching CPU implementations for
+//! verifying results.
+//!
+//! ```ignore
+//! let kernels = ImageKernels::new(&queue)?;
+//! let src = kernels.upload(&Frame::from_host(&HostImage::load("in.png")?))?;
+//! let blurred = kernels.gaussian(&src, 3, 1.5)?;
+//! let out = kernels.download(&blurred)?;
+//! ```
+//!
+
+extern crate ocl;
+extern crate image_io;
+
+pub mod reference;
+
+use ocl::{Buffer, Image, Kernel, Program, Queue, Sampler, SpatialDims};
+use ocl::enums::{AddressingMode, FilterMode, ImageChannelOrder, ImageChannelDataType,
+    MemObjectType, DeviceInfo, DeviceInfoResult};
+use ocl::error::Result as OclResult;
+use ocl::flags::MemFlags;
+use ocl::prm::Float4;
+use image_io::HostImage;
+
+static COLOR_SRC: &str = include_str!("kernel/color.cl");
+static IMAGE_SRC: &str = include_str!("kernel/image.cl");
+static BUFFER_SRC: &str = include_str!("kernel/buffer.cl");
+
+
+/// An RGBA float image in host memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Frame {
+    /// A black, transparent frame.
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame { width, height, pixels: vec![[0.; 4]; width * height] }
+    }
+
+    /// Converts a host image to normalized RGBA.
+    pub fn from_host(host: &HostImage) -> Frame {
+        Frame {
+            width: host.width(),
+            height: host.height(),
+            pixels: (0..host.len()).map(|idx| host.rgba(idx)).collect(),
+        }
+    }
+
+    /// Converts to a host image with `channels` channels in `0..=max_val`.
+    pub fn to_host(&self, channels: usize, max_val: u16) -> OclResult<HostImage> {
+        HostImage::from_rgba(self.width, self.height, channels, max_val, &self.pixels)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [f32; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Every component of every pixel, in order.
+    pub fn lanes(&self) -> Vec<f32> {
+        self.pixels.iter().flat_map(|px| px.iter().cloned()).collect()
+    }
+}
+
+
+/// Whether kernels run on images or buffers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Image,
+    Buffer,
+}
+
+enum FrameMem {
+    Image(Image<f32>),
+    Buffer(Buffer<Float4>),
+}
+
+/// A frame in device memory.
+pub struct DeviceFrame {
+    width: usize,
+    height: usize,
+    mem: FrameMem,
+}
+
+impl DeviceFrame {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The backing image, if on the image backend.
+    pub fn image(&self) -> Option<&Image<f32>> {
+        match self.mem {
+            FrameMem::Image(ref image) => Some(image),
+            FrameMem::Buffer(_) => None,
+        }
+    }
+
+    /// The backing buffer, if on the buffer backend.
+    pub fn buffer(&self) -> Option<&Buffer<Float4>> {
+        match self.mem {
+            FrameMem::Image(_) => None,
+            FrameMem::Buffer(ref buffer) => Some(buffer),
+        }
+    }
+
+    fn dims(&self) -> SpatialDims {
+        SpatialDims::Two(self.width, self.height)
+    }
+}
+
+
+/// A kernel argument.
+enum KernArg<'a> {
+    Frame(&'a DeviceFrame),
+    Sampler(&'a Sampler),
+    Weights(&'a Buffer<f32>),
+    Hist(&'a Buffer<u32>),
+    Int(i32),
+}
+
+
+/// Image processing kernels built for one queue's device.
+pub struct ImageKernels {
+    queue: Queue,
+    program: Program,
+    backend: Backend,
+    // Unnormalized, nearest (convolution, color, histogram) and normalized,
+    // linear (resizing) samplers, image backend only:
+    samplers: Option<(Sampler, Sampler)>,
+}
+
+impl ImageKernels {
+    /// Builds the kernels, using images if the device supports them.
+    pub fn new(queue: &Queue) -> OclResult<ImageKernels> {
+        let backend = match queue.device().info(DeviceInfo::ImageSupport)? {
+            DeviceInfoResult::ImageSupport(true) => Backend::Image,
+            _ => Backend::Buffer,
+        };
+        ImageKernels::with_backend(queue, backend)
+    }
+
+    /// Builds the kernels for a specific backend.
+    pub fn with_backend(queue: &Queue, backend: Backend) -> OclResult<ImageKernels> {
+        let context = queue.context();
+        let program = Program::builder()
+            .devices(queue.device())
+            .src(COLOR_SRC)
+            .src(match backend { Backend::Image => IMAGE_SRC, Backend::Buffer => BUFFER_SRC })
+            .build(&context)?;
+
+        let samplers = match backend {
+            Backend::Image => Some((
+                Sampler::new(&context, false, AddressingMode::ClampToEdge, FilterMode::Nearest)?,
+                Sampler::new(&context, true, AddressingMode::ClampToEdge, FilterMode::Linear)?,
+            )),
+            Backend::Buffer => None,
+        };
+
+        Ok(ImageKernels { queue: queue.clone(), program, backend, samplers })
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Allocates an uninitialized frame.
+    pub fn alloc(&self, width: usize, height: usize) -> OclResult<DeviceFrame> {
+        self.create(width, height, None)
+    }
+
+    /// Copies `frame` to the device.
+    pub fn upload(&self, frame: &Frame) -> OclResult<DeviceFrame> {
+        self.create(frame.width, frame.height, Some(frame))
+    }
+
+    fn create(&self, width: usize, height: usize, frame: Option<&Frame>) -> OclResult<DeviceFrame> {
+        let mem = match self.backend {
+            Backend::Image => {
+                let mut builder = Image::<f32>::builder();
+                builder.channel_order(ImageChannelOrder::Rgba)
+                    .channel_data_type(ImageChannelDataType::Float)
+                    .image_type(MemObjectType::Image2d)
+                    .dims((width, height))
+                    .flags(MemFlags::new().read_write())
+                    .queue(self.queue.clone());
+                let texels = frame.map(|frame| frame.lanes());
+                if let Some(ref texels) = texels {
+                    builder.copy_host_slice(texels);
+                }
+                FrameMem::Image(builder.build()?)
+            },
+            Backend::Buffer => {
+                let builder = Buffer::<Float4>::builder()
+                    .queue(self.queue.clone())
+                    .flags(MemFlags::new().read_write())
+                    .len(width * height);
+                let buffer = match frame {
+                    Some(frame) => {
+                        let pixels: Vec<_> = frame.pixels.iter()
+                            .map(|px| Float4::new(px[0], px[1], px[2], px[3]))
+                            .collect();
+                        builder.copy_host_slice(&pixels).build()?
+                    },
+                    None => builder.build()?,
+                };
+                FrameMem::Buffer(buffer)
+            },
+        };
+
+        Ok(DeviceFrame { width, height, mem })
+    }
+
+    /// Reads a frame back to the host.
+    pub fn download(&self, frame: &DeviceFrame) -> OclResult<Frame> {
+        let pixels = match frame.mem {
+            FrameMem::Image(ref image) => {
+                let mut texels = vec![0f32; frame.width * frame.height * 4];
+                image.read(&mut texels).queue(&self.queue).enq()?;
+                texels.chunks(4).map(|t| [t[0], t[1], t[2], t[3]]).collect()
+            },
+            FrameMem::Buffer(ref buffer) => {
+                let mut pixels = vec![Float4::default(); frame.width * frame.height];
+                buffer.read(&mut pixels).queue(&self.queue).enq()?;
+                pixels.iter().map(|p| [p[0], p[1], p[2], p[3]]).collect()
+            },
+        };
+
+        Ok(Frame { width: frame.width, height: frame.height, pixels })
+    }
+
+    fn launch(&self, name: &str, gws: SpatialDims, args: &[KernArg]) -> OclResult<()> {
+        let mut builder = Kernel::builder();
+        builder.program(&self.program)
+            .name(name)
+            .queue(self.queue.clone())
+            .global_work_size(gws);
+
+        for arg in args {
+            match *arg {
+                KernArg::Frame(&DeviceFrame { mem: FrameMem::Image(ref image), .. }) => {
+                    builder.arg(image);
+                },
+                KernArg::Frame(&DeviceFrame { mem: FrameMem::Buffer(ref buffer), .. }) => {
+                    builder.arg(buffer);
+                },
+                KernArg::Sampler(sampler) => { builder.arg_sampler(sampler); },
+                KernArg::Weights(weights) => { builder.arg(weights); },
+                KernArg::Hist(hist) => { builder.arg(hist); },
+                KernArg::Int(val) => { builder.arg(val); },
+            }
+        }
+
+        unsafe { builder.build()?.enq() }
+    }
+
+    fn nearest(&self) -> &Sampler {
+        &self.samplers.as_ref().unwrap().0
+    }
+
+    fn linear(&self) -> &Sampler {
+        &self.samplers.as_ref().unwrap().1
+    }
+
+    /// Separable convolution: `weights` (an odd number of taps) applied
+    /// horizontally, then vertically, with clamped edges.
+    pub fn convolve(&self, src: &DeviceFrame, weights: &[f32]) -> OclResult<DeviceFrame> {
+        if weights.len() % 2 == 0 {
+            return Err("ImageKernels: Convolution weights must have an odd length.".into());
+        }
+
+        let weights = Buffer::<f32>::builder()
+            .queue(self.queue.clone())
+            .flags(MemFlags::new().read_only())
+            .len(weights.len())
+            .copy_host_slice(weights)
+            .build()?;
+        let radius = (weights.len() / 2) as i32;
+        let tmp = self.alloc(src.width, src.height)?;
+        let dst = self.alloc(src.width, src.height)?;
+
+        for &(name, from, to) in [("convolve_h", src, &tmp), ("convolve_v", &tmp, &dst)].iter() {
+            match self.backend {
+                Backend::Image => self.launch(name, src.dims(), &[KernArg::Frame(from),
+                    KernArg::Frame(to), KernArg::Sampler(self.nearest()),
+                    KernArg::Weights(&weights), KernArg::Int(radius)])?,
+                Backend::Buffer => self.launch(name, src.dims(), &[KernArg::Frame(from),
+                    KernArg::Frame(to), KernArg::Int(src.width as i32),
+                    KernArg::Int(src.height as i32), KernArg::Weights(&weights),
+                    KernArg::Int(radius)])?,
+            }
+        }
+        Ok(dst)
+    }
+
+    /// Gaussian blur with `2 * radius + 1` taps.
+    pub fn gaussian(&self, src: &DeviceFrame, radius: usize, sigma: f32) -> OclResult<DeviceFrame> {
+        self.convolve(src, &reference::gaussian_weights(radius, sigma))
+    }
+
+    /// Box blur with `2 * radius + 1` taps.
+    pub fn box_blur(&self, src: &DeviceFrame, radius: usize) -> OclResult<DeviceFrame> {
+        self.convolve(src, &reference::box_weights(radius))
+    }
+
+    /// Bilinear resize to `width` x `height`.
+    pub fn resize(&self, src: &DeviceFrame, width: usize, height: usize) -> OclResult<DeviceFrame> {
+        let dst = self.alloc(width, height)?;
+
+        match self.backend {
+            Backend::Image => self.launch("resize_bilinear", dst.dims(), &[KernArg::Frame(src),
+                KernArg::Frame(&dst), KernArg::Sampler(self.linear())])?,
+            Backend::Buffer => self.launch("resize_bilinear", dst.dims(), &[KernArg::Frame(src),
+                KernArg::Int(src.width as i32), KernArg::Int(src.height as i32),
+                KernArg::Frame(&dst), KernArg::Int(width as i32), KernArg::Int(height as i32)])?,
+        }
+        Ok(dst)
+    }
+
+    fn map(&self, name: &str, src: &DeviceFrame) -> OclResult<DeviceFrame> {
+        let dst = self.alloc(src.width, src.height)?;
+
+        match self.backend {
+            Backend::Image => self.launch(name, src.dims(), &[KernArg::Frame(src),
+                KernArg::Frame(&dst), KernArg::Sampler(self.nearest())])?,
+            Backend::Buffer => self.launch(name, SpatialDims::One(src.width * src.height),
+                &[KernArg::Frame(src), KernArg::Frame(&dst)])?,
+        }
+        Ok(dst)
+    }
+
+    /// Luma (BT.601) replicated to red, green and blue.
+    pub fn rgb_to_gray(&self, src: &DeviceFrame) -> OclResult<DeviceFrame> {
+        self.map("rgb_to_gray", src)
+    }
+
+    /// YUV (BT.601) in the red, green and blue channels, U and V offset by
+    /// 0.5.
+    pub fn rgb_to_yuv(&self, src: &DeviceFrame) -> OclResult<DeviceFrame> {
+        self.map("rgb_to_yuv", src)
+    }
+
+    /// The inverse of `rgb_to_yuv`.
+    pub fn yuv_to_rgb(&self, src: &DeviceFrame) -> OclResult<DeviceFrame> {
+        self.map("yuv_to_rgb", src)
+    }
+
+    /// Luma histogram with `bins` bins.
+    pub fn histogram(&self, src: &DeviceFrame, bins: usize) -> OclResult<Vec<u32>> {
+        let hist = Buffer::<u32>::builder()
+            .queue(self.queue.clone())
+            .len(bins)
+            .fill_val(0u32)
+            .build()?;
+
+        match self.backend {
+            Backend::Image => self.launch("histogram", src.dims(), &[KernArg::Frame(src),
+                KernArg::Sampler(self.nearest()), KernArg::Hist(&hist),
+                KernArg::Int(bins as i32)])?,
+            Backend::Buffer => self.launch("histogram", SpatialDims::One(src.width * src.height),
+                &[KernArg::Frame(src), KernArg::Hist(&hist), KernArg::Int(bins as i32)])?,
+        }
+
+        let mut counts = vec![0u32; bins];
+        hist.read(&mut counts).enq()?;
+        Ok(counts)
+    }
+}
@@ -0,0 +1,155 @@
+//! Runs every kernel in `image_kernels` on an image and checks the results
+//! against the CPU reference implementations.
+//!
+
+#[macro_use] extern crate colorify;
+extern crate ocl;
+extern crate image_io;
+extern crate image_kernels;
+extern crate verify;
+
+use std::env;
+use std::path::PathBuf;
+use ocl::{Result as OclResult, Platform, Device, Context, Queue};
+use image_io::HostImage;
+use image_kernels::{ImageKernels, Backend, Frame, DeviceFrame, reference};
+use verify::{Verifier, Report};
+
+static USAGE: &'static str = "\
+Usage: image_kernels [IMAGE] [OPTIONS]
+
+Runs each kernel on IMAGE (PNG/PPM/PGM, default: a generated test pattern)
+and compares the results with the CPU reference implementations.
+
+Options:
+    --buffer        Use the buffer fallback even if images are supported
+    --out <DIR>     Save each result as a PNG in DIR";
+
+// Linear filtering precision is implementation defined (often 8 bit
+// fractions), so resizing is compared more loosely:
+const TOLERANCE: f64 = 1e-4;
+const RESIZE_TOLERANCE: f64 = 1. / 64.;
+const HIST_BINS: usize = 64;
+
+
+/// A colorful gradient with some hard edges.
+fn test_pattern(width: usize, height: usize) -> Frame {
+    let mut frame = Frame::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let (u, v) = (x as f32 / width as f32, y as f32 / height as f32);
+            let checker = if (x / 16 + y / 16) % 2 == 0 { 0.25 } else { 0. };
+            frame.pixels[y * width + x] = [u, v, (1. - u) * 0.75 + checker, 1.];
+        }
+    }
+    frame
+}
+
+fn check(kernels: &ImageKernels, label: &str, result: &DeviceFrame, expected: &Frame,
+        tolerance: f64, out_dir: &Option<PathBuf>) -> OclResult<Report>
+{
+    let actual = kernels.download(result)?;
+    let report = Verifier::<f32>::new()
+        .label(label)
+        .abs(tolerance)
+        .compare(&actual.lanes(), &expected.lanes());
+
+    if let Some(ref dir) = *out_dir {
+        actual.to_host(4, 255)?.save(dir.join(format!("{}.png", label)))?;
+    }
+
+    if report.is_ok() {
+        printlnc!(lime: "{}", report);
+    } else {
+        printlnc!(red_bold: "{}", report);
+    }
+    Ok(report)
+}
+
+fn image_kernels() -> OclResult<bool> {
+    let mut path = None;
+    let mut force_buffer = false;
+    let mut out_dir = None;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--buffer" => force_buffer = true,
+            "--out" => out_dir = Some(PathBuf::from(args.next().ok_or(USAGE)?)),
+            "--help" | "-h" => { println!("{}", USAGE); return Ok(true); },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let src = match path {
+        Some(ref path) => Frame::from_host(&HostImage::load(path)?),
+        None => test_pattern(256, 192),
+    };
+
+    let platform = Platform::default();
+    let device = Device::first(platform)?;
+    let context = Context::builder().platform(platform).devices(device).build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    let kernels = if force_buffer {
+        ImageKernels::with_backend(&queue, Backend::Buffer)?
+    } else {
+        ImageKernels::new(&queue)?
+    };
+
+    printlnc!(teal: "Device: {} {} ({:?} backend)", device.vendor()?, device.name()?,
+        kernels.backend());
+    printlnc!(white_bold: "Source: {}x{}", src.width, src.height);
+
+    let dev_src = kernels.upload(&src)?;
+    let (half_w, half_h) = ((src.width / 2).max(1), (src.height / 2).max(1));
+    let yuv = reference::rgb_to_yuv(&src);
+
+    let reports = vec![
+        check(&kernels, "gaussian", &kernels.gaussian(&dev_src, 3, 1.5)?,
+            &reference::convolve(&src, &reference::gaussian_weights(3, 1.5)), TOLERANCE, &out_dir)?,
+        check(&kernels, "box", &kernels.box_blur(&dev_src, 2)?,
+            &reference::convolve(&src, &reference::box_weights(2)), TOLERANCE, &out_dir)?,
+        check(&kernels, "resize_half", &kernels.resize(&dev_src, half_w, half_h)?,
+            &reference::resize_bilinear(&src, half_w, half_h), RESIZE_TOLERANCE, &out_dir)?,
+        check(&kernels, "resize_double", &kernels.resize(&dev_src, src.width * 2, src.height * 2)?,
+            &reference::resize_bilinear(&src, src.width * 2, src.height * 2), RESIZE_TOLERANCE,
+            &out_dir)?,
+        check(&kernels, "gray", &kernels.rgb_to_gray(&dev_src)?, &reference::rgb_to_gray(&src),
+            TOLERANCE, &out_dir)?,
+        check(&kernels, "yuv", &kernels.rgb_to_yuv(&dev_src)?, &yuv, TOLERANCE, &out_dir)?,
+        check(&kernels, "yuv_rgb", &kernels.yuv_to_rgb(&kernels.rgb_to_yuv(&dev_src)?)?,
+            &reference::yuv_to_rgb(&yuv), TOLERANCE, &out_dir)?,
+    ];
+
+    // Pixels right on a bin edge may land either side of it:
+    let hist = kernels.histogram(&dev_src, HIST_BINS)?;
+    let hist_expected = reference::histogram(&src, HIST_BINS);
+    let hist_diff: u32 = hist.iter().zip(hist_expected.iter())
+        .map(|(&a, &b)| (a as i64 - b as i64).abs() as u32)
+        .sum();
+    let hist_ok = hist.iter().sum::<u32>() as usize == src.pixels.len()
+        && hist_diff as usize <= src.pixels.len() / 1000;
+
+    if hist_ok {
+        printlnc!(lime: "histogram: {} bins correct ({} pixels moved across bin edges).",
+            HIST_BINS, hist_diff / 2);
+    } else {
+        printlnc!(red_bold: "histogram: mismatch:\n    device:    {:?}\n    reference: {:?}",
+            hist, hist_expected);
+    }
+
+    Ok(hist_ok && reports.iter().all(|report| report.is_ok()))
+}
+
+pub fn main() {
+    match image_kernels() {
+        Ok(true) => (),
+        Ok(false) => {
+            printlnc!(red_bold: "Some results did not match the reference implementations.");
+            std::process::exit(1);
+        },
+        Err(err) => println!("{}", err),
+    }
+}
@@ -0,0 +1,126 @@
+//! CPU reference implementations, following the kernels' (and samplers')
+//! edge handling and rounding so that results can be compared directly.
+
+use crate::Frame;
+
+const LUMA: [f32; 3] = [0.299, 0.587, 0.114];
+
+
+fn dot3(c: &[f32; 4], w: [f32; 3]) -> f32 {
+    c[0] * w[0] + c[1] * w[1] + c[2] * w[2]
+}
+
+fn clamp_idx(idx: isize, len: usize) -> usize {
+    idx.max(0).min(len as isize - 1) as usize
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut out = a;
+    for (o, &b) in out.iter_mut().zip(b.iter()) { *o += (b - *o) * t; }
+    out
+}
+
+
+/// Normalized Gaussian weights for a `2 * radius + 1` tap kernel.
+pub fn gaussian_weights(radius: usize, sigma: f32) -> Vec<f32> {
+    let weights: Vec<f32> = (0..=2 * radius).map(|i| {
+        let x = i as f32 - radius as f32;
+        (-(x * x) / (2. * sigma * sigma)).exp()
+    }).collect();
+    let sum: f32 = weights.iter().sum();
+    weights.iter().map(|w| w / sum).collect()
+}
+
+/// Box filter weights for a `2 * radius + 1` tap kernel.
+pub fn box_weights(radius: usize) -> Vec<f32> {
+    vec![1. / (2 * radius + 1) as f32; 2 * radius + 1]
+}
+
+
+/// One convolution pass, horizontal or vertical, with clamped edges.
+pub fn convolve_pass(src: &Frame, weights: &[f32], horizontal: bool) -> Frame {
+    let radius = (weights.len() / 2) as isize;
+    let mut dst = Frame::new(src.width, src.height);
+
+    for y in 0..src.height {
+        for x in 0..src.width {
+            let mut sum = [0.; 4];
+            for (i, &w) in weights.iter().enumerate() {
+                let k = i as isize - radius;
+                let px = if horizontal {
+                    src.pixel(clamp_idx(x as isize + k, src.width), y)
+                } else {
+                    src.pixel(x, clamp_idx(y as isize + k, src.height))
+                };
+                for (s, &p) in sum.iter_mut().zip(px.iter()) { *s += w * p; }
+            }
+            dst.pixels[y * src.width + x] = sum;
+        }
+    }
+    dst
+}
+
+/// A separable convolution: `weights` applied horizontally, then
+/// vertically.
+pub fn convolve(src: &Frame, weights: &[f32]) -> Frame {
+    convolve_pass(&convolve_pass(src, weights, true), weights, false)
+}
+
+/// Bilinear resize with clamp-to-edge addressing.
+pub fn resize_bilinear(src: &Frame, width: usize, height: usize) -> Frame {
+    let mut dst = Frame::new(width, height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let u = (x as f32 + 0.5) * src.width as f32 / width as f32 - 0.5;
+            let v = (y as f32 + 0.5) * src.height as f32 / height as f32 - 0.5;
+            let (x0, y0) = (u.floor() as isize, v.floor() as isize);
+            let (a, b) = (u - x0 as f32, v - y0 as f32);
+            let (xa, xb) = (clamp_idx(x0, src.width), clamp_idx(x0 + 1, src.width));
+            let (ya, yb) = (clamp_idx(y0, src.height), clamp_idx(y0 + 1, src.height));
+
+            let top = mix(src.pixel(xa, ya), src.pixel(xb, ya), a);
+            let bottom = mix(src.pixel(xa, yb), src.pixel(xb, yb), a);
+            dst.pixels[y * width + x] = mix(top, bottom, b);
+        }
+    }
+    dst
+}
+
+fn map(src: &Frame, f: impl Fn(&[f32; 4]) -> [f32; 4]) -> Frame {
+    Frame { width: src.width, height: src.height, pixels: src.pixels.iter().map(f).collect() }
+}
+
+pub fn rgb_to_gray(src: &Frame) -> Frame {
+    map(src, |c| {
+        let y = dot3(c, LUMA);
+        [y, y, y, c[3]]
+    })
+}
+
+/// RGB to YUV with U and V offset by 0.5.
+pub fn rgb_to_yuv(src: &Frame) -> Frame {
+    map(src, |c| [
+        dot3(c, LUMA),
+        dot3(c, [-0.14713, -0.28886, 0.436]) + 0.5,
+        dot3(c, [0.615, -0.51499, -0.10001]) + 0.5,
+        c[3],
+    ])
+}
+
+pub fn yuv_to_rgb(src: &Frame) -> Frame {
+    map(src, |c| {
+        let (y, u, v) = (c[0], c[1] - 0.5, c[2] - 0.5);
+        [y + 1.13983 * v, y - 0.39465 * u - 0.58060 * v, y + 2.03211 * u, c[3]]
+    })
+}
+
+/// Luma histogram with `bins` bins.
+pub fn histogram(src: &Frame, bins: usize) -> Vec<u32> {
+    let mut hist = vec![0; bins];
+    for c in src.pixels.iter() {
+        let bin = ((dot3(c, LUMA) * bins as f32) as isize).max(0).min(bins as isize - 1);
+        hist[bin as usize] += 1;
+    }
+    hist
+}
@@ -3,6 +3,9 @@
 //! Printing info for any of the main types is as simple as
 //! `println("{}", &instance);` as `Display` is implemented for each.
 //!
+//! Platforms, devices, objects and fields can be selected at runtime, and
+//! output as terse or detailed text, a table or JSON (see `--help`).
+//!
 //! Printing algorithm is highly janky (due to laziness -- need to complete
 //! for each `*InfoResult` type) so lots of stuff isn't formatted correctly
 //! (or at all).
@@ -10,16 +13,19 @@
 //!
 
 extern crate ocl;
+extern crate image_fallback;
+extern crate inventory;
+extern crate serde;
+extern crate serde_json;
 #[macro_use] extern crate colorify;
 
-use ocl::{Result as OclResult, Platform, Device, Context, Queue, Buffer, Image, Sampler, Program,
+mod select;
+
+use ocl::{Result as OclResult, Platform, Device, Context, Queue, Buffer, Sampler, Program,
     Kernel, Event, EventList};
 use ocl::core::{ProgramInfo, OclPrm};
-
-const PRINT_DETAILED: bool = true;
-// Overrides above for device and program:
-const PRINT_DETAILED_DEVICE: bool = false;
-const PRINT_DETAILED_PROGRAM: bool = false;
+use image_fallback::FallbackImage;
+use crate::select::{Options, Format};
 
 static TAB: &'static str = "    ";
 static SRC: &'static str = r#"
@@ -28,42 +34,47 @@ static SRC: &'static str = r#"
     }
 "#;
 
-fn info() -> OclResult<()> {
+fn info(opts: &Options) -> OclResult<()> {
+    // Selected fields, tables and JSON come from the inventory:
+    if !opts.fields.is_empty() || opts.format == Format::Table || opts.format == Format::Json {
+        return select::print_fields(opts);
+    }
+
     let dims = 2048;
-    let platforms = Platform::list();
+    let platforms = opts.platforms();
+    let detailed = opts.format == Format::Detailed;
 
     println!("Looping through avaliable platforms ({}):", platforms.len());
 
     // Loop through all avaliable platforms:
-    for p_idx in 0..platforms.len() {
-        let platform = &platforms[p_idx];
-
-        let devices = Device::list_all(platform)?;
+    for (p_idx, platform) in platforms {
+        let devices = opts.devices(&platform)?;
 
         if devices.is_empty() { continue; }
 
-        // [NOTE]: A new context can also be created for each device if desired.
-        let context = Context::builder()
-            .platform(platform.clone())
-            .devices(&devices)
-            .build()?;
-
         print_platform_info(&platform)?;
 
-        for device in devices.iter() {
-            print_device_info(device)?;
+        for &(_, device) in devices.iter() {
+            print_device_info(&device, detailed)?;
         }
 
-        // Loop through each device
-        for d_idx in 0..devices.len() {
-            let device = devices[d_idx];
+        if opts.objects.is_empty() { continue; }
 
+        // [NOTE]: A new context can also be created for each device if desired.
+        let device_list: Vec<Device> = devices.iter().map(|&(_, device)| device).collect();
+        let context = Context::builder()
+            .platform(platform)
+            .devices(&device_list)
+            .build()?;
+
+        // Loop through each device
+        for (d_idx, &device) in device_list.iter().enumerate() {
             let queue = Queue::new(&context, device, Some(ocl::core::QUEUE_PROFILING_ENABLE))?;
             let buffer = Buffer::<f32>::builder()
                 .queue(queue.clone())
                 .len(dims)
                 .build()?;
-            let image = Image::<u8>::builder()
+            let image = FallbackImage::<u8>::builder()
                 .dims(dims)
                 .queue(queue.clone())
                 .build()?;
@@ -91,15 +102,16 @@ fn info() -> OclResult<()> {
 
             // Print all but device (just once per platform):
             if d_idx == 0 {
-                print_context_info(&context);
-                print_queue_info(&queue);
-                print_buffer_info(&buffer);
-                print_image_info(&image);
-                print_sampler_info(&sampler);
-                print_program_info(&program)?;
-                print_kernel_info(&kernel);
-                print_event_list_info(&event_list);
-                print_event_info(&event);
+                printlnc!(white_bold: "Objects (platform [{}]):", p_idx);
+                if opts.object("context") { print_context_info(&context); }
+                if opts.object("queue") { print_queue_info(&queue); }
+                if opts.object("buffer") { print_buffer_info(&buffer); }
+                if opts.object("image") { print_image_info(&image); }
+                if opts.object("sampler") { print_sampler_info(&sampler); }
+                if opts.object("program") { print_program_info(&program, detailed)?; }
+                if opts.object("kernel") { print_kernel_info(&kernel); }
+                if opts.object("event_list") { print_event_list_info(&event_list); }
+                if opts.object("event") { print_event_info(&event); }
             }
         }
     }
@@ -116,11 +128,11 @@ fn print_platform_info(platform: &Platform) -> OclResult<()> {
 }
 
 
-fn print_device_info(device: &Device) -> OclResult<()> {
-    if PRINT_DETAILED_DEVICE {
+fn print_device_info(device: &Device, detailed: bool) -> OclResult<()> {
+    if detailed {
         printlnc!(teal: "{}", device);
     } else {
-        if !PRINT_DETAILED { print!("{t}", t = TAB); }
+        print!("{t}", t = TAB);
         printlnc!(teal: "Device (terse) {{ Name: {}, Vendor: {} }}", device.name()?,
             device.vendor()?);
     }
@@ -143,7 +155,10 @@ fn print_buffer_info<T: OclPrm>(buffer: &Buffer<T>) {
 }
 
 
-fn print_image_info<S: OclPrm>(image: &Image<S>) {
+fn print_image_info<S: OclPrm>(image: &FallbackImage<S>) {
+    if let Some(reason) = image.fallback_reason() {
+        printlnc!(orange: "Image format unavailable ({}), using a buffer.", reason);
+    }
     printlnc!(peach: "{}", image);
 }
 
@@ -153,11 +168,11 @@ fn print_sampler_info(sampler: &Sampler) {
 }
 
 
-fn print_program_info(program: &Program) -> OclResult<()> {
-    if PRINT_DETAILED_PROGRAM {
+fn print_program_info(program: &Program, detailed: bool) -> OclResult<()> {
+    if detailed {
         printlnc!(magenta: "{}", program);
     } else {
-        if !PRINT_DETAILED { print!("{t}{t}", t = TAB); }
+        print!("{t}{t}", t = TAB);
         printlnc!(magenta: "Program (terse) {{ KernelNames: '{}', NumDevices: {}, ReferenceCount: {}, Context: {} }}",
             program.info(ProgramInfo::KernelNames)?,
             program.info(ProgramInfo::NumDevices)?,
@@ -185,7 +200,13 @@ fn print_event_info(event: &Event) {
 
 
 pub fn main() {
-    match info() {
+    let res = match Options::from_args() {
+        Ok(Some(opts)) => info(&opts),
+        Ok(None) => { println!("{}", select::USAGE); Ok(()) },
+        Err(err) => Err(err),
+    };
+
+    match res {
         Ok(_) => (),
         Err(err) => println!("{}", err),
     }
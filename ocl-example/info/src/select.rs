@@ -0,0 +1,441 @@
+//! Runtime selection of platforms, devices, objects, fields and output
+//! format.
+
+use std::env;
+use serde_json::{Map, Value};
+use ocl::{Result as OclResult, Platform, Device};
+use inventory::{PlatformInventory, DeviceInventory, ObjectInventory, ContextInventory,
+    QueueInventory, MemInventory, ImageInventory, SamplerInventory, ProgramInventory,
+    KernelInventory, EventInventory, field_names};
+
+pub static USAGE: &'static str = "\
+Usage: info [OPTIONS]
+
+Options:
+    --platform <IDX>         Only the platform with index IDX
+    --device <IDX>           Only the device with index IDX (within each platform)
+    --objects <LIST>         Comma separated objects to create and print:
+                             context, queue, buffer, image, sampler, program,
+                             kernel, event_list, event, all or none
+                             (default: all)
+    --fields <LIST>          Comma separated fields to print per device,
+                             e.g. 'DeviceInfo::MaxComputeUnits,PlatformInfo::Name'
+                             (`ContextInfo`, `MemInfo`, `KernelInfo`, etc.
+                             fields refer to the created objects)
+    --format <FORMAT>        terse, detailed, table or json (default: terse)
+    --help                   Print this message";
+
+/// Objects which can be created and printed for each platform.
+pub const OBJECTS: &[&str] = &["context", "queue", "buffer", "image", "sampler", "program",
+    "kernel", "event_list", "event"];
+
+/// Device fields shown by the table format when no fields are selected.
+const TABLE_FIELDS: &[&str] = &["DeviceInfo::Vendor", "DeviceInfo::Name", "DeviceInfo::Version",
+    "DeviceInfo::MaxComputeUnits", "DeviceInfo::MaxWorkGroupSize", "DeviceInfo::GlobalMemSize",
+    "DeviceInfo::MaxMemAllocSize"];
+
+/// Info variants stored under a different inventory field name.
+const RENAMED: &[(&str, &str)] = &[("DeviceInfo::Type", "device_type"),
+    ("DeviceInfo::ParentDevice", "is_sub_device"), ("MemInfo::Type", "mem_type"),
+    ("ImageInfo::Format", "format")];
+
+/// Inventory struct members which are not info fields.
+const NOT_FIELDS: &[&str] = &["objects", "objects_error", "emulated", "args"];
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Terse,
+    Detailed,
+    Table,
+    Json,
+}
+
+
+/// A field selector such as `DeviceInfo::MaxComputeUnits`, resolved to the
+/// matching inventory field.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    pub label: String,
+    /// `None` for platform fields, `Some("")` for device fields, otherwise
+    /// the object inventory key.
+    scope: Option<&'static str>,
+    key: String,
+}
+
+impl Selector {
+    pub fn parse(sel: &str) -> OclResult<Selector> {
+        let (kind, name) = match sel.find("::") {
+            Some(idx) => (&sel[..idx], &sel[idx + 2..]),
+            None => return Err(format!("Invalid field '{}' (expected e.g. \
+                'DeviceInfo::MaxComputeUnits').", sel).into()),
+        };
+
+        let scope = match kind {
+            "PlatformInfo" => None,
+            "DeviceInfo" => Some(""),
+            "ContextInfo" => Some("context"),
+            "CommandQueueInfo" => Some("queue"),
+            "MemInfo" => Some("buffer"),
+            "ImageInfo" => Some("image"),
+            "SamplerInfo" => Some("sampler"),
+            "ProgramInfo" | "ProgramBuildInfo" => Some("program"),
+            "KernelInfo" | "KernelWorkGroupInfo" => Some("kernel"),
+            "EventInfo" | "ProfilingInfo" => Some("event"),
+            other => return Err(format!("Unknown info kind '{}' in field '{}'.", other, sel)
+                .into()),
+        };
+
+        // Inventory fields are the snake case variant names, apart from a few
+        // renamed where the name alone is ambiguous:
+        let key = match RENAMED.iter().find(|&&(from, _)| from == sel) {
+            Some(&(_, to)) => to.to_owned(),
+            None if kind == "ProfilingInfo" => format!("{}_ns", snake_case(name)),
+            None => snake_case(name),
+        };
+
+        let valid = valid_names(kind, scope);
+        if !valid.iter().any(|(_, field)| *field == key) {
+            let names: Vec<&str> = valid.iter().map(|(name, _)| name.as_str()).collect();
+            return Err(format!("Unknown field '{}' (valid {} fields: {}).", sel, kind,
+                names.join(", ")).into());
+        }
+
+        Ok(Selector { label: sel.to_owned(), scope, key })
+    }
+
+    pub fn needs_objects(&self) -> bool {
+        match self.scope {
+            Some(scope) => !scope.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Finds the field within a platform and device inventory (as JSON). Object
+    /// fields of a device whose objects failed are the failure (a string).
+    pub fn lookup<'v>(&self, platform: &'v Value, device: &'v Value) -> Option<&'v Value> {
+        let fields = match self.scope {
+            None => platform,
+            Some("") => device,
+            Some(object) => match device.get("objects") {
+                Some(objects) => objects.get(object)?,
+                None => return device.get("objects_error"),
+            },
+        };
+        fields.get(&self.key)
+    }
+}
+
+/// The inventory field keys in `scope`.
+fn scope_fields(scope: Option<&str>) -> &'static [&'static str] {
+    match scope {
+        None => field_names::<PlatformInventory>(),
+        Some("") => field_names::<DeviceInventory>(),
+        Some("context") => field_names::<ContextInventory>(),
+        Some("queue") => field_names::<QueueInventory>(),
+        Some("buffer") => field_names::<MemInventory>(),
+        Some("image") => field_names::<ImageInventory>(),
+        Some("sampler") => field_names::<SamplerInventory>(),
+        Some("program") => field_names::<ProgramInventory>(),
+        Some("kernel") => field_names::<KernelInventory>(),
+        Some("event") => field_names::<EventInventory>(),
+        Some(_) => &[],
+    }
+}
+
+/// The selectors of `kind` (e.g. "DeviceInfo::MaxComputeUnits") with their
+/// inventory field keys.
+fn valid_names(kind: &str, scope: Option<&str>) -> Vec<(String, &'static str)> {
+    scope_fields(scope).iter()
+        .filter(|key| !NOT_FIELDS.contains(*key))
+        .filter(|key| match kind {
+            "ProfilingInfo" => key.ends_with("_ns"),
+            "EventInfo" => !key.ends_with("_ns"),
+            _ => true,
+        })
+        .map(|&key| {
+            let name = match RENAMED.iter()
+                .find(|&&(from, to)| to == key && from.starts_with(kind))
+            {
+                Some(&(from, _)) => from.to_owned(),
+                None => format!("{}::{}", kind, camel_case(key.trim_end_matches("_ns"))),
+            };
+            (name, key)
+        })
+        .collect()
+}
+
+fn camel_case(key: &str) -> String {
+    key.split('_').map(|word| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    }).collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (idx, c) in name.chars().enumerate() {
+        if c.is_uppercase() && idx > 0 { snake.push('_'); }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+/// Formats a serialized `inventory::Field` (or an object failure, see
+/// `Selector::lookup`).
+pub fn show(field: Option<&Value>) -> String {
+    let field = match field {
+        Some(&Value::String(ref err)) => return format!("error: {}", err),
+        Some(field) => field,
+        None => return "-".to_owned(),
+    };
+    match (field.get("status").and_then(|s| s.as_str()), field.get("value")) {
+        (Some("ok"), Some(&Value::String(ref s))) => s.clone(),
+        (Some("ok"), Some(value)) => value.to_string(),
+        (Some("not_available"), _) => "n/a".to_owned(),
+        (Some("error"), Some(err)) => format!("error: {}", err.as_str().unwrap_or("")),
+        _ => field.to_string(),
+    }
+}
+
+
+/// Everything selected on the command line.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub platform: Option<usize>,
+    pub device: Option<usize>,
+    pub objects: Vec<String>,
+    pub fields: Vec<Selector>,
+    pub format: Format,
+}
+
+impl Options {
+    /// Parses the command line, returning `None` if help was requested.
+    pub fn from_args() -> OclResult<Option<Options>> {
+        let mut opts = Options {
+            platform: None,
+            device: None,
+            objects: OBJECTS.iter().map(|s| s.to_string()).collect(),
+            fields: Vec::new(),
+            format: Format::Terse,
+        };
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next()
+                .ok_or_else(|| format!("Missing value for '{}'.\n\n{}", arg, USAGE));
+
+            match arg.as_str() {
+                "--platform" => opts.platform = Some(value()?.parse()
+                    .map_err(|err| format!("Invalid platform index: {}", err))?),
+                "--device" => opts.device = Some(value()?.parse()
+                    .map_err(|err| format!("Invalid device index: {}", err))?),
+                "--objects" => opts.objects = parse_objects(&value()?)?,
+                "--fields" => opts.fields = value()?.split(',').filter(|s| !s.is_empty())
+                    .map(|s| Selector::parse(s.trim()))
+                    .collect::<OclResult<_>>()?,
+                "--format" => opts.format = match value()?.as_str() {
+                    "terse" => Format::Terse,
+                    "detailed" => Format::Detailed,
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    other => return Err(format!("Unknown format '{}'.\n\n{}", other, USAGE)
+                        .into()),
+                },
+                "--help" | "-h" => return Ok(None),
+                other => return Err(format!("Unknown argument: '{}'\n\n{}", other, USAGE)
+                    .into()),
+            }
+        }
+        Ok(Some(opts))
+    }
+
+    pub fn object(&self, name: &str) -> bool {
+        self.objects.iter().any(|o| o == name)
+    }
+
+    /// The selected platforms, with their indices.
+    pub fn platforms(&self) -> Vec<(usize, Platform)> {
+        Platform::list().into_iter().enumerate()
+            .filter(|&(idx, _)| self.platform.map(|p| p == idx).unwrap_or(true))
+            .collect()
+    }
+
+    /// The selected devices of `platform`, with their indices.
+    pub fn devices(&self, platform: &Platform) -> OclResult<Vec<(usize, Device)>> {
+        Ok(Device::list_all(platform)?.into_iter().enumerate()
+            .filter(|&(idx, _)| self.device.map(|d| d == idx).unwrap_or(true))
+            .collect())
+    }
+
+    /// Whether the table/JSON output needs objects created on each device.
+    fn gather_objects(&self) -> bool {
+        if self.fields.is_empty() {
+            self.format == Format::Json && !self.objects.is_empty()
+        } else {
+            self.fields.iter().any(|sel| sel.needs_objects())
+        }
+    }
+}
+
+fn parse_objects(list: &str) -> OclResult<Vec<String>> {
+    match list {
+        "all" => return Ok(OBJECTS.iter().map(|s| s.to_string()).collect()),
+        "none" => return Ok(Vec::new()),
+        _ => (),
+    }
+    list.split(',').map(|name| {
+        let name = name.trim();
+        if OBJECTS.contains(&name) {
+            Ok(name.to_owned())
+        } else {
+            Err(format!("Unknown object '{}' (expected one of: {}).", name, OBJECTS.join(", "))
+                .into())
+        }
+    }).collect()
+}
+
+
+/// One selected device, as JSON.
+struct Row {
+    platform_idx: usize,
+    device_idx: usize,
+    platform: Value,
+    device: Value,
+}
+
+fn gather(opts: &Options) -> OclResult<Vec<Row>> {
+    let mut rows = Vec::new();
+
+    for (platform_idx, platform) in opts.platforms() {
+        let platform_inv = PlatformInventory::query(platform);
+
+        for (device_idx, device) in opts.devices(&platform)? {
+            let mut device_inv = DeviceInventory::query(device);
+            // A device whose objects can't be created still gets its own row:
+            if opts.gather_objects() {
+                match ObjectInventory::query(platform, device) {
+                    Ok(objects) => device_inv.objects = Some(objects),
+                    Err(err) => device_inv.objects_error = Some(err.to_string()),
+                }
+            }
+
+            let mut device_json = to_value(&device_inv)?;
+            // Only the selected objects:
+            if let Some(&mut Value::Object(ref mut objects)) = device_json.get_mut("objects") {
+                let keep: Vec<String> = objects.keys()
+                    .filter(|key| opts.object(key) || (*key == "image_formats"
+                        && opts.object("image")))
+                    .cloned()
+                    .collect();
+                objects.retain(|key, _| keep.contains(key));
+            }
+
+            rows.push(Row {
+                platform_idx,
+                device_idx,
+                platform: to_value(&platform_inv)?,
+                device: device_json,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> OclResult<Value> {
+    serde_json::to_value(value).map_err(|err| format!("Unable to serialize: {}", err).into())
+}
+
+
+/// Prints the selected fields (or, with none, a summary table or the full
+/// JSON inventory) of every selected device.
+pub fn print_fields(opts: &Options) -> OclResult<()> {
+    let fields = if opts.fields.is_empty() && opts.format != Format::Json {
+        TABLE_FIELDS.iter().map(|sel| Selector::parse(sel)).collect::<OclResult<Vec<_>>>()?
+    } else {
+        opts.fields.clone()
+    };
+    let rows = gather(opts)?;
+
+    match opts.format {
+        Format::Json if fields.is_empty() => {
+            let devices: Vec<Value> = rows.into_iter().map(|mut row| {
+                if let Value::Object(ref mut map) = row.device {
+                    map.insert("platform".to_owned(), row.platform);
+                    map.insert("platform_idx".to_owned(), row.platform_idx.into());
+                    map.insert("device_idx".to_owned(), row.device_idx.into());
+                }
+                row.device
+            }).collect();
+            print_json(&Value::Array(devices))?;
+        },
+        Format::Json => {
+            let devices: Vec<Value> = rows.iter().map(|row| {
+                let mut map = Map::new();
+                map.insert("platform_idx".to_owned(), row.platform_idx.into());
+                map.insert("device_idx".to_owned(), row.device_idx.into());
+                for sel in fields.iter() {
+                    map.insert(sel.label.clone(), sel.lookup(&row.platform, &row.device)
+                        .cloned().unwrap_or(Value::Null));
+                }
+                Value::Object(map)
+            }).collect();
+            print_json(&Value::Array(devices))?;
+        },
+        Format::Table => print_table(&rows, &fields),
+        Format::Terse | Format::Detailed => {
+            for row in rows.iter() {
+                let values: Vec<String> = fields.iter()
+                    .map(|sel| format!("{}: {}", sel.label,
+                        show(sel.lookup(&row.platform, &row.device))))
+                    .collect();
+
+                if opts.format == Format::Terse {
+                    println!("[{}:{}] {}", row.platform_idx, row.device_idx, values.join(", "));
+                } else {
+                    println!("Platform [{}] Device [{}]:", row.platform_idx, row.device_idx);
+                    for value in values { println!("    {}", value); }
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+fn print_json(value: &Value) -> OclResult<()> {
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|err| format!("Unable to serialize: {}", err))?;
+    println!("{}", text);
+    Ok(())
+}
+
+fn print_table(rows: &[Row], fields: &[Selector]) {
+    let mut header = vec!["P".to_owned(), "D".to_owned()];
+    header.extend(fields.iter().map(|sel| {
+        sel.label.rsplit("::").next().unwrap_or(&sel.label).to_owned()
+    }));
+
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| {
+        let mut cells = vec![row.platform_idx.to_string(), row.device_idx.to_string()];
+        cells.extend(fields.iter().map(|sel| show(sel.lookup(&row.platform, &row.device))));
+        cells
+    }).collect();
+
+    let widths: Vec<usize> = (0..header.len()).map(|col| {
+        cells.iter().map(|row| row[col].len()).chain(Some(header[col].len())).max().unwrap_or(0)
+    }).collect();
+
+    let line = |row: &[String]| {
+        row.iter().zip(widths.iter()).map(|(cell, &width)| format!("{:<1$}", cell, width))
+            .collect::<Vec<_>>().join(" | ")
+    };
+
+    println!("{}", line(&header));
+    println!("{}", widths.iter().map(|&w| "-".repeat(w)).collect::<Vec<_>>().join("-+-"));
+    for row in cells.iter() {
+        println!("{}", line(row));
+    }
+}
@@ -1,18 +1,31 @@
 //! Get information about all the things using `core` function calls.
 //!
-//! Set `INFO_FORMAT_MULTILINE` to `false` for compact printing.
+//! Pass `--compact` for one line per object, and `--platform`/`--device` to
+//! limit the output (see `--help`).
 
 extern crate ocl;
+extern crate image_fallback;
+
+use std::env;
 
 use ocl::core::{self, PlatformInfo, DeviceInfo, ContextInfo,
     CommandQueueInfo, MemInfo, ImageInfo, SamplerInfo, ProgramInfo,
     ProgramBuildInfo, KernelInfo, KernelArgInfo, KernelWorkGroupInfo,
     EventInfo, ProfilingInfo, Status};
-use ocl::{Platform, Device, Context, Queue, Buffer, Image, Sampler, Program,
+use ocl::{Platform, Device, Context, Queue, Buffer, Sampler, Program,
     Kernel, Event, EventList, SpatialDims};
+use image_fallback::FallbackImage;
 
 const WORK_SIZE: [usize; 3] = [1024, 64, 16];
-const INFO_FORMAT_MULTILINE: bool = true;
+
+static USAGE: &'static str = "\
+Usage: info_core [OPTIONS]
+
+Options:
+    --platform <IDX>    Only the platform with index IDX
+    --device <IDX>      Only the device with index IDX (within each platform)
+    --compact           Print each object's info on a single line
+    --help              Print this message";
 
 static SRC: &'static str = r#"
     __kernel void multiply(float coeff, __global float* buffer) {
@@ -37,7 +50,7 @@ macro_rules! to_string {
 
 
 fn print_platform_device(plat_idx: usize, platform: Platform, device_idx: usize,
-        device: Device) -> ocl::Result<()> {
+        device: Device, multiline: bool) -> ocl::Result<()> {
     let work_dims = SpatialDims::from(WORK_SIZE);
 
     let context = Context::builder().platform(platform).devices(device).build()?;
@@ -50,7 +63,7 @@ fn print_platform_device(plat_idx: usize, platform: Platform, device_idx: usize,
         .queue(queue.clone())
         .len(work_dims)
         .build()?;
-    let image = Image::<u8>::builder()
+    let image = FallbackImage::<u8>::builder()
         .dims(work_dims)
         .queue(queue.clone())
         .build()?;
@@ -77,7 +90,7 @@ fn print_platform_device(plat_idx: usize, platform: Platform, device_idx: usize,
     println!("############### OpenCL Platform-Device Full Info ################");
     print!("\n");
 
-    let (begin, delim, end) = if INFO_FORMAT_MULTILINE {
+    let (begin, delim, end) = if multiline {
         ("\n", "\n", "\n")
     } else {
         ("{ ", ", ", " }")
@@ -337,53 +350,69 @@ fn print_platform_device(plat_idx: usize, platform: Platform, device_idx: usize,
     // ##################### IMAGE ######################
     // ##################################################
 
-    println!("Image: {b}\
-            ElementSize: {}{d}\
-            RowPitch: {}{d}\
-            SlicePitch: {}{d}\
-            Width: {}{d}\
-            Height: {}{d}\
-            Depth: {}{d}\
-            ArraySize: {}{d}\
-            Buffer: {}{d}\
-            NumMipLevels: {}{d}\
-            NumSamples: {}{e}\
-        ",
-        to_string!(core::get_image_info(&image, ImageInfo::ElementSize)),
-        to_string!(core::get_image_info(&image, ImageInfo::RowPitch)),
-        to_string!(core::get_image_info(&image, ImageInfo::SlicePitch)),
-        to_string!(core::get_image_info(&image, ImageInfo::Width)),
-        to_string!(core::get_image_info(&image, ImageInfo::Height)),
-        to_string!(core::get_image_info(&image, ImageInfo::Depth)),
-        to_string!(core::get_image_info(&image, ImageInfo::ArraySize)),
-        to_string!(core::get_image_info(&image, ImageInfo::Buffer)),
-        to_string!(core::get_image_info(&image, ImageInfo::NumMipLevels)),
-        to_string!(core::get_image_info(&image, ImageInfo::NumSamples)),
-        b = begin, d = delim, e = end,
-    );
-
-    println!("Image Memory:{b}\
-            Type: {}{d}\
-            Flags: {}{d}\
-            Size: {}{d}\
-            HostPtr: {}{d}\
-            MapCount: {}{d}\
-            ReferenceCount: {}{d}\
-            Context: {}{d}\
-            AssociatedMemobject: {}{d}\
-            Offset: {}{e}\
-        ",
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::Type)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::Flags)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::Size)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::HostPtr)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::MapCount)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::ReferenceCount)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::Context)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::AssociatedMemobject)),
-        to_string!(core::get_mem_object_info(&buffer, MemInfo::Offset)),
-        b = begin, d = delim, e = end,
-    );
+    // Devices without image support (or the default format) get a buffer
+    // instead, which has no image info to query:
+    match image.as_image() {
+        Some(image) => {
+            println!("Image: {b}\
+                    ElementSize: {}{d}\
+                    RowPitch: {}{d}\
+                    SlicePitch: {}{d}\
+                    Width: {}{d}\
+                    Height: {}{d}\
+                    Depth: {}{d}\
+                    ArraySize: {}{d}\
+                    Buffer: {}{d}\
+                    NumMipLevels: {}{d}\
+                    NumSamples: {}{e}\
+                ",
+                to_string!(core::get_image_info(image, ImageInfo::ElementSize)),
+                to_string!(core::get_image_info(image, ImageInfo::RowPitch)),
+                to_string!(core::get_image_info(image, ImageInfo::SlicePitch)),
+                to_string!(core::get_image_info(image, ImageInfo::Width)),
+                to_string!(core::get_image_info(image, ImageInfo::Height)),
+                to_string!(core::get_image_info(image, ImageInfo::Depth)),
+                to_string!(core::get_image_info(image, ImageInfo::ArraySize)),
+                to_string!(core::get_image_info(image, ImageInfo::Buffer)),
+                to_string!(core::get_image_info(image, ImageInfo::NumMipLevels)),
+                to_string!(core::get_image_info(image, ImageInfo::NumSamples)),
+                b = begin, d = delim, e = end,
+            );
+
+            println!("Image Memory:{b}\
+                    Type: {}{d}\
+                    Flags: {}{d}\
+                    Size: {}{d}\
+                    HostPtr: {}{d}\
+                    MapCount: {}{d}\
+                    ReferenceCount: {}{d}\
+                    Context: {}{d}\
+                    AssociatedMemobject: {}{d}\
+                    Offset: {}{e}\
+                ",
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::Type)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::Flags)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::Size)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::HostPtr)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::MapCount)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::ReferenceCount)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::Context)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::AssociatedMemobject)),
+                to_string!(core::get_mem_object_info(&buffer, MemInfo::Offset)),
+                b = begin, d = delim, e = end,
+            );
+        },
+        None => println!("Image: {b}\
+                Emulated: {}{d}\
+                Dims: {:?}{d}\
+                Layout: {:?}{e}\
+            ",
+            image.fallback_reason().unwrap_or(""),
+            image.dims(),
+            image.as_buffer().map(|(_, layout)| *layout),
+            b = begin, d = delim, e = end,
+        ),
+    }
 
     // ##################################################
     // #################### SAMPLER #####################
@@ -560,17 +589,39 @@ fn print_platform_device(plat_idx: usize, platform: Platform, device_idx: usize,
     Ok(())
 }
 
-fn print_platform(plat_idx: usize, platform: Platform) -> ocl::Result<()> {
-    for (device_idx, &device) in Device::list_all(&platform)?.iter().enumerate() {
-        print_platform_device(plat_idx, platform, device_idx, device)?;
+fn print_platform(plat_idx: usize, platform: Platform, device_idx: Option<usize>,
+        multiline: bool) -> ocl::Result<()> {
+    for (idx, &device) in Device::list_all(&platform)?.iter().enumerate() {
+        if device_idx.map(|d| d == idx).unwrap_or(true) {
+            print_platform_device(plat_idx, platform, idx, device, multiline)?;
+        }
     }
     Ok(())
 }
 
 fn info_core() -> ocl::Result<()> {
+    let mut platform_idx = None;
+    let mut device_idx = None;
+    let mut multiline = true;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform_idx = Some(args.next().and_then(|s| s.parse().ok())
+                .ok_or("'--platform' needs an index.")?),
+            "--device" => device_idx = Some(args.next().and_then(|s| s.parse().ok())
+                .ok_or("'--device' needs an index.")?),
+            "--compact" => multiline = false,
+            "--help" | "-h" => { println!("{}", USAGE); return Ok(()); },
+            other => return Err(format!("Unknown argument: '{}'\n\n{}", other, USAGE).into()),
+        }
+    }
+
     let platforms = Platform::list();
     for (plat_idx, &platform) in platforms.iter().enumerate() {
-        print_platform(plat_idx, platform)?;
+        if platform_idx.map(|p| p == plat_idx).unwrap_or(true) {
+            print_platform(plat_idx, platform, device_idx, multiline)?;
+        }
     }
     Ok(())
 }
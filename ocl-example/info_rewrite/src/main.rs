@@ -10,11 +10,13 @@
 //!
 
 extern crate ocl;
+extern crate image_fallback;
 #[macro_use] extern crate colorify;
 
-use ocl::{Result as OclResult, Platform, Device, Context, Queue, Buffer, Image, Sampler, Program,
+use ocl::{Result as OclResult, Platform, Device, Context, Queue, Buffer, Sampler, Program,
     Kernel, Event, EventList};
 use ocl::core::{ProgramInfo, OclPrm};
+use image_fallback::FallbackImage;
 
 const PRINT_DETAILED: bool = true;
 // Overrides above for device and program:
@@ -41,7 +43,7 @@ static SRC: &'static str = r#"
                 .queue(queue.clone())
                 .len(dims)
                 .build()?;
-            let image = Image::<u8>::builder()
+            let image = FallbackImage::<u8>::builder()
                 .dims(dims)
                 .queue(queue.clone())
                 .build()?;
@@ -121,7 +123,10 @@ fn print_buffer_info<T: OclPrm>(buffer: &Buffer<T>) {
 }
 
 
-fn print_image_info<S: OclPrm>(image: &Image<S>) {
+fn print_image_info<S: OclPrm>(image: &FallbackImage<S>) {
+    if let Some(reason) = image.fallback_reason() {
+        printlnc!(orange: "Image format unavailable ({}), using a buffer.", reason);
+    }
     printlnc!(peach: "{}", image);
 }
 
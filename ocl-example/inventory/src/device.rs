@@ -0,0 +1,285 @@
+//! Platform and device inventories.
+
+use serde::{Serialize, Deserialize};
+use ocl::{core, Platform, Device};
+use ocl::core::{PlatformInfo, PlatformInfoResult, DeviceInfo};
+use crate::field::{self, Field};
+use crate::objects::ObjectInventory;
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlatformInventory {
+    pub profile: Field<String>,
+    pub version: Field<String>,
+    pub name: Field<String>,
+    pub vendor: Field<String>,
+    pub extensions: Field<Vec<String>>,
+    pub devices: Vec<DeviceInventory>,
+}
+
+impl PlatformInventory {
+    /// Queries `platform` (but not its devices).
+    pub fn query(platform: Platform) -> PlatformInventory {
+        let info = |kind| core::get_platform_info(platform, kind);
+
+        PlatformInventory {
+            profile: field!(info(PlatformInfo::Profile), PlatformInfoResult::Profile),
+            version: field!(info(PlatformInfo::Version), PlatformInfoResult::Version,
+                |v| v.to_string()),
+            name: field!(info(PlatformInfo::Name), PlatformInfoResult::Name),
+            vendor: field!(info(PlatformInfo::Vendor), PlatformInfoResult::Vendor),
+            extensions: field!(info(PlatformInfo::Extensions), PlatformInfoResult::Extensions,
+                split_list),
+            devices: Vec::new(),
+        }
+    }
+}
+
+
+/// Splits a space separated list (extensions, built-in kernels).
+fn split_list(list: String) -> Vec<String> {
+    list.split_whitespace().map(|s| s.to_owned()).collect()
+}
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceInventory {
+    pub name: Field<String>,
+    pub vendor: Field<String>,
+    pub vendor_id: Field<u32>,
+    pub device_type: Field<String>,
+    pub version: Field<String>,
+    pub driver_version: Field<String>,
+    pub profile: Field<String>,
+    pub opencl_c_version: Field<String>,
+    pub extensions: Field<Vec<String>>,
+    pub built_in_kernels: Field<Vec<String>>,
+    pub available: Field<bool>,
+    pub compiler_available: Field<bool>,
+    pub linker_available: Field<bool>,
+    pub endian_little: Field<bool>,
+    pub error_correction_support: Field<bool>,
+    pub host_unified_memory: Field<bool>,
+
+    pub max_compute_units: Field<u32>,
+    pub max_clock_frequency: Field<u32>,
+    pub address_bits: Field<u32>,
+    pub max_work_item_dimensions: Field<u32>,
+    pub max_work_group_size: Field<usize>,
+    pub max_work_item_sizes: Field<Vec<usize>>,
+    pub max_parameter_size: Field<usize>,
+    pub profiling_timer_resolution: Field<usize>,
+    pub printf_buffer_size: Field<usize>,
+    pub execution_capabilities: Field<String>,
+    pub queue_properties: Field<String>,
+
+    pub preferred_vector_width_char: Field<u32>,
+    pub preferred_vector_width_short: Field<u32>,
+    pub preferred_vector_width_int: Field<u32>,
+    pub preferred_vector_width_long: Field<u32>,
+    pub preferred_vector_width_float: Field<u32>,
+    pub preferred_vector_width_double: Field<u32>,
+    pub preferred_vector_width_half: Field<u32>,
+    pub native_vector_width_char: Field<u32>,
+    pub native_vector_width_short: Field<u32>,
+    pub native_vector_width_int: Field<u32>,
+    pub native_vector_width_long: Field<u32>,
+    pub native_vector_width_float: Field<u32>,
+    pub native_vector_width_double: Field<u32>,
+    pub native_vector_width_half: Field<u32>,
+    pub single_fp_config: Field<String>,
+    pub double_fp_config: Field<String>,
+    pub half_fp_config: Field<String>,
+
+    pub global_mem_size: Field<u64>,
+    pub global_mem_cache_type: Field<String>,
+    pub global_mem_cache_size: Field<u64>,
+    pub global_mem_cacheline_size: Field<u32>,
+    pub local_mem_type: Field<String>,
+    pub local_mem_size: Field<u64>,
+    pub max_mem_alloc_size: Field<u64>,
+    pub max_constant_buffer_size: Field<u64>,
+    pub max_constant_args: Field<u32>,
+    pub mem_base_addr_align: Field<u32>,
+    pub min_data_type_align_size: Field<u32>,
+
+    pub image_support: Field<bool>,
+    pub max_read_image_args: Field<u32>,
+    pub max_write_image_args: Field<u32>,
+    pub max_samplers: Field<u32>,
+    pub image2d_max_width: Field<usize>,
+    pub image2d_max_height: Field<usize>,
+    pub image3d_max_width: Field<usize>,
+    pub image3d_max_height: Field<usize>,
+    pub image3d_max_depth: Field<usize>,
+    pub image_max_buffer_size: Field<usize>,
+    pub image_max_array_size: Field<usize>,
+    pub image_pitch_alignment: Field<u32>,
+    pub image_base_address_alignment: Field<u32>,
+
+    pub is_sub_device: Field<bool>,
+    pub partition_max_sub_devices: Field<u32>,
+    pub partition_properties: Field<String>,
+    pub partition_affinity_domain: Field<String>,
+    pub partition_type: Field<String>,
+    pub preferred_interop_user_sync: Field<bool>,
+
+    /// Objects created on the device, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objects: Option<ObjectInventory>,
+    /// Why the objects could not be created, if they were requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objects_error: Option<String>,
+}
+
+impl DeviceInventory {
+    /// Queries `device` (without creating any objects).
+    pub fn query(device: Device) -> DeviceInventory {
+        use ocl::core::DeviceInfoResult as R;
+        let info = |kind| core::get_device_info(&device, kind);
+        let version = device.version().ok();
+        let since = |major, minor| field::since(version, major, minor);
+        let extensions = match info(DeviceInfo::Extensions) {
+            Ok(R::Extensions(extensions)) => extensions,
+            _ => String::new(),
+        };
+        let has_ext = |name: &str| extensions.split_whitespace().any(|ext| ext == name);
+
+        DeviceInventory {
+            name: field!(info(DeviceInfo::Name), R::Name),
+            vendor: field!(info(DeviceInfo::Vendor), R::Vendor),
+            vendor_id: field!(info(DeviceInfo::VendorId), R::VendorId),
+            device_type: field_debug!(info(DeviceInfo::Type), R::Type),
+            version: field!(info(DeviceInfo::Version), R::Version, |v| v.to_string()),
+            driver_version: field!(info(DeviceInfo::DriverVersion), R::DriverVersion),
+            profile: field!(info(DeviceInfo::Profile), R::Profile),
+            opencl_c_version: optional_field!(since(1, 1),
+                info(DeviceInfo::OpenclCVersion), R::OpenclCVersion),
+            extensions: field!(info(DeviceInfo::Extensions), R::Extensions, split_list),
+            built_in_kernels: optional_field!(since(1, 2),
+                info(DeviceInfo::BuiltInKernels), R::BuiltInKernels,
+                |v: String| v.split(';').filter(|s| !s.is_empty()).map(|s| s.to_owned()).collect()),
+            available: field!(info(DeviceInfo::Available), R::Available),
+            compiler_available: field!(info(DeviceInfo::CompilerAvailable), R::CompilerAvailable),
+            linker_available: optional_field!(since(1, 2),
+                info(DeviceInfo::LinkerAvailable), R::LinkerAvailable),
+            endian_little: field!(info(DeviceInfo::EndianLittle), R::EndianLittle),
+            error_correction_support: field!(info(DeviceInfo::ErrorCorrectionSupport),
+                R::ErrorCorrectionSupport),
+            host_unified_memory: optional_field!(since(1, 1),
+                info(DeviceInfo::HostUnifiedMemory), R::HostUnifiedMemory),
+
+            max_compute_units: field!(info(DeviceInfo::MaxComputeUnits), R::MaxComputeUnits),
+            max_clock_frequency: field!(info(DeviceInfo::MaxClockFrequency), R::MaxClockFrequency),
+            address_bits: field!(info(DeviceInfo::AddressBits), R::AddressBits),
+            max_work_item_dimensions: field!(info(DeviceInfo::MaxWorkItemDimensions),
+                R::MaxWorkItemDimensions),
+            max_work_group_size: field!(info(DeviceInfo::MaxWorkGroupSize), R::MaxWorkGroupSize),
+            max_work_item_sizes: field!(info(DeviceInfo::MaxWorkItemSizes), R::MaxWorkItemSizes),
+            max_parameter_size: field!(info(DeviceInfo::MaxParameterSize), R::MaxParameterSize),
+            profiling_timer_resolution: field!(info(DeviceInfo::ProfilingTimerResolution),
+                R::ProfilingTimerResolution),
+            printf_buffer_size: optional_field!(since(1, 2),
+                info(DeviceInfo::PrintfBufferSize), R::PrintfBufferSize),
+            execution_capabilities: field_debug!(info(DeviceInfo::ExecutionCapabilities),
+                R::ExecutionCapabilities),
+            queue_properties: field_debug!(info(DeviceInfo::QueueProperties), R::QueueProperties),
+
+            preferred_vector_width_char: field!(info(DeviceInfo::PreferredVectorWidthChar),
+                R::PreferredVectorWidthChar),
+            preferred_vector_width_short: field!(info(DeviceInfo::PreferredVectorWidthShort),
+                R::PreferredVectorWidthShort),
+            preferred_vector_width_int: field!(info(DeviceInfo::PreferredVectorWidthInt),
+                R::PreferredVectorWidthInt),
+            preferred_vector_width_long: field!(info(DeviceInfo::PreferredVectorWidthLong),
+                R::PreferredVectorWidthLong),
+            preferred_vector_width_float: field!(info(DeviceInfo::PreferredVectorWidthFloat),
+                R::PreferredVectorWidthFloat),
+            preferred_vector_width_double: field!(info(DeviceInfo::PreferredVectorWidthDouble),
+                R::PreferredVectorWidthDouble),
+            preferred_vector_width_half: optional_field!(since(1, 1),
+                info(DeviceInfo::PreferredVectorWidthHalf), R::PreferredVectorWidthHalf),
+            native_vector_width_char: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthChar), R::NativeVectorWidthChar),
+            native_vector_width_short: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthShort), R::NativeVectorWidthShort),
+            native_vector_width_int: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthInt), R::NativeVectorWidthInt),
+            native_vector_width_long: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthLong), R::NativeVectorWidthLong),
+            native_vector_width_float: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthFloat), R::NativeVectorWidthFloat),
+            native_vector_width_double: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthDouble), R::NativeVectorWidthDouble),
+            native_vector_width_half: optional_field!(since(1, 1),
+                info(DeviceInfo::NativeVectorWidthHalf), R::NativeVectorWidthHalf),
+            single_fp_config: field_debug!(info(DeviceInfo::SingleFpConfig), R::SingleFpConfig),
+            double_fp_config: optional_field_debug!(since(1, 2) || has_ext("cl_khr_fp64"),
+                info(DeviceInfo::DoubleFpConfig), R::DoubleFpConfig),
+            half_fp_config: optional_field_debug!(has_ext("cl_khr_fp16"),
+                info(DeviceInfo::HalfFpConfig), R::HalfFpConfig),
+
+            global_mem_size: field!(info(DeviceInfo::GlobalMemSize), R::GlobalMemSize),
+            global_mem_cache_type: field_debug!(info(DeviceInfo::GlobalMemCacheType),
+                R::GlobalMemCacheType),
+            global_mem_cache_size: field!(info(DeviceInfo::GlobalMemCacheSize),
+                R::GlobalMemCacheSize),
+            global_mem_cacheline_size: field!(info(DeviceInfo::GlobalMemCachelineSize),
+                R::GlobalMemCachelineSize),
+            local_mem_type: field_debug!(info(DeviceInfo::LocalMemType), R::LocalMemType),
+            local_mem_size: field!(info(DeviceInfo::LocalMemSize), R::LocalMemSize),
+            max_mem_alloc_size: field!(info(DeviceInfo::MaxMemAllocSize), R::MaxMemAllocSize),
+            max_constant_buffer_size: field!(info(DeviceInfo::MaxConstantBufferSize),
+                R::MaxConstantBufferSize),
+            max_constant_args: field!(info(DeviceInfo::MaxConstantArgs), R::MaxConstantArgs),
+            mem_base_addr_align: field!(info(DeviceInfo::MemBaseAddrAlign), R::MemBaseAddrAlign),
+            min_data_type_align_size: field!(info(DeviceInfo::MinDataTypeAlignSize),
+                R::MinDataTypeAlignSize),
+
+            image_support: field!(info(DeviceInfo::ImageSupport), R::ImageSupport),
+            max_read_image_args: field!(info(DeviceInfo::MaxReadImageArgs), R::MaxReadImageArgs),
+            max_write_image_args: field!(info(DeviceInfo::MaxWriteImageArgs),
+                R::MaxWriteImageArgs),
+            max_samplers: field!(info(DeviceInfo::MaxSamplers), R::MaxSamplers),
+            image2d_max_width: field!(info(DeviceInfo::Image2dMaxWidth), R::Image2dMaxWidth),
+            image2d_max_height: field!(info(DeviceInfo::Image2dMaxHeight), R::Image2dMaxHeight),
+            image3d_max_width: field!(info(DeviceInfo::Image3dMaxWidth), R::Image3dMaxWidth),
+            image3d_max_height: field!(info(DeviceInfo::Image3dMaxHeight), R::Image3dMaxHeight),
+            image3d_max_depth: field!(info(DeviceInfo::Image3dMaxDepth), R::Image3dMaxDepth),
+            image_max_buffer_size: optional_field!(since(1, 2),
+                info(DeviceInfo::ImageMaxBufferSize), R::ImageMaxBufferSize),
+            image_max_array_size: optional_field!(since(1, 2), info(DeviceInfo::ImageMaxArraySize),
+                R::ImageMaxArraySize),
+            image_pitch_alignment: optional_field!(since(2, 0),
+                info(DeviceInfo::ImagePitchAlignment), R::ImagePitchAlignment),
+            image_base_address_alignment: optional_field!(since(2, 0),
+                info(DeviceInfo::ImageBaseAddressAlignment), R::ImageBaseAddressAlignment),
+
+            is_sub_device: optional_field!(since(1, 2),
+                info(DeviceInfo::ParentDevice), R::ParentDevice,
+                |parent: Option<_>| parent.is_some()),
+            partition_max_sub_devices: optional_field!(since(1, 2),
+                info(DeviceInfo::PartitionMaxSubDevices), R::PartitionMaxSubDevices),
+            partition_properties: optional_field_debug!(since(1, 2),
+                info(DeviceInfo::PartitionProperties), R::PartitionProperties),
+            partition_affinity_domain: optional_field_debug!(since(1, 2),
+                info(DeviceInfo::PartitionAffinityDomain), R::PartitionAffinityDomain),
+            partition_type: optional_field_debug!(since(1, 2),
+                info(DeviceInfo::PartitionType), R::PartitionType),
+            preferred_interop_user_sync: optional_field!(since(1, 2),
+                info(DeviceInfo::PreferredInteropUserSync), R::PreferredInteropUserSync),
+
+            objects: None,
+            objects_error: None,
+        }
+    }
+
+    /// "Vendor Name", or whatever of the two could be queried.
+    pub fn label(&self) -> String {
+        match (self.vendor.ok(), self.name.ok()) {
+            (Some(vendor), Some(name)) => format!("{} {}", vendor, name),
+            (None, Some(name)) => name.clone(),
+            _ => "<unknown device>".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Comparing an inventory against a saved snapshot.
+//!
+//! Devices are matched by platform and device name. Each differing field is
+//! classified by how likely it is to break kernels which worked with the
+//! snapshot:
+//!
+//! * Lower limits (`LIMITS`), lost extensions, built-in kernels or image
+//!   formats, missing devices and fields which no longer query are
+//!   `Incompatible`.
+//! * Version changes (`VERSIONS`) are a `Warning`.
+//! * Anything else (raised limits, new extensions or devices) is `Info`.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+use serde_json::{Map, Value};
+use crate::{Inventory, PlatformInventory, DeviceInventory};
+
+/// Device fields where a lower value may break existing kernels.
+pub const LIMITS: &[&str] = &[
+    "max_compute_units", "max_work_item_dimensions", "max_work_group_size",
+    "max_work_item_sizes", "max_parameter_size", "printf_buffer_size",
+    "global_mem_size", "global_mem_cache_size", "local_mem_size", "max_mem_alloc_size",
+    "max_constant_buffer_size", "max_constant_args", "max_read_image_args",
+    "max_write_image_args", "max_samplers", "image2d_max_width", "image2d_max_height",
+    "image3d_max_width", "image3d_max_height", "image3d_max_depth", "image_max_buffer_size",
+    "image_max_array_size", "partition_max_sub_devices",
+];
+
+/// Device fields listing capabilities, where lost entries may break existing
+/// kernels.
+pub const CAPABILITIES: &[&str] = &["extensions", "built_in_kernels", "image_formats"];
+
+/// Device fields identifying the driver and OpenCL version.
+pub const VERSIONS: &[&str] = &["version", "driver_version", "opencl_c_version", "profile"];
+
+/// Fields which differ on every run and are never compared.
+const IGNORED: &[&str] = &["objects", "objects_error"];
+
+/// Fields pulled up from the object inventory, compared only if both
+/// inventories have one (objects may fail to be created on either run).
+const OBJECT_FIELDS: &[&str] = &["image_formats"];
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Incompatible,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Incompatible => "INCOMPATIBLE",
+        })
+    }
+}
+
+
+/// One differing field (or a device added or removed).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub device: String,
+    /// The field name, or `None` for a device added or removed.
+    pub field: Option<String>,
+    pub old: String,
+    pub new: String,
+    pub severity: Severity,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.field {
+            Some(ref field) => write!(f, "[{:<12}] {}: {}: {} -> {}", self.severity, self.device,
+                field, self.old, self.new),
+            None => write!(f, "[{:<12}] {}: {} -> {}", self.severity, self.device, self.old,
+                self.new),
+        }
+    }
+}
+
+
+/// The differences between a snapshot and a later inventory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InventoryDiff {
+    pub changes: Vec<Change>,
+}
+
+impl InventoryDiff {
+    /// Compares `current` against `snapshot`.
+    pub fn new(snapshot: &Inventory, current: &Inventory) -> InventoryDiff {
+        let old = device_map(snapshot);
+        let new = device_map(current);
+        let mut changes = Vec::new();
+
+        for (key, old_device) in old.iter() {
+            match new.get(key) {
+                Some(new_device) => diff_device(key, old_device, new_device, &mut changes),
+                None => changes.push(Change {
+                    device: key.clone(),
+                    field: None,
+                    old: "present".to_owned(),
+                    new: "missing".to_owned(),
+                    severity: Severity::Incompatible,
+                }),
+            }
+        }
+
+        for key in new.keys().filter(|key| !old.contains_key(*key)) {
+            changes.push(Change {
+                device: key.clone(),
+                field: None,
+                old: "missing".to_owned(),
+                new: "present".to_owned(),
+                severity: Severity::Info,
+            });
+        }
+
+        InventoryDiff { changes }
+    }
+
+    /// The most severe change, if any.
+    pub fn severity(&self) -> Option<Severity> {
+        self.changes.iter().map(|change| change.severity).max()
+    }
+
+    /// Returns `false` if any change may break kernels.
+    pub fn is_compatible(&self) -> bool {
+        self.severity() != Some(Severity::Incompatible)
+    }
+
+    /// The changes at least as severe as `min`.
+    pub fn filter(&self, min: Severity) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(move |change| change.severity >= min)
+    }
+}
+
+impl fmt::Display for InventoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Devices keyed by "Platform / Vendor Name", numbered if several share a
+/// name, with their fields as JSON (image formats pulled up from the object
+/// inventory).
+fn device_map(inventory: &Inventory) -> BTreeMap<String, Map<String, Value>> {
+    let mut map = BTreeMap::new();
+
+    for (platform, device) in inventory.devices() {
+        let base = format!("{} / {}", platform_name(platform), device.label());
+        let mut key = base.clone();
+        let mut idx = 1;
+        while map.contains_key(&key) {
+            idx += 1;
+            key = format!("{} #{}", base, idx);
+        }
+        map.insert(key, device_fields(device));
+    }
+    map
+}
+
+fn platform_name(platform: &PlatformInventory) -> String {
+    platform.name.ok().cloned().unwrap_or_else(|| "<unknown platform>".to_owned())
+}
+
+fn device_fields(device: &DeviceInventory) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(device) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    if let Some(ref objects) = device.objects {
+        if let Ok(formats) = serde_json::to_value(&objects.image_formats) {
+            fields.insert("image_formats".to_owned(), formats);
+        }
+    }
+    for field in IGNORED {
+        fields.remove(*field);
+    }
+    fields
+}
+
+
+static NULL: Value = Value::Null;
+
+/// Splits a serialized `Field` into its status and value.
+fn status(field: &Value) -> (&str, &Value) {
+    (field.get("status").and_then(|s| s.as_str()).unwrap_or(""),
+        field.get("value").unwrap_or(&NULL))
+}
+
+fn show(field: &Value) -> String {
+    match status(field) {
+        ("ok", &Value::String(ref s)) => s.clone(),
+        ("ok", value) => value.to_string(),
+        ("not_available", _) => "not available".to_owned(),
+        ("", _) => "missing".to_owned(),
+        (_, value) => format!("error ({})", value.as_str().unwrap_or("")),
+    }
+}
+
+fn items(value: &Value) -> Vec<String> {
+    value.as_array().map(|items| {
+        items.iter().map(|item| item.as_str().map(|s| s.to_owned())
+            .unwrap_or_else(|| item.to_string())).collect()
+    }).unwrap_or_default()
+}
+
+/// Returns `true` if `new` is lower than `old` (in any element, for
+/// arrays).
+fn is_lower(old: &Value, new: &Value) -> bool {
+    match (old, new) {
+        (&Value::Number(ref a), &Value::Number(ref b)) => match (a.as_u64(), b.as_u64()) {
+            (Some(a), Some(b)) => b < a,
+            _ => false,
+        },
+        (&Value::Array(ref a), &Value::Array(ref b)) => {
+            b.len() < a.len() || a.iter().zip(b.iter()).any(|(a, b)| is_lower(a, b))
+        },
+        _ => false,
+    }
+}
+
+fn diff_device(device: &str, old: &Map<String, Value>, new: &Map<String, Value>,
+        changes: &mut Vec<Change>)
+{
+    for (name, old_field) in old.iter() {
+        if OBJECT_FIELDS.contains(&name.as_str()) && !new.contains_key(name) { continue; }
+        let new_field = new.get(name).unwrap_or(&NULL);
+        if old_field == new_field { continue; }
+
+        let (old_status, old_value) = status(old_field);
+        let (new_status, new_value) = status(new_field);
+        let name = name.as_str();
+
+        if old_status == "ok" && new_status == "ok" && CAPABILITIES.contains(&name) {
+            let (old_items, new_items) = (items(old_value), items(new_value));
+            let lost: Vec<_> = old_items.iter().filter(|i| !new_items.contains(i)).collect();
+            let gained: Vec<_> = new_items.iter().filter(|i| !old_items.contains(i)).collect();
+
+            let mut push = |list: Vec<&String>, old: &str, new: &str, severity| {
+                for item in list {
+                    changes.push(Change {
+                        device: device.to_owned(),
+                        field: Some(format!("{}[{}]", name, item)),
+                        old: old.to_owned(),
+                        new: new.to_owned(),
+                        severity: severity,
+                    });
+                }
+            };
+            push(lost, "present", "lost", Severity::Incompatible);
+            push(gained, "missing", "added", Severity::Info);
+            continue;
+        }
+
+        let severity = if old_status == "ok" && new_status != "ok" {
+            Severity::Incompatible
+        } else if old_status == "ok" && LIMITS.contains(&name) && is_lower(old_value, new_value) {
+            Severity::Incompatible
+        } else if VERSIONS.contains(&name) {
+            Severity::Warning
+        } else {
+            Severity::Info
+        };
+
+        changes.push(Change {
+            device: device.to_owned(),
+            field: Some(name.to_owned()),
+            old: show(old_field),
+            new: show(new_field),
+            severity: severity,
+        });
+    }
+}
@@ -0,0 +1,137 @@
+//! A single queried value.
+
+use serde::{Serialize, Deserialize, Deserializer, forward_to_deserialize_any};
+use serde::de::{self, DeserializeOwned, Visitor, value::Error as DeError};
+use ocl::core::{Error as OclCoreError, OpenclVersion, Status};
+
+
+/// The result of one info query.
+///
+/// Serializes as `{"status": "ok", "value": ...}`,
+/// `{"status": "not_available"}` or `{"status": "error", "value": "..."}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "value", rename_all = "snake_case")]
+pub enum Field<T> {
+    Ok(T),
+    /// The implementation does not support this query (e.g. kernel argument
+    /// info for programs built from binaries, or a query newer than the
+    /// device's OpenCL version, see `optional_field!`).
+    NotAvailable,
+    Error(String),
+}
+
+impl<T> Field<T> {
+    /// Classifies a query error.
+    pub fn from_error(err: &OclCoreError) -> Field<T> {
+        Field::from_error_if(err, true)
+    }
+
+    /// Classifies an error of a query which the object may not support:
+    /// unless `supported`, the `CL_INVALID_VALUE` an unknown query fails with
+    /// means `NotAvailable`. Anywhere else it is a real error.
+    pub fn from_error_if(err: &OclCoreError, supported: bool) -> Field<T> {
+        match err.api_status() {
+            Some(Status::CL_KERNEL_ARG_INFO_NOT_AVAILABLE)
+                | Some(Status::CL_PROFILING_INFO_NOT_AVAILABLE) => Field::NotAvailable,
+            Some(Status::CL_INVALID_VALUE) if !supported => Field::NotAvailable,
+            _ => Field::Error(err.to_string()),
+        }
+    }
+
+    pub fn ok(&self) -> Option<&T> {
+        match *self {
+            Field::Ok(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        match *self {
+            Field::Error(_) => true,
+            _ => false,
+        }
+    }
+}
+
+
+/// Whether an object of OpenCL `version` (if known) supports the queries
+/// introduced in `major.minor`.
+pub fn since(version: Option<OpenclVersion>, major: u16, minor: u16) -> bool {
+    version.map_or(true, |version| version >= OpenclVersion::new(major, minor))
+}
+
+
+/// Turns an info query into a `Field`, unpacking `$variant` and passing its
+/// contents through `$map` (if given).
+macro_rules! field {
+    ( $query:expr, $variant:path ) => {
+        field!($query, $variant, |value| value)
+    };
+    ( $query:expr, $variant:path, $map:expr ) => {
+        optional_field!(true, $query, $variant, $map)
+    };
+}
+
+/// `field!` for a query the object may not support, such as one newer than
+/// its OpenCL version (see `since`). Unless `$supported`, an invalid value
+/// error is `NotAvailable`.
+macro_rules! optional_field {
+    ( $supported:expr, $query:expr, $variant:path ) => {
+        optional_field!($supported, $query, $variant, |value| value)
+    };
+    ( $supported:expr, $query:expr, $variant:path, $map:expr ) => {
+        match $query {
+            Ok($variant(value)) => crate::field::Field::Ok(($map)(value)),
+            Ok(other) => crate::field::Field::Error(format!("Unexpected info result: {:?}", other)),
+            Err(err) => crate::field::Field::from_error_if(&err, $supported),
+        }
+    };
+}
+
+/// `field!` for values kept as their `Debug` representation (enums and
+/// flags).
+macro_rules! field_debug {
+    ( $query:expr, $variant:path ) => {
+        field!($query, $variant, |value| format!("{:?}", value))
+    };
+}
+
+/// `optional_field!` for values kept as their `Debug` representation.
+macro_rules! optional_field_debug {
+    ( $supported:expr, $query:expr, $variant:path ) => {
+        optional_field!($supported, $query, $variant, |value| format!("{:?}", value))
+    };
+}
+
+
+/// A deserializer which only records the field names `deserialize_struct`
+/// is called with.
+struct FieldNames<'n>(&'n mut &'static [&'static str]);
+
+impl<'de, 'n> Deserializer<'de> for FieldNames<'n> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, DeError> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str,
+            fields: &'static [&'static str], _: V) -> Result<V::Value, DeError>
+    {
+        *self.0 = fields;
+        Err(de::Error::custom("field names only"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The serialized field names of an inventory struct (e.g. to check a
+/// field name before anything is queried).
+pub fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut names: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut names));
+    names
+}
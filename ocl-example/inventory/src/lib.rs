@@ -0,0 +1,150 @@
+//! A typed, serializable inventory of OpenCL platforms, devices and objects.
+//!
+//! `Inventory::gather` queries the same information the `info` examples
+//! print, but into plain structs which serialize to JSON or YAML:
+//!
+//! ```ignore
+//! let inventory = Inventory::gather(&Options::default())?;
+//! println!("{}", inventory.to_yaml()?);
+//! ```
+//!
+//! Every queried value is a `Field`, which keeps queries the implementation
+//! does not support (`Field::NotAvailable`) apart from queries that failed
+//! (`Field::Error`). Opaque handles (contexts, queues, pointers) are left
+//! out since they mean nothing outside of the process.
+//!
+//! A saved inventory serves as a snapshot which later runs (e.g. after a
+//! driver update) can be checked against with `Inventory::diff`.
+//!
+//! `KernelReport` builds any program on every device and lists the resource
+//! usage and argument info of each of its kernels.
+//!
+
+extern crate ocl;
+extern crate image_fallback;
+extern crate serde;
+extern crate serde_json;
+extern crate serde_yaml;
+
+#[macro_use] mod field;
+mod device;
+mod objects;
+mod diff;
+mod report;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use ocl::{Platform, Device};
+use ocl::error::Result as OclResult;
+
+pub use crate::field::{Field, field_names};
+pub use crate::device::{PlatformInventory, DeviceInventory};
+pub use crate::objects::{ObjectInventory, ContextInventory, QueueInventory, MemInventory,
+    ImageInventory, SamplerInventory, ProgramInventory, KernelInventory, KernelArgInventory,
+    EventInventory};
+pub use crate::diff::{InventoryDiff, Change, Severity};
+pub use crate::report::{KernelReport, DeviceKernels};
+
+
+/// What to gather.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Create a context, queue, buffer, image, sampler, program, kernel and
+    /// event on each device and inventory those too.
+    pub objects: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options { objects: true }
+    }
+}
+
+
+/// Everything found on this machine.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    pub platforms: Vec<PlatformInventory>,
+}
+
+impl Inventory {
+    /// Queries every platform and device.
+    pub fn gather(options: &Options) -> OclResult<Inventory> {
+        let mut platforms = Vec::new();
+
+        for platform in Platform::list() {
+            let mut inventory = PlatformInventory::query(platform);
+
+            for device in Device::list_all(&platform)? {
+                let mut device_inventory = DeviceInventory::query(device);
+                if options.objects {
+                    match ObjectInventory::query(platform, device) {
+                        Ok(objects) => device_inventory.objects = Some(objects),
+                        Err(err) => device_inventory.objects_error = Some(err.to_string()),
+                    }
+                }
+                inventory.devices.push(device_inventory);
+            }
+            platforms.push(inventory);
+        }
+        Ok(Inventory { platforms })
+    }
+
+    /// Loads an inventory saved as JSON or YAML (by extension).
+    pub fn load<P: AsRef<Path>>(path: P) -> OclResult<Inventory> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| format!("Unable to open inventory '{}': {}",
+            path.display(), err))?;
+        let reader = BufReader::new(file);
+
+        let res = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_reader(reader)
+                .map_err(|err| err.to_string()),
+            _ => serde_json::from_reader(reader).map_err(|err| err.to_string()),
+        };
+        res.map_err(|err| format!("Invalid inventory '{}': {}", path.display(), err).into())
+    }
+
+    pub fn to_json(&self) -> OclResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|err| format!("Unable to serialize inventory: {}", err).into())
+    }
+
+    pub fn to_yaml(&self) -> OclResult<String> {
+        serde_yaml::to_string(self)
+            .map_err(|err| format!("Unable to serialize inventory: {}", err).into())
+    }
+
+    /// Compares `current` against this inventory.
+    pub fn diff(&self, current: &Inventory) -> InventoryDiff {
+        InventoryDiff::new(self, current)
+    }
+
+    /// Every device, with its platform.
+    pub fn devices(&self) -> impl Iterator<Item = (&PlatformInventory, &DeviceInventory)> {
+        self.platforms.iter()
+            .flat_map(|platform| platform.devices.iter().map(move |device| (platform, device)))
+    }
+
+    /// The number of fields which failed to query (and of devices on which
+    /// objects failed to be created).
+    pub fn error_count(&self) -> usize {
+        let json = serde_json::to_value(self).unwrap_or(serde_json::Value::Null);
+        let objects = self.devices().filter(|(_, device)| device.objects_error.is_some()).count();
+        count_errors(&json) + objects
+    }
+}
+
+fn count_errors(value: &serde_json::Value) -> usize {
+    use serde_json::Value;
+    match *value {
+        Value::Object(ref map) => {
+            let own = (map.get("status") == Some(&Value::from("error"))) as usize;
+            own + map.values().map(count_errors).sum::<usize>()
+        },
+        Value::Array(ref items) => items.iter().map(count_errors).sum(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,160 @@
+//! Prints the inventory of every platform and device as JSON or YAML, checks
+//! it against a saved snapshot, or reports the resource usage of a program's
+//! kernels on each device.
+
+extern crate ocl;
+extern crate inventory;
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::process;
+use ocl::Result as OclResult;
+use inventory::{Inventory, Options, Severity, KernelReport};
+
+static USAGE: &'static str = "\
+Usage: inventory [COMMAND]
+
+Commands:
+    print [OPTIONS]                 Print the inventory (default)
+        --yaml                      Print YAML instead of JSON
+        --no-objects                Only query platforms and devices, don't
+                                    create a context, queue, buffer, etc. on
+                                    each device
+        --out <FILE>                Write to FILE instead of stdout (e.g. to
+                                    save a snapshot)
+    diff <SNAPSHOT> [CURRENT] [--all]
+                                    Compare the devices of this machine (or
+                                    those saved in CURRENT) against SNAPSHOT.
+                                    Prints warnings and incompatible changes
+                                    (or everything with `--all`) and exits
+                                    with status 1 if any change may break
+                                    kernels which worked with SNAPSHOT.
+    report <FILE.cl> [OPTIONS]      Build FILE.cl for each device and list the
+                                    work-group and argument info of every
+                                    kernel
+        --build-options <OPTS>      Compiler options (`-cl-kernel-arg-info`
+                                    is always added)
+        --platform <IDX>            Only the platform with index IDX
+        --device <IDX>              Only the device with index IDX
+        --json                      Print JSON instead of text";
+
+fn print(args: &[String]) -> OclResult<()> {
+    let mut yaml = false;
+    let mut options = Options::default();
+    let mut out = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--yaml" => yaml = true,
+            "--no-objects" => options.objects = false,
+            "--out" => out = Some(args.next().ok_or("Missing value for '--out'.")?),
+            other => return Err(format!("Unknown argument: '{}'\n\n{}", other, USAGE).into()),
+        }
+    }
+
+    let inventory = Inventory::gather(&options)?;
+    let text = if yaml { inventory.to_yaml()? } else { inventory.to_json()? };
+
+    match out {
+        Some(path) => {
+            fs::write(path, text).map_err(|err| format!("Unable to write '{}': {}", path, err))?;
+            eprintln!("Wrote the inventory of {} devices to '{}' ({} failed queries).",
+                inventory.devices().count(), path, inventory.error_count());
+        },
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+/// Returns `false` on incompatible changes.
+fn diff(args: &[String]) -> OclResult<bool> {
+    let all = args.iter().any(|arg| arg == "--all");
+    let files: Vec<_> = args.iter().filter(|arg| *arg != "--all").collect();
+
+    let (snapshot, current) = match files.as_slice() {
+        [snapshot] => (Inventory::load(snapshot)?, Inventory::gather(&Options::default())?),
+        [snapshot, current] => (Inventory::load(snapshot)?, Inventory::load(current)?),
+        _ => return Err(USAGE.into()),
+    };
+
+    let diff = snapshot.diff(&current);
+    let min = if all { Severity::Info } else { Severity::Warning };
+
+    for change in diff.filter(min) {
+        println!("{}", change);
+    }
+
+    match diff.severity() {
+        None => println!("No changes."),
+        Some(severity) => println!("{} change(s), most severe: {}.", diff.changes.len(),
+            severity),
+    }
+    Ok(diff.is_compatible())
+}
+
+fn report(args: &[String]) -> OclResult<()> {
+    let mut path = None;
+    let mut build_options = String::new();
+    let mut platform_idx = None;
+    let mut device_idx = None;
+    let mut json = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--build-options" => build_options = args.next()
+                .ok_or("Missing value for '--build-options'.")?.clone(),
+            "--platform" => platform_idx = Some(args.next().and_then(|s| s.parse().ok())
+                .ok_or("'--platform' needs an index.")?),
+            "--device" => device_idx = Some(args.next().and_then(|s| s.parse().ok())
+                .ok_or("'--device' needs an index.")?),
+            "--json" => json = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            other => return Err(format!("Unknown argument: '{}'\n\n{}", other, USAGE).into()),
+        }
+    }
+
+    let path = path.ok_or(USAGE)?;
+    let src = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read '{}': {}", path, err))?;
+
+    let report = KernelReport::build(&src, &build_options, |p, d| {
+        platform_idx.map(|idx| idx == p).unwrap_or(true)
+            && device_idx.map(|idx| idx == d).unwrap_or(true)
+    })?;
+
+    if json {
+        let text = serde_json::to_string_pretty(&report)
+            .map_err(|err| format!("Unable to serialize report: {}", err))?;
+        println!("{}", text);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let res = match args.first().map(|s| s.as_str()) {
+        None => print(&[]).map(|_| true),
+        Some("print") => print(&args[1..]).map(|_| true),
+        Some("diff") => diff(&args[1..]),
+        Some("report") => report(&args[1..]).map(|_| true),
+        Some("--help") | Some("-h") => { println!("{}", USAGE); Ok(true) },
+        // Options without a command are for `print`:
+        Some(arg) if arg.starts_with("--") => print(&args).map(|_| true),
+        Some(other) => Err(format!("Unknown command: '{}'\n\n{}", other, USAGE).into()),
+    };
+
+    match res {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            println!("{}", err);
+            process::exit(2);
+        },
+    }
+}
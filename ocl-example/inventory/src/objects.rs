@@ -0,0 +1,411 @@
+//! Inventories of objects created on a device.
+
+use serde::{Serialize, Deserialize};
+use ocl::{core, Platform, Device, Context, Queue, Buffer, Image, Sampler, Program, Kernel, Event};
+use ocl::core::{Mem as MemCore, Kernel as KernelCore, ContextInfo, CommandQueueInfo, MemInfo,
+    ImageInfo, SamplerInfo, ProgramInfo, ProgramBuildInfo, KernelInfo, KernelArgInfo,
+    KernelWorkGroupInfo, EventInfo, ProfilingInfo, OpenclVersion};
+use ocl::enums::MemObjectType;
+use ocl::error::Result as OclResult;
+use ocl::flags::MemFlags;
+use image_fallback::FallbackImage;
+use crate::field::{self, Field};
+
+const WORK_SIZE: usize = 1024;
+
+static SRC: &'static str = r#"
+    __kernel void multiply(float coeff, __global float* buffer) {
+        buffer[get_global_id(0)] *= coeff;
+    }
+"#;
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContextInventory {
+    pub reference_count: Field<u32>,
+    pub num_devices: Field<u32>,
+    pub properties: Field<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueueInventory {
+    pub reference_count: Field<u32>,
+    pub properties: Field<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemInventory {
+    pub mem_type: Field<String>,
+    pub flags: Field<String>,
+    pub size: Field<usize>,
+    pub map_count: Field<u32>,
+    pub reference_count: Field<u32>,
+    pub offset: Field<usize>,
+}
+
+impl MemInventory {
+    fn query<M: AsRef<MemCore>>(mem: &M, version: Option<OpenclVersion>) -> MemInventory {
+        use ocl::core::MemInfoResult as R;
+        let info = |kind| core::get_mem_object_info(mem, kind);
+
+        MemInventory {
+            mem_type: field_debug!(info(MemInfo::Type), R::Type),
+            flags: field_debug!(info(MemInfo::Flags), R::Flags),
+            size: field!(info(MemInfo::Size), R::Size),
+            map_count: field!(info(MemInfo::MapCount), R::MapCount),
+            reference_count: field!(info(MemInfo::ReferenceCount), R::ReferenceCount),
+            offset: optional_field!(field::since(version, 1, 1), info(MemInfo::Offset), R::Offset),
+        }
+    }
+}
+
+/// An image. Images emulated with a buffer (see `image_fallback`) say why,
+/// report their buffer layout and have no array size, mip levels or samples.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImageInventory {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emulated: Option<String>,
+    pub format: Field<String>,
+    pub element_size: Field<usize>,
+    pub row_pitch: Field<usize>,
+    pub slice_pitch: Field<usize>,
+    pub width: Field<usize>,
+    pub height: Field<usize>,
+    pub depth: Field<usize>,
+    pub array_size: Field<usize>,
+    pub num_mip_levels: Field<u32>,
+    pub num_samples: Field<u32>,
+    pub memory: MemInventory,
+}
+
+impl ImageInventory {
+    fn query(image: &FallbackImage<u8>, version: Option<OpenclVersion>) -> ImageInventory {
+        use ocl::core::ImageInfoResult as R;
+
+        let image = match image.as_image() {
+            Some(image) => image,
+            None => return ImageInventory::emulated(image, version),
+        };
+
+        let info = |kind| core::get_image_info(image, kind);
+        let since_1_2 = field::since(version, 1, 2);
+
+        ImageInventory {
+            emulated: None,
+            format: field_debug!(info(ImageInfo::Format), R::Format),
+            element_size: field!(info(ImageInfo::ElementSize), R::ElementSize),
+            row_pitch: field!(info(ImageInfo::RowPitch), R::RowPitch),
+            slice_pitch: field!(info(ImageInfo::SlicePitch), R::SlicePitch),
+            width: field!(info(ImageInfo::Width), R::Width),
+            height: field!(info(ImageInfo::Height), R::Height),
+            depth: field!(info(ImageInfo::Depth), R::Depth),
+            array_size: optional_field!(since_1_2, info(ImageInfo::ArraySize), R::ArraySize),
+            num_mip_levels: optional_field!(since_1_2, info(ImageInfo::NumMipLevels),
+                R::NumMipLevels),
+            num_samples: optional_field!(since_1_2, info(ImageInfo::NumSamples), R::NumSamples),
+            memory: MemInventory::query(image, version),
+        }
+    }
+
+    /// Describes a buffer-backed image from its layout.
+    fn emulated(image: &FallbackImage<u8>, version: Option<OpenclVersion>) -> ImageInventory {
+        let (buffer, layout) = image.as_buffer().expect("image is neither real nor emulated");
+
+        ImageInventory {
+            emulated: image.fallback_reason().map(|reason| reason.to_owned()),
+            format: Field::Ok(format!("{:?}", image.format())),
+            element_size: Field::Ok(layout.channels),
+            row_pitch: Field::Ok(layout.row_pitch),
+            slice_pitch: Field::Ok(layout.slice_pitch),
+            width: Field::Ok(layout.dims[0]),
+            height: Field::Ok(layout.dims[1]),
+            depth: Field::Ok(layout.dims[2]),
+            array_size: Field::NotAvailable,
+            num_mip_levels: Field::NotAvailable,
+            num_samples: Field::NotAvailable,
+            memory: MemInventory::query(buffer, version),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SamplerInventory {
+    pub reference_count: Field<u32>,
+    pub normalized_coords: Field<bool>,
+    pub addressing_mode: Field<String>,
+    pub filter_mode: Field<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProgramInventory {
+    pub reference_count: Field<u32>,
+    pub num_devices: Field<u32>,
+    pub binary_sizes: Field<Vec<usize>>,
+    pub num_kernels: Field<usize>,
+    pub kernel_names: Field<Vec<String>>,
+    pub build_status: Field<String>,
+    pub build_options: Field<String>,
+    pub build_log: Field<String>,
+    pub binary_type: Field<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KernelArgInventory {
+    pub name: Field<String>,
+    pub type_name: Field<String>,
+    pub address_qualifier: Field<String>,
+    pub access_qualifier: Field<String>,
+    pub type_qualifier: Field<String>,
+}
+
+impl KernelArgInventory {
+    fn not_available() -> KernelArgInventory {
+        KernelArgInventory {
+            name: Field::NotAvailable,
+            type_name: Field::NotAvailable,
+            address_qualifier: Field::NotAvailable,
+            access_qualifier: Field::NotAvailable,
+            type_qualifier: Field::NotAvailable,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KernelInventory {
+    pub function_name: Field<String>,
+    pub num_args: Field<u32>,
+    pub reference_count: Field<u32>,
+    pub attributes: Field<String>,
+    /// Not available before OpenCL 1.2, or for programs built without
+    /// `-cl-kernel-arg-info` on some implementations.
+    pub args: Vec<KernelArgInventory>,
+    pub work_group_size: Field<usize>,
+    pub compile_work_group_size: Field<Vec<usize>>,
+    pub local_mem_size: Field<u64>,
+    pub preferred_work_group_size_multiple: Field<usize>,
+    pub private_mem_size: Field<u64>,
+    /// Only available for custom devices and built-in kernels.
+    pub global_work_size: Field<Vec<usize>>,
+}
+
+impl KernelInventory {
+    /// Queries a kernel's info, argument info and work-group info for
+    /// `device`.
+    pub fn query(kernel: &KernelCore, device: Device) -> OclResult<KernelInventory> {
+        use ocl::core::KernelInfoResult as R;
+        use ocl::core::KernelWorkGroupInfoResult as W;
+        let version = device.version().ok();
+        let since_1_2 = field::since(version, 1, 2);
+        let info = |kind| core::get_kernel_info(kernel, kind);
+        let wg_info = |kind| core::get_kernel_work_group_info(kernel, &device, kind);
+
+        let num_args = field!(info(KernelInfo::NumArgs), R::NumArgs);
+        let args = (0..num_args.ok().cloned().unwrap_or(0)).map(|idx| {
+            use ocl::core::KernelArgInfoResult as A;
+
+            // `ocl` refuses the query itself on older devices:
+            if !since_1_2 {
+                return KernelArgInventory::not_available();
+            }
+            let arg_info = |kind| core::get_kernel_arg_info(kernel, idx, kind,
+                version.as_ref().map(std::slice::from_ref));
+
+            KernelArgInventory {
+                name: field!(arg_info(KernelArgInfo::Name), A::Name),
+                type_name: field!(arg_info(KernelArgInfo::TypeName), A::TypeName),
+                address_qualifier: field_debug!(arg_info(KernelArgInfo::AddressQualifier),
+                    A::AddressQualifier),
+                access_qualifier: field_debug!(arg_info(KernelArgInfo::AccessQualifier),
+                    A::AccessQualifier),
+                type_qualifier: field_debug!(arg_info(KernelArgInfo::TypeQualifier),
+                    A::TypeQualifier),
+            }
+        }).collect();
+
+        Ok(KernelInventory {
+            function_name: field!(info(KernelInfo::FunctionName), R::FunctionName),
+            num_args: num_args,
+            reference_count: field!(info(KernelInfo::ReferenceCount), R::ReferenceCount),
+            attributes: optional_field!(since_1_2, info(KernelInfo::Attributes), R::Attributes),
+            args: args,
+            work_group_size: field!(wg_info(KernelWorkGroupInfo::WorkGroupSize),
+                W::WorkGroupSize),
+            compile_work_group_size: field!(wg_info(KernelWorkGroupInfo::CompileWorkGroupSize),
+                W::CompileWorkGroupSize, |size: [usize; 3]| size.to_vec()),
+            local_mem_size: field!(wg_info(KernelWorkGroupInfo::LocalMemSize),
+                W::LocalMemSize),
+            preferred_work_group_size_multiple: field!(
+                wg_info(KernelWorkGroupInfo::PreferredWorkGroupSizeMultiple),
+                W::PreferredWorkGroupSizeMultiple),
+            private_mem_size: field!(wg_info(KernelWorkGroupInfo::PrivateMemSize),
+                W::PrivateMemSize),
+            global_work_size: optional_field!(false, wg_info(KernelWorkGroupInfo::GlobalWorkSize),
+                W::GlobalWorkSize, |size: [usize; 3]| size.to_vec()),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventInventory {
+    pub command_type: Field<String>,
+    pub command_execution_status: Field<String>,
+    pub reference_count: Field<u32>,
+    pub queued_ns: Field<u64>,
+    pub submit_ns: Field<u64>,
+    pub start_ns: Field<u64>,
+    pub end_ns: Field<u64>,
+}
+
+
+/// One of each object type, created on a single device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObjectInventory {
+    pub context: ContextInventory,
+    pub queue: QueueInventory,
+    pub buffer: MemInventory,
+    pub image: ImageInventory,
+    /// Supported read-write 2D image formats, as "Order/DataType".
+    pub image_formats: Field<Vec<String>>,
+    pub sampler: SamplerInventory,
+    pub program: ProgramInventory,
+    pub kernel: KernelInventory,
+    /// A kernel launch.
+    pub event: EventInventory,
+}
+
+impl ObjectInventory {
+    /// Creates a context, queue, buffer, image, sampler, program and kernel
+    /// on `device`, runs the kernel once and inventories all of them.
+    pub fn query(platform: Platform, device: Device) -> OclResult<ObjectInventory> {
+        let context = Context::builder().platform(platform).devices(device).build()?;
+        let queue = Queue::new(&context, device, Some(core::QUEUE_PROFILING_ENABLE))?;
+        let buffer = Buffer::<f32>::builder()
+            .queue(queue.clone())
+            .len(WORK_SIZE)
+            .build()?;
+        let image = FallbackImage::<u8>::builder()
+            .dims(WORK_SIZE)
+            .queue(queue.clone())
+            .build()?;
+        let sampler = Sampler::with_defaults(&context)?;
+        let program = Program::builder()
+            .devices(device)
+            .src(SRC)
+            .build(&context)?;
+        let kernel = Kernel::builder()
+            .name("multiply")
+            .program(&program)
+            .queue(queue.clone())
+            .global_work_size(WORK_SIZE)
+            .arg(10.0f32)
+            .arg(&buffer)
+            .build()?;
+
+        let mut event = Event::empty();
+        unsafe { kernel.cmd().enew(&mut event).enq()?; }
+        event.wait_for()?;
+
+        let version = device.version().ok();
+        Ok(ObjectInventory {
+            context: query_context(&context, version),
+            queue: query_queue(&queue),
+            buffer: MemInventory::query(&buffer, version),
+            image: ImageInventory::query(&image, version),
+            image_formats: query_image_formats(&context),
+            sampler: query_sampler(&sampler),
+            program: query_program(&program, device, version),
+            kernel: KernelInventory::query(&kernel, device)?,
+            event: query_event(&event),
+        })
+    }
+}
+
+
+fn query_context(context: &Context, version: Option<OpenclVersion>) -> ContextInventory {
+    use ocl::core::ContextInfoResult as R;
+    let info = |kind| core::get_context_info(context, kind);
+
+    ContextInventory {
+        reference_count: field!(info(ContextInfo::ReferenceCount), R::ReferenceCount),
+        num_devices: optional_field!(field::since(version, 1, 1), info(ContextInfo::NumDevices),
+            R::NumDevices),
+        properties: field_debug!(info(ContextInfo::Properties), R::Properties),
+    }
+}
+
+fn query_queue(queue: &Queue) -> QueueInventory {
+    use ocl::core::CommandQueueInfoResult as R;
+    let info = |kind| core::get_command_queue_info(queue, kind);
+
+    QueueInventory {
+        reference_count: field!(info(CommandQueueInfo::ReferenceCount), R::ReferenceCount),
+        properties: field_debug!(info(CommandQueueInfo::Properties), R::Properties),
+    }
+}
+
+fn query_image_formats(context: &Context) -> Field<Vec<String>> {
+    match Image::<u8>::supported_formats(context, MemFlags::new().read_write(),
+            MemObjectType::Image2d) {
+        Ok(formats) => Field::Ok(formats.iter()
+            .filter_map(|format| format.as_ref().ok())
+            .map(|format| format!("{:?}/{:?}", format.channel_order, format.channel_data_type))
+            .collect()),
+        Err(err) => Field::Error(err.to_string()),
+    }
+}
+
+fn query_sampler(sampler: &Sampler) -> SamplerInventory {
+    use ocl::core::SamplerInfoResult as R;
+    let info = |kind| core::get_sampler_info(sampler, kind);
+
+    SamplerInventory {
+        reference_count: field!(info(SamplerInfo::ReferenceCount), R::ReferenceCount),
+        normalized_coords: field!(info(SamplerInfo::NormalizedCoords), R::NormalizedCoords),
+        addressing_mode: field_debug!(info(SamplerInfo::AddressingMode), R::AddressingMode),
+        filter_mode: field_debug!(info(SamplerInfo::FilterMode), R::FilterMode),
+    }
+}
+
+fn query_program(program: &Program, device: Device, version: Option<OpenclVersion>)
+        -> ProgramInventory
+{
+    use ocl::core::ProgramInfoResult as R;
+    use ocl::core::ProgramBuildInfoResult as B;
+    let since_1_2 = field::since(version, 1, 2);
+    let info = |kind| core::get_program_info(program, kind);
+    let build_info = |kind| core::get_program_build_info(program, &device, kind);
+
+    ProgramInventory {
+        reference_count: field!(info(ProgramInfo::ReferenceCount), R::ReferenceCount),
+        num_devices: field!(info(ProgramInfo::NumDevices), R::NumDevices),
+        binary_sizes: field!(info(ProgramInfo::BinarySizes), R::BinarySizes),
+        num_kernels: optional_field!(since_1_2, info(ProgramInfo::NumKernels), R::NumKernels),
+        kernel_names: optional_field!(since_1_2, info(ProgramInfo::KernelNames), R::KernelNames,
+            |names: String| names.split(';').filter(|s| !s.is_empty())
+                .map(|s| s.to_owned()).collect()),
+        build_status: field_debug!(build_info(ProgramBuildInfo::BuildStatus), B::BuildStatus),
+        build_options: field!(build_info(ProgramBuildInfo::BuildOptions), B::BuildOptions),
+        build_log: field!(build_info(ProgramBuildInfo::BuildLog), B::BuildLog,
+            |log: String| log.trim().to_owned()),
+        binary_type: optional_field_debug!(since_1_2, build_info(ProgramBuildInfo::BinaryType),
+            B::BinaryType),
+    }
+}
+
+fn query_event(event: &Event) -> EventInventory {
+    use ocl::core::EventInfoResult as R;
+    use ocl::core::ProfilingInfoResult as P;
+    let info = |kind| core::get_event_info(event, kind);
+    let profiling = |kind| core::get_event_profiling_info(event, kind);
+
+    EventInventory {
+        command_type: field_debug!(info(EventInfo::CommandType), R::CommandType),
+        command_execution_status: field_debug!(info(EventInfo::CommandExecutionStatus),
+            R::CommandExecutionStatus),
+        reference_count: field!(info(EventInfo::ReferenceCount), R::ReferenceCount),
+        queued_ns: field!(profiling(ProfilingInfo::Queued), P::Queued),
+        submit_ns: field!(profiling(ProfilingInfo::Submit), P::Submit),
+        start_ns: field!(profiling(ProfilingInfo::Start), P::Start),
+        end_ns: field!(profiling(ProfilingInfo::End), P::End),
+    }
+}
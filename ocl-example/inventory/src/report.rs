@@ -0,0 +1,179 @@
+//! Per-device resource usage of every kernel in a program.
+//!
+//! Builds a program source for each device and lists each kernel's
+//! work-group info (`WorkGroupSize`, `CompileWorkGroupSize`, `LocalMemSize`,
+//! `PrivateMemSize`, `PreferredWorkGroupSizeMultiple`) and argument info, so
+//! that occupancy and argument qualifiers can be checked without a custom
+//! binary. Argument info is only available if the program was built with
+//! `-cl-kernel-arg-info` on OpenCL 1.2+ devices. The option is added to the
+//! build options for those devices only, since older ones reject it; their
+//! argument info is reported as not available.
+//!
+
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use ocl::{core, Platform, Device, Context, Program};
+use ocl::core::{ProgramInfo, ProgramInfoResult, OpenclVersion};
+use ocl::error::Result as OclResult;
+use crate::field::Field;
+use crate::objects::KernelInventory;
+
+/// Build option enabling argument info (`KernelArgInfo`).
+pub const ARG_INFO_OPTION: &str = "-cl-kernel-arg-info";
+
+
+/// The kernels of one program on one device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceKernels {
+    pub platform_idx: usize,
+    pub device_idx: usize,
+    pub device: String,
+    /// The options the program was built with on this device.
+    pub build_options: String,
+    /// The build error and log, if the program failed to build.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_error: Option<String>,
+    pub kernels: Vec<KernelInventory>,
+}
+
+/// The kernels of one program on every device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KernelReport {
+    /// The requested build options (see `DeviceKernels::build_options`).
+    pub build_options: String,
+    pub devices: Vec<DeviceKernels>,
+}
+
+impl KernelReport {
+    /// Builds `src` with `build_options` (plus `ARG_INFO_OPTION` on OpenCL
+    /// 1.2+ devices) for every device (or those `filter` accepts, by platform
+    /// and device index) and queries each kernel.
+    pub fn build<F>(src: &str, build_options: &str, filter: F) -> OclResult<KernelReport>
+            where F: Fn(usize, usize) -> bool
+    {
+        let build_options = build_options.trim();
+        let with_arg_info = if build_options.split_whitespace().any(|opt| opt == ARG_INFO_OPTION) {
+            build_options.to_owned()
+        } else {
+            format!("{} {}", build_options, ARG_INFO_OPTION).trim().to_owned()
+        };
+
+        let mut devices = Vec::new();
+        for (platform_idx, platform) in Platform::list().into_iter().enumerate() {
+            for (device_idx, device) in Device::list_all(&platform)?.into_iter().enumerate() {
+                if !filter(platform_idx, device_idx) { continue; }
+
+                let version = device.version().ok();
+                let options = match version {
+                    Some(version) if version >= OpenclVersion::new(1, 2) => with_arg_info.clone(),
+                    _ => build_options.to_owned(),
+                };
+
+                let mut entry = DeviceKernels {
+                    platform_idx,
+                    device_idx,
+                    device: format!("{} {}", device.vendor()?, device.name()?),
+                    build_options: options,
+                    build_error: None,
+                    kernels: Vec::new(),
+                };
+
+                match device_kernels(platform, device, version, src, &entry.build_options) {
+                    Ok(kernels) => entry.kernels = kernels,
+                    Err(err) => entry.build_error = Some(err.to_string()),
+                }
+                devices.push(entry);
+            }
+        }
+
+        Ok(KernelReport { build_options: build_options.to_owned(), devices })
+    }
+}
+
+fn device_kernels(platform: Platform, device: Device, version: Option<OpenclVersion>,
+        src: &str, options: &str) -> OclResult<Vec<KernelInventory>>
+{
+    let context = Context::builder().platform(platform).devices(device).build()?;
+    let program = Program::builder()
+        .devices(device)
+        .src(src)
+        .cmplr_opt(options)
+        .build(&context)?;
+
+    // `KernelNames` is OpenCL 1.2+, older devices get the names from the
+    // source:
+    let names = match version {
+        Some(version) if version >= OpenclVersion::new(1, 2) => {
+            match core::get_program_info(&program, ProgramInfo::KernelNames)? {
+                ProgramInfoResult::KernelNames(names) => names,
+                _ => return Err("Unexpected `KernelNames` info result.".into()),
+            }
+        },
+        _ => kernel_names_in(src).join(";"),
+    };
+
+    names.split(';').filter(|name| !name.is_empty()).map(|name| {
+        let kernel = core::create_kernel(&program, name)?;
+        KernelInventory::query(&kernel, device)
+    }).collect()
+}
+
+/// The names of the `__kernel` (or `kernel`) functions in `src`.
+fn kernel_names_in(src: &str) -> Vec<String> {
+    let tokens: Vec<_> = src
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    tokens.windows(3)
+        .filter(|window| (window[0] == "__kernel" || window[0] == "kernel")
+            && window[1] == "void")
+        .map(|window| window[2].to_owned())
+        .collect()
+}
+
+
+fn show<T: fmt::Debug>(field: &Field<T>) -> String {
+    match *field {
+        Field::Ok(ref value) => format!("{:?}", value).trim_matches('"').to_owned(),
+        Field::NotAvailable => "n/a".to_owned(),
+        Field::Error(ref err) => format!("error: {}", err),
+    }
+}
+
+impl fmt::Display for KernelReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Build options: '{}'", self.build_options)?;
+
+        for device in self.devices.iter() {
+            writeln!(f, "\nPlatform [{}] Device [{}]: {}", device.platform_idx, device.device_idx,
+                device.device)?;
+            if device.build_options != self.build_options {
+                writeln!(f, "    Built with: '{}'", device.build_options)?;
+            }
+
+            if let Some(ref err) = device.build_error {
+                writeln!(f, "    Build failed: {}", err)?;
+                continue;
+            }
+
+            for kernel in device.kernels.iter() {
+                writeln!(f, "    Kernel '{}':", show(&kernel.function_name))?;
+                writeln!(f, "        WorkGroupSize: {}", show(&kernel.work_group_size))?;
+                writeln!(f, "        CompileWorkGroupSize: {}",
+                    show(&kernel.compile_work_group_size))?;
+                writeln!(f, "        PreferredWorkGroupSizeMultiple: {}",
+                    show(&kernel.preferred_work_group_size_multiple))?;
+                writeln!(f, "        LocalMemSize: {}", show(&kernel.local_mem_size))?;
+                writeln!(f, "        PrivateMemSize: {}", show(&kernel.private_mem_size))?;
+
+                for (idx, arg) in kernel.args.iter().enumerate() {
+                    writeln!(f, "        Arg [{}] {}: {} {} (access: {}, qualifiers: {})", idx,
+                        show(&arg.name), show(&arg.address_qualifier), show(&arg.type_name),
+                        show(&arg.access_qualifier), show(&arg.type_qualifier))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Declarative device requirements for kernels.
+//!
+//! Kernels tend to assume things about the device they run on: an OpenCL C
+//! version, extensions such as `cl_khr_fp64`, enough local memory, a large
+//! enough work-group size, image support, or that their buffers fit in a
+//! single allocation. `Requirements` lists these per kernel so they can be
+//! checked against `DeviceInfo` before a `Program` is built, failing with a
+//! precise error instead of a build failure or a wrong result:
+//!
+//! ```ignore
+//! let reqs = Requirements::new("kernel_a")
+//!     .opencl_c(1, 2)
+//!     .extension("cl_khr_fp64")
+//!     .work_group_size(256)
+//!     .allocation::<Float4>("buffer pool", 1 << 24);
+//!
+//! // "Device 'Foo' can't run kernel 'kernel_a' because ..."
+//! reqs.check(device)?;
+//! ```
+//!
+//! Vector types (`int4`, `float4`, ...) are core OpenCL C and need no
+//! requirement unless their element type does (`double` and `half`, see
+//! `Requirements::fp64` and `Requirements::fp16`).
+//!
+
+extern crate ocl;
+
+use std::fmt;
+use std::mem;
+use ocl::{Device, OclPrm};
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::error::Result as OclResult;
+
+
+/// A single thing a kernel needs from a device.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Requirement {
+    /// A minimum OpenCL C version (major, minor).
+    OpenclCVersion(u16, u16),
+    /// A device extension, e.g. `cl_khr_fp64`.
+    Extension(String),
+    /// A minimum amount of local memory, in bytes.
+    LocalMemSize(u64),
+    /// A minimum `MaxWorkGroupSize`.
+    WorkGroupSize(usize),
+    /// Image support.
+    ImageSupport,
+    /// A buffer which must fit within `MaxMemAllocSize`.
+    Allocation { name: String, bytes: u64 },
+}
+
+impl Requirement {
+    /// Returns why `device` does not meet this requirement, or `None` if it
+    /// does.
+    pub fn unmet(&self, device: Device) -> OclResult<Option<String>> {
+        let reason = match *self {
+            Requirement::OpenclCVersion(major, minor) => {
+                // OpenCL 1.0 devices have no OpenCL C version to query:
+                let version = match device.info(DeviceInfo::OpenclCVersion) {
+                    Ok(DeviceInfoResult::OpenclCVersion(version)) => version,
+                    Ok(_) => return Err("Unexpected `OpenclCVersion` info result.".into()),
+                    Err(err) => return Ok(Some(format!("it needs OpenCL C {}.{} but its \
+                        OpenCL C version can't be queried ({})", major, minor, err))),
+                };
+                match parse_version(&version) {
+                    Some(found) if found >= (major, minor) => None,
+                    _ => Some(format!("it needs OpenCL C {}.{} but supports '{}'", major,
+                        minor, version.trim())),
+                }
+            },
+            Requirement::Extension(ref ext) => {
+                let extensions = match device.info(DeviceInfo::Extensions)? {
+                    DeviceInfoResult::Extensions(extensions) => extensions,
+                    _ => return Err("Unexpected `Extensions` info result.".into()),
+                };
+                if extensions.split_whitespace().any(|e| e == ext) {
+                    None
+                } else {
+                    Some(format!("it needs the '{}' extension", ext))
+                }
+            },
+            Requirement::LocalMemSize(bytes) => match device.info(DeviceInfo::LocalMemSize)? {
+                DeviceInfoResult::LocalMemSize(size) if size >= bytes => None,
+                DeviceInfoResult::LocalMemSize(size) => Some(format!("it needs {} bytes of \
+                    local memory but LocalMemSize is {}", bytes, size)),
+                _ => return Err("Unexpected `LocalMemSize` info result.".into()),
+            },
+            Requirement::WorkGroupSize(wgs) => match device.info(DeviceInfo::MaxWorkGroupSize)? {
+                DeviceInfoResult::MaxWorkGroupSize(max) if max >= wgs => None,
+                DeviceInfoResult::MaxWorkGroupSize(max) => Some(format!("it needs work groups \
+                    of {} but MaxWorkGroupSize is {}", wgs, max)),
+                _ => return Err("Unexpected `MaxWorkGroupSize` info result.".into()),
+            },
+            Requirement::ImageSupport => match device.info(DeviceInfo::ImageSupport)? {
+                DeviceInfoResult::ImageSupport(true) => None,
+                DeviceInfoResult::ImageSupport(false) => Some("it needs image support".to_owned()),
+                _ => return Err("Unexpected `ImageSupport` info result.".into()),
+            },
+            Requirement::Allocation { ref name, bytes } => {
+                match device.info(DeviceInfo::MaxMemAllocSize)? {
+                    DeviceInfoResult::MaxMemAllocSize(max) if max >= bytes => None,
+                    DeviceInfoResult::MaxMemAllocSize(max) => Some(format!("'{}' needs {} \
+                        bytes in one allocation but MaxMemAllocSize is {}", name, bytes, max)),
+                    _ => return Err("Unexpected `MaxMemAllocSize` info result.".into()),
+                }
+            },
+        };
+        Ok(reason)
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Requirement::OpenclCVersion(major, minor) => write!(f, "OpenCL C {}.{}", major, minor),
+            Requirement::Extension(ref ext) => write!(f, "{}", ext),
+            Requirement::LocalMemSize(bytes) => write!(f, "{} bytes of local memory", bytes),
+            Requirement::WorkGroupSize(wgs) => write!(f, "work groups of {}", wgs),
+            Requirement::ImageSupport => write!(f, "image support"),
+            Requirement::Allocation { ref name, bytes } => write!(f, "{} bytes for '{}'",
+                bytes, name),
+        }
+    }
+}
+
+
+/// Parses the version from e.g. "OpenCL C 1.2 " or "OpenCL 2.0 (build 12)".
+fn parse_version(info: &str) -> Option<(u16, u16)> {
+    info.split_whitespace().filter_map(|word| {
+        let mut parts = word.splitn(2, '.');
+        match (parts.next()?.parse().ok(), parts.next()?.parse().ok()) {
+            (Some(major), Some(minor)) => Some((major, minor)),
+            _ => None,
+        }
+    }).next()
+}
+
+
+/// A device and kernel which don't go together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unmet {
+    pub device: String,
+    pub kernel: String,
+    pub reasons: Vec<String>,
+}
+
+impl fmt::Display for Unmet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Device '{}' can't run kernel '{}' because {}.", self.device, self.kernel,
+            self.reasons.join(", and "))
+    }
+}
+
+
+/// Everything one kernel needs from a device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Requirements {
+    kernel: String,
+    reqs: Vec<Requirement>,
+}
+
+impl Requirements {
+    pub fn new<S: Into<String>>(kernel: S) -> Requirements {
+        Requirements { kernel: kernel.into(), reqs: Vec::new() }
+    }
+
+    pub fn require(mut self, req: Requirement) -> Requirements {
+        if !self.reqs.contains(&req) {
+            self.reqs.push(req);
+        }
+        self
+    }
+
+    pub fn opencl_c(self, major: u16, minor: u16) -> Requirements {
+        self.require(Requirement::OpenclCVersion(major, minor))
+    }
+
+    pub fn extension<S: Into<String>>(self, ext: S) -> Requirements {
+        self.require(Requirement::Extension(ext.into()))
+    }
+
+    /// Double precision (`double`, `double4`, ...).
+    pub fn fp64(self) -> Requirements {
+        self.extension("cl_khr_fp64")
+    }
+
+    /// Half precision arithmetic (`half`, `half4`, ...).
+    pub fn fp16(self) -> Requirements {
+        self.extension("cl_khr_fp16")
+    }
+
+    pub fn local_mem(self, bytes: u64) -> Requirements {
+        self.require(Requirement::LocalMemSize(bytes))
+    }
+
+    pub fn work_group_size(self, wgs: usize) -> Requirements {
+        self.require(Requirement::WorkGroupSize(wgs))
+    }
+
+    pub fn image_support(self) -> Requirements {
+        self.require(Requirement::ImageSupport)
+    }
+
+    /// A buffer of `len` `T`s (which no device can allocate if its size
+    /// overflows).
+    pub fn allocation<T: OclPrm>(self, name: &str, len: usize) -> Requirements {
+        let bytes = len.checked_mul(mem::size_of::<T>()).map(|bytes| bytes as u64)
+            .unwrap_or(u64::max_value());
+        self.require(Requirement::Allocation { name: name.to_owned(), bytes })
+    }
+
+    pub fn kernel(&self) -> &str {
+        &self.kernel
+    }
+
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.reqs
+    }
+
+    /// Returns everything `device` lacks, or `None` if it can run the kernel.
+    pub fn unmet(&self, device: Device) -> OclResult<Option<Unmet>> {
+        let mut reasons = Vec::new();
+        for req in self.reqs.iter() {
+            if let Some(reason) = req.unmet(device)? {
+                reasons.push(reason);
+            }
+        }
+
+        if reasons.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Unmet { device: device.name()?, kernel: self.kernel.clone(), reasons }))
+        }
+    }
+
+    /// Returns an error explaining what `device` lacks, if anything.
+    pub fn check(&self, device: Device) -> OclResult<()> {
+        match self.unmet(device)? {
+            Some(unmet) => Err(unmet.to_string().into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Requirements {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reqs: Vec<_> = self.reqs.iter().map(|req| req.to_string()).collect();
+        write!(f, "{}: {}", self.kernel, if reqs.is_empty() { "nothing".to_owned() }
+            else { reqs.join(", ") })
+    }
+}
+
+
+/// Checks several kernels at once, reporting every unmet requirement. Kernels
+/// lacking the same things (e.g. sharing a buffer) are reported together.
+pub fn check_all(reqs: &[Requirements], device: Device) -> OclResult<()> {
+    let mut unmet: Vec<(Vec<String>, Unmet)> = Vec::new();
+    for kernel_reqs in reqs {
+        if let Some(kernel_unmet) = kernel_reqs.unmet(device)? {
+            match unmet.iter_mut().find(|(_, u)| u.reasons == kernel_unmet.reasons) {
+                Some((kernels, _)) => kernels.push(kernel_unmet.kernel),
+                None => unmet.push((vec![kernel_unmet.kernel.clone()], kernel_unmet)),
+            }
+        }
+    }
+
+    if unmet.is_empty() {
+        return Ok(());
+    }
+    let errors: Vec<String> = unmet.into_iter().map(|(kernels, unmet)| match kernels.len() {
+        1 => unmet.to_string(),
+        _ => format!("Device '{}' can't run kernels '{}' because {}.", unmet.device,
+            kernels.join("', '"), unmet.reasons.join(", and ")),
+    }).collect();
+    Err(errors.join("\n").into())
+}
@@ -0,0 +1,405 @@
+//! Partitioning devices into sub-devices.
+//!
+//! `ocl` reads `PartitionMaxSubDevices`, `PartitionProperties` and
+//! `PartitionAffinityDomain` but has no way to act on them. `SubDevices`
+//! calls `clCreateSubDevices` directly and hands back `SubDevice`s, which
+//! dereference to ordinary `Device`s usable with `Context`, `Queue` and
+//! `ProQue` like any other:
+//!
+//! ```ignore
+//! let sub_devices = SubDevices::create(device, Partition::Equally(2))?;
+//! let sub_device = sub_devices.devices()[0].clone();
+//! let context = Context::builder()
+//!     .platform(platform)
+//!     .devices(*sub_device)
+//!     .build()?;
+//! ```
+//!
+//! On a CPU device this pins work to groups of cores, and gives a GPU-less
+//! host several devices to test multi-device code with. Each `SubDevice`
+//! holds a reference to its sub-device, released when it is dropped.
+//! `Device` is `Copy` and holds none, so keep the `SubDevice` alive for as
+//! long as copies of its `Device` are used (contexts and queues created on
+//! it keep it alive until they are dropped too).
+//!
+
+extern crate ocl;
+
+use std::ffi::c_void;
+use std::fmt;
+use std::mem;
+use std::ops::Deref;
+use std::ptr;
+use std::str::FromStr;
+use ocl::{Device, ffi};
+use ocl::core::{DeviceId as DeviceIdCore, OpenclVersion};
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::error::Result as OclResult;
+use ocl::ffi::{cl_device_id, cl_device_partition_property, cl_device_affinity_domain, cl_uint};
+
+
+/// A cache level or NUMA node to split a device along.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AffinityDomain {
+    Numa,
+    L4Cache,
+    L3Cache,
+    L2Cache,
+    L1Cache,
+    /// The first of the above which the device can be split along.
+    NextPartitionable,
+}
+
+impl AffinityDomain {
+    pub const ALL: [AffinityDomain; 6] = [AffinityDomain::Numa, AffinityDomain::L4Cache,
+        AffinityDomain::L3Cache, AffinityDomain::L2Cache, AffinityDomain::L1Cache,
+        AffinityDomain::NextPartitionable];
+
+    fn bits(self) -> cl_device_affinity_domain {
+        match self {
+            AffinityDomain::Numa => ffi::CL_DEVICE_AFFINITY_DOMAIN_NUMA,
+            AffinityDomain::L4Cache => ffi::CL_DEVICE_AFFINITY_DOMAIN_L4_CACHE,
+            AffinityDomain::L3Cache => ffi::CL_DEVICE_AFFINITY_DOMAIN_L3_CACHE,
+            AffinityDomain::L2Cache => ffi::CL_DEVICE_AFFINITY_DOMAIN_L2_CACHE,
+            AffinityDomain::L1Cache => ffi::CL_DEVICE_AFFINITY_DOMAIN_L1_CACHE,
+            AffinityDomain::NextPartitionable =>
+                ffi::CL_DEVICE_AFFINITY_DOMAIN_NEXT_PARTITIONABLE,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AffinityDomain::Numa => "numa",
+            AffinityDomain::L4Cache => "l4",
+            AffinityDomain::L3Cache => "l3",
+            AffinityDomain::L2Cache => "l2",
+            AffinityDomain::L1Cache => "l1",
+            AffinityDomain::NextPartitionable => "next",
+        }
+    }
+}
+
+
+/// How to split a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Partition {
+    /// As many sub-devices as possible with this many compute units each.
+    Equally(u32),
+    /// One sub-device per count, with that many compute units.
+    ByCounts(Vec<u32>),
+    ByAffinityDomain(AffinityDomain),
+}
+
+impl Partition {
+    /// The `CL_DEVICE_PARTITION_*` property this partition needs.
+    fn property(&self) -> cl_device_partition_property {
+        match *self {
+            Partition::Equally(_) => ffi::CL_DEVICE_PARTITION_EQUALLY,
+            Partition::ByCounts(_) => ffi::CL_DEVICE_PARTITION_BY_COUNTS,
+            Partition::ByAffinityDomain(_) => ffi::CL_DEVICE_PARTITION_BY_AFFINITY_DOMAIN,
+        }
+    }
+
+    /// The zero terminated property list for `clCreateSubDevices`.
+    fn properties(&self) -> Vec<cl_device_partition_property> {
+        let mut props = vec![self.property()];
+        match *self {
+            Partition::Equally(units) => props.push(units as cl_device_partition_property),
+            Partition::ByCounts(ref counts) => {
+                props.extend(counts.iter().map(|&c| c as cl_device_partition_property));
+                props.push(ffi::CL_DEVICE_PARTITION_BY_COUNTS_LIST_END);
+            },
+            Partition::ByAffinityDomain(domain) => {
+                props.push(domain.bits() as cl_device_partition_property)
+            },
+        }
+        props.push(0);
+        props
+    }
+
+    /// Parses a property list as returned by `CL_DEVICE_PARTITION_TYPE`
+    /// (the terminating zero is optional).
+    fn from_properties(props: &[cl_device_partition_property]) -> OclResult<Partition> {
+        let value = props.get(1).cloned().unwrap_or(0);
+        match props.first().cloned() {
+            Some(ffi::CL_DEVICE_PARTITION_EQUALLY) => Ok(Partition::Equally(value as u32)),
+            Some(ffi::CL_DEVICE_PARTITION_BY_COUNTS) => Ok(Partition::ByCounts(props[1..].iter()
+                .take_while(|&&c| c != ffi::CL_DEVICE_PARTITION_BY_COUNTS_LIST_END)
+                .map(|&c| c as u32).collect())),
+            Some(ffi::CL_DEVICE_PARTITION_BY_AFFINITY_DOMAIN) => AffinityDomain::ALL.iter()
+                .find(|d| d.bits() as cl_device_partition_property == value)
+                .map(|&d| Partition::ByAffinityDomain(d))
+                .ok_or_else(|| format!("Unknown affinity domain {:#x}.", value).into()),
+            _ => Err(format!("Unknown partition properties {:?}.", props).into()),
+        }
+    }
+
+    /// The partition `device` was created with, or `None` if it is not a
+    /// sub-device (devices older than OpenCL 1.2 never are).
+    pub fn of(device: Device) -> OclResult<Option<Partition>> {
+        if device.version()? < OpenclVersion::new(1, 2) {
+            return Ok(None);
+        }
+        let props: Vec<cl_device_partition_property> =
+            raw_info(device, ffi::CL_DEVICE_PARTITION_TYPE)?;
+        match props.first() {
+            None | Some(&0) => Ok(None),
+            Some(_) => Partition::from_properties(&props).map(Some),
+        }
+    }
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Partition::Equally(units) => write!(f, "equally:{}", units),
+            Partition::ByCounts(ref counts) => write!(f, "counts:{}", counts.iter()
+                .map(|c| c.to_string()).collect::<Vec<_>>().join(",")),
+            Partition::ByAffinityDomain(domain) => write!(f, "affinity:{}", domain.name()),
+        }
+    }
+}
+
+/// Parses `equally:N`, `counts:A,B,...` or
+/// `affinity:<numa|l4|l3|l2|l1|next>`.
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Partition, String> {
+        let (kind, value) = match s.find(':') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => (s, ""),
+        };
+        let count = |v: &str| v.trim().parse::<u32>()
+            .map_err(|err| format!("Invalid compute unit count '{}': {}", v, err));
+
+        match kind {
+            "equally" => Ok(Partition::Equally(count(value)?)),
+            "counts" => Ok(Partition::ByCounts(value.split(',').map(count)
+                .collect::<Result<_, _>>()?)),
+            "affinity" => AffinityDomain::ALL.iter().find(|d| d.name() == value)
+                .map(|&d| Partition::ByAffinityDomain(d))
+                .ok_or_else(|| format!("Unknown affinity domain '{}'.", value)),
+            _ => Err(format!("Invalid partition '{}' (expected 'equally:N', 'counts:A,B,...' \
+                or 'affinity:<numa|l4|l3|l2|l1|next>').", s)),
+        }
+    }
+}
+
+
+/// Reads a device info array of `T` directly (`ocl`'s result types for the
+/// partition queries are not easily inspected).
+fn raw_info<T: Copy + Default>(device: Device, param: u32) -> OclResult<Vec<T>> {
+    let raw = device.as_core().as_raw();
+    let mut size = 0usize;
+    let err = unsafe { ffi::clGetDeviceInfo(raw, param, 0, ptr::null_mut(), &mut size) };
+    if err != 0 {
+        return Err(format!("clGetDeviceInfo({:#x}) failed: {}", param, status_name(err)).into());
+    }
+
+    let mut values = vec![T::default(); size / mem::size_of::<T>()];
+    let err = unsafe { ffi::clGetDeviceInfo(raw, param, size,
+        values.as_mut_ptr() as *mut c_void, ptr::null_mut()) };
+    if err != 0 {
+        return Err(format!("clGetDeviceInfo({:#x}) failed: {}", param, status_name(err)).into());
+    }
+    Ok(values)
+}
+
+fn status_name(err: i32) -> String {
+    let name = match err {
+        -5 => "CL_OUT_OF_RESOURCES",
+        -6 => "CL_OUT_OF_HOST_MEMORY",
+        -18 => "CL_DEVICE_PARTITION_FAILED",
+        -19 => "CL_INVALID_DEVICE_PARTITION_COUNT",
+        -30 => "CL_INVALID_VALUE",
+        -33 => "CL_INVALID_DEVICE",
+        _ => return format!("error {}", err),
+    };
+    format!("{} ({})", name, err)
+}
+
+
+/// What a device can be split into.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartitionSupport {
+    pub max_sub_devices: u32,
+    pub equally: bool,
+    pub by_counts: bool,
+    pub affinity_domains: Vec<AffinityDomain>,
+}
+
+impl PartitionSupport {
+    pub fn query(device: Device) -> OclResult<PartitionSupport> {
+        let max_sub_devices = match device.info(DeviceInfo::PartitionMaxSubDevices)? {
+            DeviceInfoResult::PartitionMaxSubDevices(max) => max,
+            _ => return Err("Unexpected `PartitionMaxSubDevices` info result.".into()),
+        };
+        let props: Vec<cl_device_partition_property> =
+            raw_info(device, ffi::CL_DEVICE_PARTITION_PROPERTIES)?;
+        let domains: Vec<cl_device_affinity_domain> =
+            raw_info(device, ffi::CL_DEVICE_PARTITION_AFFINITY_DOMAIN)?;
+        let domain_bits = domains.first().cloned().unwrap_or(0);
+
+        let affinity_domains = if props.contains(&ffi::CL_DEVICE_PARTITION_BY_AFFINITY_DOMAIN) {
+            AffinityDomain::ALL.iter().cloned().filter(|d| domain_bits & d.bits() != 0).collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(PartitionSupport {
+            max_sub_devices,
+            equally: props.contains(&ffi::CL_DEVICE_PARTITION_EQUALLY),
+            by_counts: props.contains(&ffi::CL_DEVICE_PARTITION_BY_COUNTS),
+            affinity_domains,
+        })
+    }
+
+    /// Returns why `partition` is unsupported, or `None` if it is supported.
+    pub fn unsupported(&self, partition: &Partition) -> Option<String> {
+        if self.max_sub_devices < 2 {
+            return Some("the device cannot be partitioned".to_owned());
+        }
+        match *partition {
+            Partition::Equally(_) if !self.equally =>
+                Some("the device cannot be partitioned equally".to_owned()),
+            Partition::ByCounts(_) if !self.by_counts =>
+                Some("the device cannot be partitioned by counts".to_owned()),
+            Partition::ByCounts(ref counts) if counts.len() > self.max_sub_devices as usize =>
+                Some(format!("{} sub-devices requested but PartitionMaxSubDevices is {}",
+                    counts.len(), self.max_sub_devices)),
+            Partition::ByAffinityDomain(domain) if !self.affinity_domains.contains(&domain) =>
+                Some(format!("the device cannot be partitioned along the {:?} affinity domain \
+                    (supported: {:?})", domain, self.affinity_domains)),
+            _ => None,
+        }
+    }
+}
+
+
+/// A sub-device, released when dropped (clones retain it again).
+///
+/// Dereferences to its `Device`, which holds no reference and must not be
+/// used after the last `SubDevice` for it (and every context and queue
+/// created on it) has been dropped.
+#[derive(Debug)]
+pub struct SubDevice {
+    device: Device,
+}
+
+impl SubDevice {
+    /// Takes ownership of a reference to the sub-device `raw`.
+    unsafe fn from_raw(raw: cl_device_id) -> SubDevice {
+        SubDevice { device: Device::from(DeviceIdCore::from_raw(raw)) }
+    }
+}
+
+impl Clone for SubDevice {
+    fn clone(&self) -> SubDevice {
+        unsafe { ffi::clRetainDevice(self.device.as_core().as_raw()); }
+        SubDevice { device: self.device }
+    }
+}
+
+impl Deref for SubDevice {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl Drop for SubDevice {
+    fn drop(&mut self) {
+        unsafe { ffi::clReleaseDevice(self.device.as_core().as_raw()); }
+    }
+}
+
+
+/// The sub-devices of a partitioned device.
+#[derive(Debug)]
+pub struct SubDevices {
+    parent: Device,
+    partition: Partition,
+    devices: Vec<SubDevice>,
+}
+
+impl SubDevices {
+    /// Partitions `device`, checking first that it supports `partition`.
+    pub fn create(device: Device, partition: Partition) -> OclResult<SubDevices> {
+        if let Some(reason) = PartitionSupport::query(device)?.unsupported(&partition) {
+            return Err(format!("Unable to partition device '{}' ({}): {}.", device.name()?,
+                partition, reason).into());
+        }
+
+        let raw = device.as_core().as_raw();
+        let props = partition.properties();
+        let mut count: cl_uint = 0;
+        let err = unsafe {
+            ffi::clCreateSubDevices(raw, props.as_ptr(), 0, ptr::null_mut(), &mut count)
+        };
+        if err != 0 {
+            return Err(format!("Unable to partition device '{}' ({}): {}.", device.name()?,
+                partition, status_name(err)).into());
+        }
+
+        let mut raw_devices: Vec<cl_device_id> = vec![ptr::null_mut(); count as usize];
+        let err = unsafe {
+            ffi::clCreateSubDevices(raw, props.as_ptr(), count, raw_devices.as_mut_ptr(),
+                ptr::null_mut())
+        };
+        if err != 0 {
+            return Err(format!("Unable to partition device '{}' ({}): {}.", device.name()?,
+                partition, status_name(err)).into());
+        }
+
+        let devices = raw_devices.into_iter()
+            .map(|raw| unsafe { SubDevice::from_raw(raw) })
+            .collect();
+
+        Ok(SubDevices { parent: device, partition, devices })
+    }
+
+    pub fn parent(&self) -> Device {
+        self.parent
+    }
+
+    pub fn partition(&self) -> &Partition {
+        &self.partition
+    }
+
+    /// The sub-devices (clone one to keep it alive after this is dropped).
+    pub fn devices(&self) -> &[SubDevice] {
+        &self.devices
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn properties_round_trip() {
+        let partitions = [Partition::Equally(2), Partition::ByCounts(vec![3, 1]),
+            Partition::ByAffinityDomain(AffinityDomain::L2Cache)];
+        for partition in partitions.iter() {
+            assert_eq!(Partition::from_properties(&partition.properties()).unwrap(), *partition);
+        }
+    }
+
+    #[test]
+    fn unknown_properties() {
+        assert!(Partition::from_properties(&[0x7fff, 2, 0]).is_err());
+        assert!(Partition::from_properties(&[ffi::CL_DEVICE_PARTITION_BY_AFFINITY_DOMAIN,
+            0x4000, 0]).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Local work size benchmarking.
+//!
+//! Each candidate is launched once to warm up and then `iters` times back
+//! to back, timed with the host clock between `finish` calls. Kernels are
+//! launched as-is, so they must be safe to run repeatedly with their
+//! current arguments.
+//!
+
+use std::time::Instant;
+use ocl::{Device, Kernel, Queue, SpatialDims};
+use ocl::enums::{DeviceInfo, DeviceInfoResult, KernelWorkGroupInfo, KernelWorkGroupInfoResult};
+use ocl::error::Result as OclResult;
+use crate::TuneResult;
+
+/// Default number of timed launches per candidate.
+pub const DEFAULT_ITERS: usize = 20;
+
+
+/// The outcome of an autotuning run.
+#[derive(Clone, Debug)]
+pub struct Tuning {
+    /// The fastest candidate.
+    pub result: TuneResult,
+    /// Mean time per launch for every candidate (`None` being the driver's
+    /// choice).
+    pub timings: Vec<(Option<usize>, u64)>,
+}
+
+
+/// Benchmarks local work sizes for a kernel.
+pub struct Autotuner<'k> {
+    kernel: &'k Kernel,
+    queue: Queue,
+    device: Device,
+    iters: usize,
+}
+
+impl<'k> Autotuner<'k> {
+    /// Returns an autotuner for `kernel`, which must have a default queue.
+    pub fn new(kernel: &'k Kernel) -> OclResult<Autotuner<'k>> {
+        let queue = kernel.default_queue()
+            .ok_or("Autotuner: The kernel has no default queue.")?.clone();
+
+        Ok(Autotuner {
+            kernel: kernel,
+            device: queue.device(),
+            queue: queue,
+            iters: DEFAULT_ITERS,
+        })
+    }
+
+    /// Sets the number of timed launches per candidate.
+    pub fn iters(mut self, iters: usize) -> Autotuner<'k> {
+        self.iters = iters.max(1);
+        self
+    }
+
+    /// The device being tuned for.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Returns the local work sizes worth trying for `global_work_size`:
+    /// power of two multiples of the preferred multiple, no larger than the
+    /// kernel's and device's limits, which evenly divide the global size.
+    pub fn candidates(&self, global_work_size: usize) -> OclResult<Vec<usize>> {
+        let max_wg_size = match self.kernel.wg_info(self.device, KernelWorkGroupInfo::WorkGroupSize)? {
+            KernelWorkGroupInfoResult::WorkGroupSize(size) => size,
+            _ => return Err("Autotuner: Unexpected kernel work group size info.".into()),
+        };
+        let multiple = match self.kernel.wg_info(self.device,
+                KernelWorkGroupInfo::PreferredWorkGroupSizeMultiple)? {
+            KernelWorkGroupInfoResult::PreferredWorkGroupSizeMultiple(multiple) => multiple.max(1),
+            _ => 1,
+        };
+        let max_item_size = match self.device.info(DeviceInfo::MaxWorkItemSizes)? {
+            DeviceInfoResult::MaxWorkItemSizes(sizes) => sizes.first().cloned().unwrap_or(1),
+            _ => 1,
+        };
+        let max = max_wg_size.min(max_item_size);
+
+        let mut candidates = Vec::new();
+        let mut lws = multiple;
+        while lws <= max {
+            if global_work_size % lws == 0 { candidates.push(lws); }
+            lws *= 2;
+        }
+        // Sizes below the preferred multiple only if nothing else fits:
+        if candidates.is_empty() {
+            let mut lws = multiple / 2;
+            while lws >= 1 {
+                if lws <= max && global_work_size % lws == 0 { candidates.push(lws); }
+                lws /= 2;
+            }
+        }
+        Ok(candidates)
+    }
+
+    /// Times every candidate for `global_work_size` and returns the fastest.
+    pub fn run(&self, global_work_size: usize) -> OclResult<Tuning> {
+        let driver_avg_ns = self.time(global_work_size, None)?;
+        let mut timings = vec![(None, driver_avg_ns)];
+
+        for lws in self.candidates(global_work_size)? {
+            timings.push((Some(lws), self.time(global_work_size, Some(lws))?));
+        }
+
+        Ok(Tuning {
+            result: fastest(global_work_size, &timings),
+            timings: timings,
+        })
+    }
+
+    /// Returns the mean time per launch in nanoseconds, letting the driver
+    /// choose the local work size if `lws` is `None`.
+    pub fn time(&self, global_work_size: usize, lws: Option<usize>) -> OclResult<u64> {
+        let lws = lws.map(SpatialDims::One).unwrap_or(SpatialDims::Unspecified);
+        let enq = || -> OclResult<()> {
+            unsafe {
+                self.kernel.cmd()
+                    .queue(&self.queue)
+                    .global_work_size(global_work_size)
+                    .local_work_size(lws)
+                    .enq()
+            }
+        };
+
+        enq()?;
+        self.queue.finish()?;
+
+        let start = Instant::now();
+        for _ in 0..self.iters {
+            enq()?;
+        }
+        self.queue.finish()?;
+
+        let elapsed = start.elapsed();
+        let elapsed_ns = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+        Ok(elapsed_ns / self.iters as u64)
+    }
+}
+
+/// Picks the fastest of `timings`, the first of which is the driver's choice
+/// (which wins ties, so that a local size is only stored if it helps).
+fn fastest(global_work_size: usize, timings: &[(Option<usize>, u64)]) -> TuneResult {
+    let driver_avg_ns = timings.iter().find(|&&(lws, _)| lws.is_none())
+        .map(|&(_, ns)| ns).unwrap_or(u64::max_value());
+    let (local_work_size, avg_ns) = timings.iter().cloned()
+        .fold((None, driver_avg_ns), |best, (lws, ns)| if ns < best.1 { (lws, ns) } else { best });
+
+    TuneResult {
+        global_work_size: global_work_size,
+        local_work_size: local_work_size,
+        avg_ns: avg_ns,
+        driver_avg_ns: driver_avg_ns,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::fastest;
+
+    #[test]
+    fn driver_choice_wins() {
+        let result = fastest(1024, &[(None, 100), (Some(64), 120), (Some(128), 150)]);
+        assert_eq!(result.local_work_size, None);
+        assert_eq!(result.avg_ns, 100);
+        assert_eq!(result.speedup(), 1.0);
+    }
+
+    #[test]
+    fn explicit_size_wins() {
+        let result = fastest(1024, &[(None, 100), (Some(64), 80), (Some(128), 90)]);
+        assert_eq!(result.local_work_size, Some(64));
+        assert_eq!(result.avg_ns, 80);
+        assert!(result.speedup() > 1.0);
+    }
+
+    #[test]
+    fn ties_go_to_the_driver() {
+        let result = fastest(1024, &[(None, 100), (Some(64), 100)]);
+        assert_eq!(result.local_work_size, None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Work-group size autotuning and a per-device tuning database.
+//!
+//! An `Autotuner` launches a kernel with every sensible local work size for
+//! a given global work size, bounded by the kernel's `WorkGroupSize` and
+//! `PreferredWorkGroupSizeMultiple` and the device's `MaxWorkItemSizes`,
+//! and reports the fastest. Results are stored in a `TuningDb`, a JSON file
+//! keyed by device (vendor, name and driver version, plus the partition and
+//! compute unit count of a sub-device) and kernel name, which kernels
+//! consult at launch:
+//!
+//! ```ignore
+//! let db = TuningDb::load_default()?;
+//!
+//! let kernel = Kernel::builder()
+//!     .name("multiply_by_scalar")
+//!     .global_work_size(work_size)
+//!     .local_work_size(db.lws(device, "multiply_by_scalar", work_size))
+//!     ...
+//! ```
+//!
+//! Kernels without a tuned entry, where the driver's choice was fastest, or
+//! whose global size the tuned local size does not evenly divide are left to
+//! the driver.
+//!
+
+extern crate ocl;
+extern crate serde;
+extern crate serde_json;
+extern crate subdevice;
+
+mod autotune;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use ocl::{Device, SpatialDims};
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::error::Result as OclResult;
+use subdevice::Partition;
+
+pub use crate::autotune::{Autotuner, Tuning};
+
+/// Environment variable overriding `DEFAULT_DB_PATH`.
+pub const DB_PATH_VAR: &str = "OCL_TUNING_DB";
+
+/// Tuning database location used when `DB_PATH_VAR` is not set.
+pub const DEFAULT_DB_PATH: &str = "tuning_db.json";
+
+
+/// The fastest local work size found for one kernel and global work size.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TuneResult {
+    pub global_work_size: usize,
+    /// `None` if the driver's choice was fastest.
+    pub local_work_size: Option<usize>,
+    /// Mean time per launch with `local_work_size`.
+    pub avg_ns: u64,
+    /// Mean time per launch when the driver picks the local work size.
+    pub driver_avg_ns: u64,
+}
+
+impl TuneResult {
+    /// Speedup over letting the driver choose.
+    pub fn speedup(&self) -> f64 {
+        self.driver_avg_ns as f64 / self.avg_ns.max(1) as f64
+    }
+}
+
+
+/// Tuned kernels for a single device.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct DeviceEntry {
+    kernels: BTreeMap<String, Vec<TuneResult>>,
+}
+
+
+/// Returns the key a device's results are stored under.
+pub fn device_key(device: Device) -> OclResult<String> {
+    let driver = match device.info(DeviceInfo::DriverVersion)? {
+        DeviceInfoResult::DriverVersion(version) => version,
+        other => other.to_string(),
+    };
+    let key = format!("{} | {} | {}", device.vendor()?, device.name()?, driver);
+
+    // Sub-devices share the above with their parent and each other:
+    match Partition::of(device)? {
+        Some(partition) => {
+            let units = match device.info(DeviceInfo::MaxComputeUnits)? {
+                DeviceInfoResult::MaxComputeUnits(units) => units.to_string(),
+                other => other.to_string(),
+            };
+            Ok(format!("{} | {} | {} compute units", key, partition, units))
+        },
+        None => Ok(key),
+    }
+}
+
+
+/// Tuned local work sizes, by device and kernel name.
+#[derive(Clone, Debug)]
+pub struct TuningDb {
+    path: PathBuf,
+    devices: BTreeMap<String, DeviceEntry>,
+}
+
+impl TuningDb {
+    /// The path given by `OCL_TUNING_DB`, or `DEFAULT_DB_PATH`.
+    pub fn default_path() -> PathBuf {
+        env::var_os(DB_PATH_VAR).map(PathBuf::from).unwrap_or_else(|| DEFAULT_DB_PATH.into())
+    }
+
+    /// Loads the database at `path`. A missing file is an empty database.
+    pub fn load<P: AsRef<Path>>(path: P) -> OclResult<TuningDb> {
+        let path = path.as_ref();
+        let devices = if path.exists() {
+            let file = File::open(path).map_err(|err| format!("Unable to open tuning \
+                database '{}': {}", path.display(), err))?;
+            serde_json::from_reader(BufReader::new(file)).map_err(|err| format!("Invalid \
+                tuning database '{}': {}", path.display(), err))?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(TuningDb { path: path.to_owned(), devices: devices })
+    }
+
+    /// Loads the database at `TuningDb::default_path()`.
+    pub fn load_default() -> OclResult<TuningDb> {
+        TuningDb::load(TuningDb::default_path())
+    }
+
+    /// Like `load_default`, but warns and returns an empty database (leaving
+    /// every local work size to the driver) if the file can't be read.
+    pub fn load_default_or_empty() -> TuningDb {
+        let path = TuningDb::default_path();
+        TuningDb::load(&path).unwrap_or_else(|err| {
+            eprintln!("Warning: {} Using the driver's local work sizes.", err);
+            TuningDb { path: path, devices: BTreeMap::new() }
+        })
+    }
+
+    /// Where this database is saved.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the database back to its file.
+    pub fn save(&self) -> OclResult<()> {
+        let write = || -> std::io::Result<()> {
+            let mut out = BufWriter::new(File::create(&self.path)?);
+            serde_json::to_writer_pretty(&mut out, &self.devices)?;
+            out.write_all(b"\n")?;
+            out.flush()
+        };
+
+        write().map_err(|err| format!("Unable to write tuning database '{}': {}",
+            self.path.display(), err).into())
+    }
+
+    /// Stores `result`, replacing any previous result for the same device,
+    /// kernel and global work size.
+    pub fn insert(&mut self, device: Device, kernel_name: &str, result: TuneResult)
+            -> OclResult<()>
+    {
+        let results = self.devices.entry(device_key(device)?).or_insert_with(Default::default)
+            .kernels.entry(kernel_name.to_owned()).or_insert_with(Vec::new);
+
+        results.retain(|r| r.global_work_size != result.global_work_size);
+        results.push(result);
+        results.sort_by_key(|r| r.global_work_size);
+        Ok(())
+    }
+
+    /// Returns the result tuned for exactly `global_work_size`.
+    pub fn get(&self, device: Device, kernel_name: &str, global_work_size: usize)
+            -> Option<&TuneResult>
+    {
+        self.results(device, kernel_name)?.iter()
+            .find(|r| r.global_work_size == global_work_size)
+    }
+
+    /// Returns the local work size to launch `kernel_name` with, or `None`
+    /// to leave it to the driver.
+    ///
+    /// An exact match is preferred. Otherwise the result tuned for the
+    /// nearest global work size (by ratio) whose local size evenly divides
+    /// `global_work_size` (or where the driver's choice won) is used.
+    pub fn local_work_size(&self, device: Device, kernel_name: &str, global_work_size: usize)
+            -> Option<usize>
+    {
+        nearest(self.results(device, kernel_name)?, global_work_size)
+    }
+
+    /// Like `local_work_size` but returns `SpatialDims::Unspecified` (leaving
+    /// the choice to the driver) when nothing applies, for passing straight
+    /// to a kernel builder or command.
+    pub fn lws(&self, device: Device, kernel_name: &str, global_work_size: usize) -> SpatialDims {
+        match self.local_work_size(device, kernel_name, global_work_size) {
+            Some(lws) => SpatialDims::One(lws),
+            None => SpatialDims::Unspecified,
+        }
+    }
+
+    /// Number of tuned kernel/size pairs stored for `device`.
+    pub fn len(&self, device: Device) -> usize {
+        device_key(device).ok().and_then(|key| self.devices.get(&key))
+            .map(|entry| entry.kernels.values().map(|results| results.len()).sum())
+            .unwrap_or(0)
+    }
+
+    fn results(&self, device: Device, kernel_name: &str) -> Option<&Vec<TuneResult>> {
+        let key = device_key(device).ok()?;
+        self.devices.get(&key)?.kernels.get(kernel_name)
+    }
+}
+
+/// The local work size of the result nearest to `global_work_size` which
+/// applies to it.
+fn nearest(results: &[TuneResult], global_work_size: usize) -> Option<usize> {
+    let distance = |r: &TuneResult| {
+        (r.global_work_size.max(1) as f64 / global_work_size.max(1) as f64).ln().abs()
+    };
+
+    results.iter()
+        .filter(|r| match r.local_work_size {
+            Some(lws) => lws > 0 && global_work_size % lws == 0,
+            None => true,
+        })
+        .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
+        .and_then(|r| r.local_work_size)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{nearest, TuneResult};
+
+    fn result(global_work_size: usize, local_work_size: Option<usize>) -> TuneResult {
+        TuneResult { global_work_size, local_work_size, avg_ns: 1, driver_avg_ns: 1 }
+    }
+
+    #[test]
+    fn driver_choice_is_kept_unspecified() {
+        let results = [result(1024, None), result(4096, Some(64))];
+        assert_eq!(nearest(&results, 1024), None);
+        assert_eq!(nearest(&results, 4096), Some(64));
+    }
+
+    #[test]
+    fn sizes_which_do_not_divide_are_skipped() {
+        let results = [result(1024, Some(256)), result(4096, Some(64))];
+        assert_eq!(nearest(&results, 1088), Some(64));
+    }
+}
@@ -0,0 +1,518 @@
+//! Verification of device results against host references.
+//!
+//! A `Verifier` compares every lane of every element (so each component of a
+//! `Float4` or `Int4` is checked separately) and, rather than bailing on the
+//! first difference, collects a `Report` holding mismatch counts, the first
+//! few mismatching indices, the largest errors and a histogram of ULP
+//! distances.
+//!
+//! ```ignore
+//! let report = Verifier::<Float4>::new()
+//!     .label("Task [3] (complex)")
+//!     .ulps(4)
+//!     .compare_splat(&data, expected);
+//!
+//! if !report.is_ok() { println!("{}", report); }
+//! ```
+//!
+//! Every lane defaults to an exact match (with `0.0 == -0.0` and identical
+//! NaNs matching); floating point results which may round differently on the
+//! device opt into a tolerance with `ulps`, `abs` or `rel`.
+//!
+
+#[cfg(feature = "ocl")]
+extern crate ocl;
+
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::slice;
+
+/// Default number of mismatches recorded individually in a report.
+pub const DEFAULT_MAX_RECORDED: usize = 10;
+
+
+/// The tolerances a lane is checked against. A lane passes if it is equal
+/// to its reference or is within *any* of the non-zero tolerances.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tolerance {
+    /// Maximum absolute error.
+    pub abs: f64,
+    /// Maximum error relative to the larger magnitude of the two values.
+    pub rel: f64,
+    /// Maximum distance in units in the last place (the absolute difference
+    /// for integers).
+    pub ulps: u64,
+}
+
+impl Tolerance {
+    /// Only exact matches pass.
+    pub fn exact() -> Tolerance {
+        Tolerance::default()
+    }
+
+    pub fn abs(abs: f64) -> Tolerance {
+        Tolerance { abs, ..Tolerance::default() }
+    }
+
+    pub fn rel(rel: f64) -> Tolerance {
+        Tolerance { rel, ..Tolerance::default() }
+    }
+
+    pub fn ulps(ulps: u64) -> Tolerance {
+        Tolerance { ulps, ..Tolerance::default() }
+    }
+
+    /// Returns `true` if an error of the given size is acceptable.
+    pub fn accepts(&self, error: &LaneError) -> bool {
+        error.abs == 0.0 ||
+            (self.abs > 0.0 && error.abs <= self.abs) ||
+            (self.rel > 0.0 && error.rel <= self.rel) ||
+            (self.ulps > 0 && error.ulps <= self.ulps)
+    }
+}
+
+impl fmt::Display for Tolerance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Tolerance::exact() { return write!(f, "exact"); }
+        let mut parts = Vec::with_capacity(3);
+        if self.abs > 0.0 { parts.push(format!("abs <= {:e}", self.abs)); }
+        if self.rel > 0.0 { parts.push(format!("rel <= {:e}", self.rel)); }
+        if self.ulps > 0 { parts.push(format!("ulps <= {}", self.ulps)); }
+        write!(f, "{}", parts.join(" or "))
+    }
+}
+
+
+/// The difference between a lane and its reference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LaneError {
+    pub abs: f64,
+    pub rel: f64,
+    pub ulps: u64,
+}
+
+impl LaneError {
+    fn none() -> LaneError {
+        LaneError { abs: 0.0, rel: 0.0, ulps: 0 }
+    }
+
+    fn from_abs(abs: f64, magnitude: f64, ulps: u64) -> LaneError {
+        let rel = if magnitude == 0.0 { if abs == 0.0 { 0.0 } else { std::f64::INFINITY } }
+            else { abs / magnitude };
+        LaneError { abs, rel, ulps }
+    }
+}
+
+
+/// A single scalar component of an element.
+pub trait Lane: Copy + Debug {
+    /// The tolerance used when none is specified.
+    fn default_tolerance() -> Tolerance;
+
+    /// Returns the error of `self` relative to `expected`.
+    fn error(self, expected: Self) -> LaneError;
+}
+
+macro_rules! impl_float_lane {
+    ($ty:ty, $signed:ty) => {
+        impl Lane for $ty {
+            fn default_tolerance() -> Tolerance {
+                Tolerance::exact()
+            }
+
+            fn error(self, expected: $ty) -> LaneError {
+                // Identical bits (including NaNs) and `0.0 == -0.0` are matches:
+                if self.to_bits() == expected.to_bits() || self == expected {
+                    return LaneError::none();
+                }
+                if self.is_nan() || expected.is_nan() {
+                    return LaneError { abs: std::f64::NAN, rel: std::f64::NAN, ulps: std::u64::MAX };
+                }
+
+                // Map the bit patterns onto a monotonic integer line:
+                fn ordered(val: $ty) -> i128 {
+                    let bits = val.to_bits() as $signed as i128;
+                    if bits < 0 { <$signed>::min_value() as i128 - bits } else { bits }
+                }
+                let ulps = (ordered(self) - ordered(expected)).abs();
+                let ulps = if ulps > std::u64::MAX as i128 { std::u64::MAX } else { ulps as u64 };
+
+                let abs = (self as f64 - expected as f64).abs();
+                LaneError::from_abs(abs, (self as f64).abs().max((expected as f64).abs()), ulps)
+            }
+        }
+    };
+}
+
+impl_float_lane!(f32, i32);
+impl_float_lane!(f64, i64);
+
+macro_rules! impl_int_lane {
+    ($($ty:ty),*) => {
+        $(
+            impl Lane for $ty {
+                fn default_tolerance() -> Tolerance {
+                    Tolerance::exact()
+                }
+
+                fn error(self, expected: $ty) -> LaneError {
+                    if self == expected { return LaneError::none(); }
+                    let diff = (self as i128 - expected as i128).abs();
+                    let magnitude = (self as i128).abs().max((expected as i128).abs());
+                    LaneError::from_abs(diff as f64, magnitude as f64, diff as u64)
+                }
+            }
+        )*
+    };
+}
+
+impl_int_lane!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+
+/// A value made up of one or more lanes.
+pub trait Element: Copy + Debug {
+    type Lane: Lane;
+
+    fn lanes(&self) -> &[Self::Lane];
+}
+
+macro_rules! impl_scalar_element {
+    ($($ty:ty),*) => {
+        $(
+            impl Element for $ty {
+                type Lane = $ty;
+
+                fn lanes(&self) -> &[$ty] {
+                    slice::from_ref(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_element!(f32, f64, i8, i16, i32, i64, u8, u16, u32, u64);
+
+#[cfg(feature = "ocl")]
+macro_rules! impl_vector_element {
+    ($($ty:ident => $lane:ty),* $(,)*) => {
+        $(
+            impl Element for ocl::prm::$ty {
+                type Lane = $lane;
+
+                fn lanes(&self) -> &[$lane] {
+                    &self[..]
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "ocl")]
+impl_vector_element!(
+    Float2 => f32, Float4 => f32, Float8 => f32, Float16 => f32,
+    Double2 => f64, Double4 => f64, Double8 => f64, Double16 => f64,
+    Int2 => i32, Int4 => i32, Int8 => i32, Int16 => i32,
+    Uint2 => u32, Uint4 => u32, Uint8 => u32, Uint16 => u32,
+    Long2 => i64, Long4 => i64, Long8 => i64, Long16 => i64,
+    Ulong2 => u64, Ulong4 => u64, Ulong8 => u64, Ulong16 => u64,
+    Char2 => i8, Char4 => i8, Char8 => i8, Char16 => i8,
+    Uchar2 => u8, Uchar4 => u8, Uchar8 => u8, Uchar16 => u8,
+    Short2 => i16, Short4 => i16, Short8 => i16, Short16 => i16,
+    Ushort2 => u16, Ushort4 => u16, Ushort8 => u16, Ushort16 => u16,
+);
+
+
+/// A single mismatching lane.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    pub index: usize,
+    pub lane: usize,
+    pub actual: String,
+    pub expected: String,
+    pub error: LaneError,
+}
+
+/// Counts of mismatching lanes bucketed by ULP distance: bucket `k` holds
+/// distances in `[2^k, 2^(k+1))`.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    pub buckets: Vec<usize>,
+}
+
+impl Histogram {
+    fn add(&mut self, ulps: u64) {
+        let bucket = 63 - ulps.max(1).leading_zeros() as usize;
+        if self.buckets.len() <= bucket { self.buckets.resize(bucket + 1, 0); }
+        self.buckets[bucket] += 1;
+    }
+}
+
+/// The outcome of a comparison.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub label: String,
+    pub tolerance: Tolerance,
+    /// Number of elements compared.
+    pub elements: usize,
+    /// Number of lanes compared.
+    pub lanes: usize,
+    pub mismatched_elements: usize,
+    pub mismatched_lanes: usize,
+    /// `Some((actual, expected))` if the lengths differ.
+    pub length_mismatch: Option<(usize, usize)>,
+    /// The first mismatches, in index order.
+    pub first_mismatches: Vec<Mismatch>,
+    /// The mismatch with the largest absolute error.
+    pub max_error: Option<Mismatch>,
+    pub max_rel_error: f64,
+    pub max_ulps: u64,
+    pub histogram: Histogram,
+}
+
+impl Report {
+    fn new(label: String, tolerance: Tolerance) -> Report {
+        Report {
+            label, tolerance,
+            elements: 0,
+            lanes: 0,
+            mismatched_elements: 0,
+            mismatched_lanes: 0,
+            length_mismatch: None,
+            first_mismatches: Vec::new(),
+            max_error: None,
+            max_rel_error: 0.0,
+            max_ulps: 0,
+            histogram: Histogram::default(),
+        }
+    }
+
+    /// Returns `true` if every lane was within tolerance.
+    pub fn is_ok(&self) -> bool {
+        self.mismatched_lanes == 0 && self.length_mismatch.is_none()
+    }
+
+    /// Number of elements that matched.
+    pub fn matched_elements(&self) -> usize {
+        self.elements - self.mismatched_elements
+    }
+
+    /// Returns `Ok(self)` if every lane was within tolerance.
+    pub fn into_result(self) -> Result<Report, Report> {
+        if self.is_ok() { Ok(self) } else { Err(self) }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = if self.label.is_empty() { "Verify" } else { &self.label };
+
+        if self.is_ok() {
+            return write!(f, "{}: {} values correct (tolerance: {}).", label, self.elements,
+                self.tolerance);
+        }
+
+        write!(f, "{}: {} of {} values ({} of {} lanes) mismatched (tolerance: {}).", label,
+            self.mismatched_elements, self.elements, self.mismatched_lanes, self.lanes,
+            self.tolerance)?;
+
+        if let Some((actual, expected)) = self.length_mismatch {
+            write!(f, "\n    Length mismatch: {} != {}", actual, expected)?;
+        }
+        if let Some(ref max) = self.max_error {
+            write!(f, "\n    Max error: {:e} @ [{}].{} | Max relative error: {:e} | Max ULPs: {}",
+                max.error.abs, max.index, max.lane, self.max_rel_error, self.max_ulps)?;
+        }
+        if !self.first_mismatches.is_empty() {
+            write!(f, "\n    First mismatches:")?;
+            for m in self.first_mismatches.iter() {
+                write!(f, "\n        [{}].{}: {} != {} (abs: {:e}, ulps: {})", m.index, m.lane,
+                    m.actual, m.expected, m.error.abs, m.error.ulps)?;
+            }
+        }
+        if !self.histogram.buckets.is_empty() {
+            write!(f, "\n    ULP histogram:")?;
+            for (k, &count) in self.histogram.buckets.iter().enumerate() {
+                if count > 0 {
+                    write!(f, "\n        [2^{}, 2^{}): {}", k, k + 1, count)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Compares device output against host references.
+#[derive(Clone, Debug)]
+pub struct Verifier<T: Element> {
+    label: String,
+    tolerance: Tolerance,
+    max_recorded: usize,
+    _element: PhantomData<T>,
+}
+
+impl<T: Element> Verifier<T> {
+    /// Returns a verifier using the default tolerance for `T`'s lane type.
+    pub fn new() -> Verifier<T> {
+        Verifier {
+            label: String::new(),
+            tolerance: T::Lane::default_tolerance(),
+            max_recorded: DEFAULT_MAX_RECORDED,
+            _element: PhantomData,
+        }
+    }
+
+    /// Sets the label used when printing reports.
+    pub fn label<S: Into<String>>(mut self, label: S) -> Verifier<T> {
+        self.label = label.into();
+        self
+    }
+
+    /// Replaces all tolerances.
+    pub fn tolerance(mut self, tolerance: Tolerance) -> Verifier<T> {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets the absolute tolerance.
+    pub fn abs(mut self, abs: f64) -> Verifier<T> {
+        self.tolerance.abs = abs;
+        self
+    }
+
+    /// Sets the relative tolerance.
+    pub fn rel(mut self, rel: f64) -> Verifier<T> {
+        self.tolerance.rel = rel;
+        self
+    }
+
+    /// Sets the ULP tolerance.
+    pub fn ulps(mut self, ulps: u64) -> Verifier<T> {
+        self.tolerance.ulps = ulps;
+        self
+    }
+
+    /// Sets how many mismatches are recorded individually.
+    pub fn max_recorded(mut self, max_recorded: usize) -> Verifier<T> {
+        self.max_recorded = max_recorded;
+        self
+    }
+
+    /// Compares `actual` against `expected` element by element.
+    pub fn compare(&self, actual: &[T], expected: &[T]) -> Report {
+        let mut report = self.compare_fn(&actual[..actual.len().min(expected.len())],
+            |idx| expected[idx]);
+        if actual.len() != expected.len() {
+            report.length_mismatch = Some((actual.len(), expected.len()));
+        }
+        report
+    }
+
+    /// Compares every element of `actual` against the same value.
+    pub fn compare_splat(&self, actual: &[T], expected: T) -> Report {
+        self.compare_fn(actual, |_| expected)
+    }
+
+    /// Compares each element of `actual` against `expected(index)`.
+    pub fn compare_fn<F>(&self, actual: &[T], mut expected: F) -> Report
+            where F: FnMut(usize) -> T
+    {
+        let mut report = Report::new(self.label.clone(), self.tolerance);
+        report.elements = actual.len();
+
+        for (idx, act) in actual.iter().enumerate() {
+            let exp = expected(idx);
+            let mut element_ok = true;
+
+            for (lane_idx, (&a, &e)) in act.lanes().iter().zip(exp.lanes().iter()).enumerate() {
+                report.lanes += 1;
+                let error = a.error(e);
+                if self.tolerance.accepts(&error) { continue; }
+
+                element_ok = false;
+                report.mismatched_lanes += 1;
+                report.histogram.add(error.ulps);
+                report.max_ulps = report.max_ulps.max(error.ulps);
+                if error.rel > report.max_rel_error || error.rel.is_nan() {
+                    report.max_rel_error = error.rel;
+                }
+
+                let is_max = match report.max_error {
+                    Some(ref max) => error.abs > max.error.abs,
+                    None => true,
+                };
+                let is_recorded = report.first_mismatches.len() < self.max_recorded;
+
+                if is_max || is_recorded {
+                    let mismatch = Mismatch {
+                        index: idx,
+                        lane: lane_idx,
+                        actual: format!("{:?}", a),
+                        expected: format!("{:?}", e),
+                        error: error,
+                    };
+                    if is_max { report.max_error = Some(mismatch.clone()); }
+                    if is_recorded { report.first_mismatches.push(mismatch); }
+                }
+            }
+
+            if !element_ok { report.mismatched_elements += 1; }
+        }
+
+        report
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next_up(val: f32) -> f32 {
+        f32::from_bits(val.to_bits() + 1)
+    }
+
+    #[test]
+    fn signed_zeros_match() {
+        assert_eq!(0.0f32.error(-0.0), LaneError::none());
+        assert_eq!((-0.0f64).error(0.0), LaneError::none());
+    }
+
+    #[test]
+    fn nans() {
+        assert_eq!(std::f32::NAN.error(std::f32::NAN), LaneError::none());
+        let error = std::f32::NAN.error(1.0);
+        assert_eq!(error.ulps, std::u64::MAX);
+        assert!(!Tolerance::ulps(1000).accepts(&error));
+        assert!(!Tolerance::abs(1.0).accepts(&1.0f64.error(std::f64::NAN)));
+    }
+
+    #[test]
+    fn adjacent_values_are_one_ulp_apart() {
+        assert_eq!(next_up(1.0).error(1.0).ulps, 1);
+        assert_eq!(1.0f32.error(next_up(1.0)).ulps, 1);
+        assert_eq!(f64::from_bits(1.0f64.to_bits() + 3).error(1.0).ulps, 3);
+    }
+
+    #[test]
+    fn sign_crossings() {
+        let tiny = f32::from_bits(1);
+        // Both zeros are at the same point of the line, between -tiny and tiny:
+        assert_eq!(tiny.error(0.0).ulps, 1);
+        assert_eq!(tiny.error(-0.0).ulps, 1);
+        assert_eq!(tiny.error(-tiny).ulps, 2);
+        assert_eq!((-tiny).error(tiny).ulps, 2);
+        let tiny = f64::from_bits(1);
+        assert_eq!(tiny.error(-tiny).ulps, 2);
+        // The whole range still fits:
+        assert_eq!(std::f64::MAX.error(std::f64::MIN).ulps, 2 * 0x7fef_ffff_ffff_ffff);
+    }
+
+    #[test]
+    fn floats_default_to_exact() {
+        let actual = [1.0f32, next_up(2.0)];
+        assert!(!Verifier::<f32>::new().compare(&actual, &[1.0, 2.0]).is_ok());
+        assert!(Verifier::<f32>::new().ulps(1).compare(&actual, &[1.0, 2.0]).is_ok());
+        assert!(Verifier::<f32>::new().compare(&[0.0, -0.0], &[-0.0, 0.0]).is_ok());
+    }
+}